@@ -0,0 +1,63 @@
+/**
+ * Compares how long the frame waiter worker and the former `setTimeout`
+ * polling of `Stream.next({ delay })` take to deliver the next frame of a
+ * real camera.
+ *
+ * Each iteration starts right after a frame was captured and ends once the
+ * next one is, so every method takes one frame interval on average plus the
+ * time it needs to notice the frame: the difference between their averages is
+ * the latency saved. Without a camera the benchmarks are skipped.
+ *
+ * ```sh
+ * deno bench -A bench/frame_latency_bench.ts
+ * ```
+ *
+ * @module
+ */
+
+import { OpenPnp } from "../src/openpnp.ts";
+
+const backend = await openBackend();
+const streamId = backend?.openStream(0, 0) ?? -1;
+const format = backend?.getFormatInfo(0, 0);
+const buffer = new Uint8Array(format ? format.width * format.height * 3 : 0);
+
+// the worker would keep the process alive
+globalThis.addEventListener("unload", () => backend?.releaseContext());
+
+for (const delay of [1, 10]) {
+  Deno.bench({
+    name: `setTimeout polling, ${delay} ms delay`,
+    group: "next frame",
+    ignore: !backend,
+    async fn() {
+      while (!backend!.hasNewFrame(streamId)) {
+        await new Promise((resolve) => setTimeout(resolve, delay));
+      }
+      backend!.captureFrame(streamId, buffer);
+    },
+  });
+}
+
+Deno.bench({
+  name: "frame waiter worker",
+  group: "next frame",
+  baseline: true,
+  ignore: !backend,
+  async fn() {
+    await backend!.waitForNewFrame(streamId);
+    backend!.captureFrame(streamId, buffer);
+  },
+});
+
+async function openBackend(): Promise<OpenPnp | undefined> {
+  try {
+    await OpenPnp.loadLibrary({ download: false });
+    const backend = new OpenPnp();
+    if (backend.getDeviceCount() > 0) return backend;
+    backend.releaseContext();
+  } catch {
+    // no library, the benchmarks are skipped
+  }
+  return undefined;
+}
@@ -0,0 +1,77 @@
+/**
+ * Measures how long `encodeJPEG` takes on a 1280x720 frame, for each chroma
+ * subsampling and in grayscale, to tell which frame rates a live preview can
+ * sustain.
+ *
+ * The frame mixes smooth gradients with some sensor-like noise, so that the
+ * entropy coding does a realistic amount of work.
+ *
+ * ```sh
+ * deno bench -A bench/jpeg_bench.ts
+ * ```
+ *
+ * @module
+ */
+
+import { Frame } from "../src/frame.ts";
+import { encodeJPEG } from "../src/jpeg.ts";
+
+const WIDTH = 1280;
+const HEIGHT = 720;
+
+const frame = new Frame({
+  data: syntheticImage(),
+  width: WIDTH,
+  height: HEIGHT,
+  pixelFormat: "rgb24",
+  timestamp: 0,
+  sequence: 0,
+});
+
+for (const quality of [70, 90]) {
+  Deno.bench({
+    name: `4:2:0, quality ${quality}`,
+    group: `1280x720 quality ${quality}`,
+    baseline: true,
+    fn() {
+      encodeJPEG(frame, { quality });
+    },
+  });
+
+  Deno.bench({
+    name: `4:4:4, quality ${quality}`,
+    group: `1280x720 quality ${quality}`,
+    fn() {
+      encodeJPEG(frame, { quality, subsampling: "4:4:4" });
+    },
+  });
+
+  Deno.bench({
+    name: `grayscale, quality ${quality}`,
+    group: `1280x720 quality ${quality}`,
+    fn() {
+      encodeJPEG(frame, { quality, grayscale: true });
+    },
+  });
+}
+
+function syntheticImage(): Uint8Array {
+  const data = new Uint8Array(WIDTH * HEIGHT * 3);
+  let seed = 1;
+  for (let y = 0; y < HEIGHT; y++) {
+    for (let x = 0; x < WIDTH; x++) {
+      const i = (y * WIDTH + x) * 3;
+      seed = (Math.imul(seed, 1103515245) + 12345) >>> 0;
+      const noise = ((seed >>> 16) & 15) - 8;
+      const wave = 128 + 90 * Math.sin(x / 40) * Math.cos(y / 30);
+      data[i] = clamp(wave + noise);
+      data[i + 1] = clamp(x * 255 / WIDTH + noise);
+      data[i + 2] = clamp(y * 255 / HEIGHT + noise);
+    }
+  }
+  return data;
+}
+
+function clamp(value: number): number {
+  return Math.max(0, Math.min(255, value));
+}
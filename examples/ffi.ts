@@ -6,10 +6,14 @@ import {
   CAPPROPID_WHITEBALANCE,
   CAPRESULT_OK,
   LIBRARY,
+  loadLibrary,
 } from "../src/ffi.ts";
+import { Frame } from "../src/frame.ts";
+import { encodeNetpbm } from "../src/netpbm.ts";
 
 if (import.meta.main) {
   console.log("OpenPnp Capture Test Program");
+  await loadLibrary();
   const versionPtr = LIBRARY.symbols.Cap_getLibraryVersion();
   if (!versionPtr) throw new Error("getLibraryVersion returned null ptr");
   const version = Deno.UnsafePointerView.getCString(versionPtr);
@@ -131,18 +135,19 @@ if (import.meta.main) {
         CAPRESULT_OK
     ) {
       console.log("Frame captured");
-      if (
-        writeBufferAsPPM(
-          ++frameWriteCounter,
-          finfo.width,
-          finfo.height,
-          buffer,
-        )
-      ) {
-        console.log(`Written frame to frame_${frameWriteCounter}.ppm`);
-      } else {
-        console.log(`Failed to write frame to frame_${frameWriteCounter}.ppm`);
-      }
+      const frame = new Frame({
+        data: buffer,
+        width: finfo.width,
+        height: finfo.height,
+        pixelFormat: "rgb24",
+        timestamp: Date.now(),
+        sequence: ++frameWriteCounter,
+      });
+      Deno.writeFileSync(
+        `frame_${frameWriteCounter}.ppm`,
+        encodeNetpbm(frame),
+      );
+      console.log(`Written frame to frame_${frameWriteCounter}.ppm`);
     }
   }
 
@@ -162,18 +167,3 @@ function fourCCToString(fourccParam: number): string {
   }
   return v;
 }
-
-function writeBufferAsPPM(
-  frameNum: number,
-  width: number,
-  height: number,
-  buffer: Uint8Array,
-): boolean {
-  const ENCODER = new TextEncoder();
-  const fname = `frame_${frameNum}.ppm`;
-  const fout = Deno.createSync(fname);
-  fout.writeSync(ENCODER.encode(`P6 ${width} ${height} 255\n`));
-  fout.writeSync(buffer);
-  fout.close();
-  return true;
-}
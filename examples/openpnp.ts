@@ -4,10 +4,13 @@ import {
   CAPPROPID_GAIN,
   CAPPROPID_WHITEBALANCE,
 } from "../src/ffi.ts";
+import { Frame } from "../src/frame.ts";
+import { encodeNetpbm } from "../src/netpbm.ts";
 import { OpenPnp } from "../src/openpnp.ts";
 
 if (import.meta.main) {
   console.log("OpenPnp Capture Test Program");
+  await OpenPnp.loadLibrary();
   console.log("Version:", OpenPnp.getLibraryVersion());
   OpenPnp.setLogLevel(7);
 
@@ -53,11 +56,17 @@ if (import.meta.main) {
     if (pnp.hasNewFrame(streamId)) {
       pnp.captureFrame(streamId, buffer);
       console.log("Frame captured");
-      writeBufferAsPPM(
-        ++frameWriteCounter,
-        formatInfo.width,
-        formatInfo.height,
-        buffer,
+      const frame = new Frame({
+        data: buffer,
+        width: formatInfo.width,
+        height: formatInfo.height,
+        pixelFormat: "rgb24",
+        timestamp: Date.now(),
+        sequence: ++frameWriteCounter,
+      });
+      Deno.writeFileSync(
+        `frame_${frameWriteCounter}.ppm`,
+        encodeNetpbm(frame),
       );
       console.log(`Written frame to frame_${frameWriteCounter}.ppm`);
     }
@@ -67,18 +76,3 @@ if (import.meta.main) {
   pnp.closeStream(streamId);
   pnp.releaseContext();
 }
-
-function writeBufferAsPPM(
-  frameNum: number,
-  width: number,
-  height: number,
-  buffer: Uint8Array,
-): boolean {
-  const ENCODER = new TextEncoder();
-  const fname = `frame_${frameNum}.ppm`;
-  const fout = Deno.createSync(fname);
-  fout.writeSync(ENCODER.encode(`P6 ${width} ${height} 255\n`));
-  fout.writeSync(buffer);
-  fout.close();
-  return true;
-}
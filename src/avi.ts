@@ -0,0 +1,446 @@
+/**
+ * Provides a recorder of frames to Motion-JPEG AVI files.
+ *
+ * Files follow the OpenDML (AVI 2.0) extensions: frames are split into RIFF
+ * segments of at most 1 GiB, each indexed by a standard index, so recordings
+ * can grow past the 4 GiB limit of AVI 1.0. The first segment also carries a
+ * legacy `idx1` index for older players.
+ *
+ * @example
+ * ```ts
+ * import { AVIWriter, Camera } from "jsr:@sigma/camera";
+ *
+ * using cam = new Camera();
+ * const device = cam.devices().at(0);
+ * if (!device) throw new Error("no device found");
+ * const { format } = device.selectFormat({ width: 1280, height: 720 });
+ * // keep frames in order, pausing the capture rather than skipping queued ones
+ * using stream = device.stream(format, { dropPolicy: "block" });
+ * if (!stream) throw new Error("no stream found");
+ *
+ * // record a minute at the frame rate of the format
+ * using file = await Deno.create("clip.avi");
+ * await stream.readable
+ *   .pipeTo(new AVIWriter(file, { ...format, quality: 85 }), {
+ *     signal: AbortSignal.timeout(60_000),
+ *   })
+ *   .catch((e) => {
+ *     if (e.name !== "TimeoutError") throw e;
+ *   });
+ * ```
+ *
+ * @module
+ */
+
+import type { Frame } from "./frame.ts";
+import { encodeJPEG } from "./jpeg.ts";
+import type { JPEGEncodeOptions } from "./jpeg.ts";
+import type { FormatInfo } from "./types.ts";
+import { frameRateFraction } from "./y4m.ts";
+
+/**
+ * Represents a file the AVI writer can write to and seek into, such as a
+ * `Deno.FsFile`.
+ */
+export interface SeekableWriter {
+  /** Writes some bytes at the current position, returning how many were written. */
+  write(bytes: Uint8Array): Promise<number>;
+  /** Moves the current position, returning the new position. */
+  seek(offset: number, whence: Deno.SeekMode): Promise<number>;
+}
+
+/**
+ * Represents the options of an AVI writer. A `FormatInfo` can be spread into it.
+ */
+export interface AVIWriterOptions
+  extends Pick<FormatInfo, "width" | "height" | "fps">, JPEGEncodeOptions {
+  /** The maximum size of a RIFF segment in bytes, defaults to 1 GiB which some players require. */
+  maxSegmentSize?: number;
+}
+
+/** Number of RIFF segments the super index has room for. */
+const SUPER_INDEX_ENTRIES = 256;
+
+/** Size of the `hdrl` list, rewritten once the recording is finished. */
+const HDRL_SIZE = 12 + 64 + (12 + 64 + 48 + 8 + 24 + 16 * SUPER_INDEX_ENTRIES) +
+  (12 + 256);
+
+/** Offset of the `hdrl` list, after the `RIFF AVI ` header. */
+const HDRL_OFFSET = 12;
+
+const AVIF_HASINDEX = 0x10;
+const AVIF_ISINTERLEAVED = 0x100;
+const AVIIF_KEYFRAME = 0x10;
+const AVI_INDEX_OF_INDEXES = 0;
+const AVI_INDEX_OF_CHUNKS = 1;
+
+/**
+ * Represents a WritableStream of frames recording them as a Motion-JPEG AVI
+ * file.
+ *
+ * Frames are encoded with `encodeJPEG` and must have the configured size. The
+ * headers and indexes are written when the writer is closed or aborted, or
+ * when writing a frame fails, so stopping a pipe with a signal or on an error
+ * still leaves a playable file. The file itself
+ * is left open.
+ */
+export class AVIWriter extends WritableStream<Frame> {
+  #muxer: AVIMuxer;
+
+  /**
+   * Constructs an instance of the AVIWriter class.
+   * @param file - The file to write to, from its current position.
+   * @param options - The size and frame rate of the video, and the options of the JPEG encoder.
+   */
+  constructor(file: SeekableWriter, options: AVIWriterOptions) {
+    const { width, height, fps } = options;
+    if (
+      !(Number.isInteger(width) && width > 0 && width <= 0xFFFF) ||
+      !(Number.isInteger(height) && height > 0 && height <= 0xFFFF)
+    ) {
+      throw new RangeError(`Invalid AVI frame size ${width}x${height}`);
+    }
+    if (!(fps > 0)) throw new RangeError(`Invalid AVI frame rate ${fps}`);
+    const muxer = new AVIMuxer(file, options);
+    super({
+      async write(frame) {
+        try {
+          if (frame.width !== width || frame.height !== height) {
+            throw new RangeError(
+              `Frame is ${frame.width}x${frame.height}, expected ${width}x${height}`,
+            );
+          }
+          // encode first, a borrowed frame is only valid until the next capture
+          await muxer.write(encodeJPEG(frame, options));
+        } catch (error) {
+          // a failed write errors the stream without calling abort, so the
+          // frames written so far are indexed here
+          await muxer.finish().catch(() => {});
+          throw error;
+        }
+      },
+      close: () => muxer.finish(),
+      abort: () => muxer.finish(),
+    });
+    this.#muxer = muxer;
+  }
+
+  /**
+   * Retrieves the number of frames written so far.
+   * @returns The number of frames.
+   */
+  get frames(): number {
+    return this.#muxer.frames;
+  }
+}
+
+interface Segment {
+  /** Absolute offset of the `RIFF` chunk. */
+  riff: number;
+  /** Absolute offset of the `movi` list. */
+  movi: number;
+  /** Absolute offsets and sizes of the frame chunks. */
+  chunks: { offset: number; size: number }[];
+}
+
+/**
+ * Represents the state of an AVI file being written.
+ */
+class AVIMuxer {
+  frames = 0;
+  #file: SeekableWriter;
+  #options: AVIWriterOptions;
+  #maxSegmentSize: number;
+  #start?: number;
+  #position = 0;
+  #segment?: Segment;
+  #firstSegmentFrames = 0;
+  #superIndex: { offset: number; size: number; frames: number }[] = [];
+  #maxChunkSize = 0;
+  #finished = false;
+
+  constructor(file: SeekableWriter, options: AVIWriterOptions) {
+    this.#file = file;
+    this.#options = options;
+    this.#maxSegmentSize = options.maxSegmentSize ?? 2 ** 30;
+  }
+
+  /**
+   * Appends a JPEG image as the next frame, starting a new segment when the
+   * current one would grow past the maximum size with its indexes.
+   */
+  async write(jpeg: Uint8Array) {
+    if (!this.#segment) {
+      this.#start = await this.#file.seek(0, Deno.SeekMode.Current);
+      this.#position = this.#start;
+      // the hdrl list is reserved here and written once the sizes are known
+      const header = new Uint8Array(HDRL_OFFSET + HDRL_SIZE);
+      writeFourCC(header, 0, "RIFF");
+      writeFourCC(header, 8, "AVI ");
+      await this.#writeAll(header);
+      await this.#startMovi(this.#start);
+    } else {
+      const segment = this.#segment;
+      const count = segment.chunks.length + 1;
+      const indexes = 32 + 8 * count +
+        (segment.riff === this.#start ? 8 + 16 * count : 0);
+      const size = this.#position + 8 + jpeg.length + 1 + indexes -
+        segment.riff;
+      if (size > this.#maxSegmentSize) {
+        await this.#finishSegment();
+        await this.#startRiff();
+      }
+    }
+
+    const chunk = new Uint8Array(8 + jpeg.length + (jpeg.length & 1));
+    const view = new DataView(chunk.buffer);
+    writeFourCC(chunk, 0, "00dc");
+    view.setUint32(4, jpeg.length, true);
+    chunk.set(jpeg, 8);
+    const offset = this.#position;
+    await this.#writeAll(chunk);
+    this.#segment!.chunks.push({ offset, size: jpeg.length });
+    this.#maxChunkSize = Math.max(this.#maxChunkSize, jpeg.length);
+    this.frames++;
+  }
+
+  /**
+   * Writes the indexes and the final headers.
+   */
+  async finish() {
+    if (this.#finished) return;
+    this.#finished = true;
+    if (!this.#segment) return;
+    // drop the part of a chunk a failed write may have left
+    await this.#file.seek(this.#position, Deno.SeekMode.Start);
+    await this.#finishSegment();
+    const end = this.#position;
+    await this.#file.seek(this.#start! + HDRL_OFFSET, Deno.SeekMode.Start);
+    this.#position = this.#start! + HDRL_OFFSET;
+    await this.#writeAll(this.#hdrl());
+    await this.#file.seek(end, Deno.SeekMode.Start);
+  }
+
+  async #startRiff() {
+    const riff = this.#position;
+    const header = new Uint8Array(12);
+    writeFourCC(header, 0, "RIFF");
+    writeFourCC(header, 8, "AVIX");
+    await this.#writeAll(header);
+    await this.#startMovi(riff);
+  }
+
+  async #startMovi(riff: number) {
+    if (this.#superIndex.length === SUPER_INDEX_ENTRIES) {
+      throw new RangeError(
+        `AVI file is limited to ${SUPER_INDEX_ENTRIES} segments`,
+      );
+    }
+    const movi = this.#position;
+    const header = new Uint8Array(12);
+    writeFourCC(header, 0, "LIST");
+    writeFourCC(header, 8, "movi");
+    await this.#writeAll(header);
+    this.#segment = { riff, movi, chunks: [] };
+  }
+
+  /**
+   * Closes the `movi` list with a standard index, adds the legacy index to
+   * the first segment, and patches the sizes of the lists.
+   */
+  async #finishSegment() {
+    const { riff, movi, chunks } = this.#segment!;
+    const first = riff === this.#start;
+
+    // standard index, offsets point to the chunk data from the movi list
+    const index = new ByteWriter(32 + 8 * chunks.length);
+    index.fourcc("ix00");
+    index.u32(24 + 8 * chunks.length);
+    index.u16(2);
+    index.u8(0);
+    index.u8(AVI_INDEX_OF_CHUNKS);
+    index.u32(chunks.length);
+    index.fourcc("00dc");
+    index.u64(movi);
+    index.u32(0);
+    for (const { offset, size } of chunks) {
+      index.u32(offset + 8 - movi);
+      index.u32(size);
+    }
+    this.#superIndex.push({
+      offset: this.#position,
+      size: index.bytes.length,
+      frames: chunks.length,
+    });
+    await this.#writeAll(index.bytes);
+    const moviEnd = this.#position;
+
+    if (first) {
+      // legacy index, offsets point to the chunk headers from the movi type
+      const idx1 = new ByteWriter(8 + 16 * chunks.length);
+      idx1.fourcc("idx1");
+      idx1.u32(16 * chunks.length);
+      for (const { offset, size } of chunks) {
+        idx1.fourcc("00dc");
+        idx1.u32(AVIIF_KEYFRAME);
+        idx1.u32(offset - (movi + 8));
+        idx1.u32(size);
+      }
+      await this.#writeAll(idx1.bytes);
+      this.#firstSegmentFrames = chunks.length;
+    }
+
+    const end = this.#position;
+    await this.#patchSize(riff, end - riff - 8);
+    await this.#patchSize(movi, moviEnd - movi - 8);
+    await this.#file.seek(end, Deno.SeekMode.Start);
+    this.#position = end;
+  }
+
+  /**
+   * Builds the `hdrl` list describing the whole recording.
+   */
+  #hdrl(): Uint8Array {
+    const { width, height, fps } = this.#options;
+    const [rate, scale] = frameRateFraction(fps);
+    const bufferSize = this.#maxChunkSize + 8;
+    const header = new ByteWriter(HDRL_SIZE);
+    header.fourcc("LIST");
+    header.u32(HDRL_SIZE - 8);
+    header.fourcc("hdrl");
+
+    header.fourcc("avih");
+    header.u32(56);
+    header.u32(Math.round(1e6 * scale / rate));
+    header.u32(Math.min(Math.ceil(bufferSize * fps), 0xFFFFFFFF));
+    header.u32(0);
+    header.u32(AVIF_HASINDEX | AVIF_ISINTERLEAVED);
+    header.u32(this.#firstSegmentFrames);
+    header.u32(0);
+    header.u32(1);
+    header.u32(bufferSize);
+    header.u32(width);
+    header.u32(height);
+    header.skip(16);
+
+    header.fourcc("LIST");
+    header.u32(4 + 64 + 48 + 8 + 24 + 16 * SUPER_INDEX_ENTRIES);
+    header.fourcc("strl");
+
+    header.fourcc("strh");
+    header.u32(56);
+    header.fourcc("vids");
+    header.fourcc("MJPG");
+    header.skip(12);
+    header.u32(scale);
+    header.u32(rate);
+    header.u32(0);
+    header.u32(this.frames);
+    header.u32(bufferSize);
+    header.u32(0xFFFFFFFF);
+    header.u32(0);
+    header.u16(0);
+    header.u16(0);
+    header.u16(width);
+    header.u16(height);
+
+    // BITMAPINFOHEADER
+    header.fourcc("strf");
+    header.u32(40);
+    header.u32(40);
+    header.u32(width);
+    header.u32(height);
+    header.u16(1);
+    header.u16(24);
+    header.fourcc("MJPG");
+    header.u32(width * height * 3);
+    header.skip(16);
+
+    // super index of the standard indexes of each segment
+    header.fourcc("indx");
+    header.u32(24 + 16 * SUPER_INDEX_ENTRIES);
+    header.u16(4);
+    header.u8(0);
+    header.u8(AVI_INDEX_OF_INDEXES);
+    header.u32(this.#superIndex.length);
+    header.fourcc("00dc");
+    header.skip(12);
+    for (const { offset, size, frames } of this.#superIndex) {
+      header.u64(offset);
+      header.u32(size);
+      header.u32(frames);
+    }
+    header.skip(16 * (SUPER_INDEX_ENTRIES - this.#superIndex.length));
+
+    header.fourcc("LIST");
+    header.u32(4 + 256);
+    header.fourcc("odml");
+    header.fourcc("dmlh");
+    header.u32(248);
+    header.u32(this.frames);
+    return header.bytes;
+  }
+
+  async #patchSize(offset: number, size: number) {
+    const bytes = new Uint8Array(4);
+    new DataView(bytes.buffer).setUint32(0, size, true);
+    await this.#file.seek(offset + 4, Deno.SeekMode.Start);
+    this.#position = offset + 4;
+    await this.#writeAll(bytes);
+  }
+
+  async #writeAll(bytes: Uint8Array) {
+    let written = 0;
+    while (written < bytes.length) {
+      written += await this.#file.write(bytes.subarray(written));
+    }
+    this.#position += bytes.length;
+  }
+}
+
+/**
+ * Represents a fixed size little-endian buffer filled front to back.
+ */
+class ByteWriter {
+  readonly bytes: Uint8Array;
+  #view: DataView;
+  #offset = 0;
+
+  constructor(size: number) {
+    this.bytes = new Uint8Array(size);
+    this.#view = new DataView(this.bytes.buffer);
+  }
+
+  fourcc(code: string) {
+    writeFourCC(this.bytes, this.#offset, code);
+    this.#offset += 4;
+  }
+
+  u8(value: number) {
+    this.#view.setUint8(this.#offset, value);
+    this.#offset += 1;
+  }
+
+  u16(value: number) {
+    this.#view.setUint16(this.#offset, value, true);
+    this.#offset += 2;
+  }
+
+  u32(value: number) {
+    this.#view.setUint32(this.#offset, value, true);
+    this.#offset += 4;
+  }
+
+  u64(value: number) {
+    this.#view.setBigUint64(this.#offset, BigInt(value), true);
+    this.#offset += 8;
+  }
+
+  skip(size: number) {
+    this.#offset += size;
+  }
+}
+
+function writeFourCC(bytes: Uint8Array, offset: number, code: string) {
+  for (let i = 0; i < 4; i++) bytes[offset + i] = code.charCodeAt(i);
+}
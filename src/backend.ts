@@ -0,0 +1,166 @@
+/**
+ * Provides the interface implemented by the capture backends used by `Camera`.
+ *
+ * `OpenPnp` is the default backend, `MockBackend` from `jsr:@sigma/camera/mock`
+ * serves synthetic frames without any physical device.
+ *
+ * @module
+ */
+
+import type { CapPropertyID, FormatInfo } from "./types.ts";
+
+/**
+ * Represents a source of devices and frames that `Camera` can drive.
+ *
+ * Device and format IDs are enumeration indexes, stream IDs are returned by `openStream`.
+ */
+export interface CaptureBackend {
+  /**
+   * Retrieves the number of available devices.
+   * @returns The number of available devices.
+   */
+  getDeviceCount(): number;
+
+  /**
+   * Retrieves the name of a device.
+   * @param id - The ID of the device.
+   * @returns The name of the device.
+   */
+  getDeviceName(id: number): string;
+
+  /**
+   * Retrieves the unique identifier of a device.
+   * @param id - The ID of the device.
+   * @returns The unique identifier of the device.
+   */
+  getDeviceUniqueId(id: number): string;
+
+  /**
+   * Retrieves the number of formats supported by a device.
+   * @param id - The ID of the device.
+   * @returns The number of supported formats.
+   */
+  getNumFormats(id: number): number;
+
+  /**
+   * Retrieves information about a specific format.
+   * @param id - The ID of the device.
+   * @param formatId - The ID of the format.
+   * @returns Information about the format.
+   */
+  getFormatInfo(id: number, formatId: number): FormatInfo;
+
+  /**
+   * Sets the value of a property.
+   * @param id - The ID of the stream.
+   * @param propertyId - The ID of the property.
+   * @param value - The value to set.
+   */
+  setProperty(id: number, propertyId: CapPropertyID, value: number): void;
+
+  /**
+   * Retrieves the value of a property.
+   * @param id - The ID of the stream.
+   * @param propertyId - The ID of the property.
+   * @returns The current value of the property.
+   */
+  getProperty(id: number, propertyId: CapPropertyID): number;
+
+  /**
+   * Sets the automatic mode of a property.
+   * @param id - The ID of the stream.
+   * @param propertyId - The ID of the property.
+   * @param value - 1 to enable automatic mode, 0 to disable it.
+   */
+  setAutoProperty(id: number, propertyId: CapPropertyID, value: number): void;
+
+  /**
+   * Retrieves whether the automatic mode of a property is enabled.
+   * @param id - The ID of the stream.
+   * @param propertyId - The ID of the property.
+   * @returns A boolean indicating if automatic mode is enabled.
+   */
+  getAutoProperty(id: number, propertyId: CapPropertyID): boolean;
+
+  /**
+   * Retrieves the limits of a property.
+   * @param id - The ID of the stream.
+   * @param propertyId - The ID of the property.
+   * @returns The limits of the property.
+   */
+  getPropertyLimits(
+    id: number,
+    propertyId: CapPropertyID,
+  ): { exmax: number; exmin: number; edefault: number };
+
+  /**
+   * Opens a stream for capturing frames.
+   * @param deviceId - The ID of the device.
+   * @param deviceFormatId - The ID of the device format.
+   * @returns The ID of the opened stream.
+   */
+  openStream(deviceId: number, deviceFormatId: number): number;
+
+  /**
+   * Checks if a stream is open.
+   * @param id - The ID of the stream.
+   * @returns A boolean indicating if the stream is open.
+   */
+  isOpenStream(id: number): boolean;
+
+  /**
+   * Checks if a new frame is available.
+   * @param id - The ID of the stream.
+   * @returns A boolean indicating if a new frame is available.
+   */
+  hasNewFrame(id: number): boolean;
+
+  /**
+   * Waits until a new frame is available, without blocking the event loop.
+   * @param id - The ID of the stream.
+   * @param timeout - Maximum time to wait in milliseconds, unbounded by default.
+   * @returns A promise resolving to false if the timeout elapsed first.
+   * @throws {DeviceNotFoundError} If the backend knows the device is gone, so that no frame will ever arrive.
+   */
+  waitForNewFrame(id: number, timeout?: number): Promise<boolean>;
+
+  /**
+   * Checks if a finite stream, such as a recording played once, delivered
+   * its last frame. Live devices don't implement it.
+   * @param id - The ID of the stream.
+   * @returns A boolean indicating if no frame will ever come.
+   */
+  isStreamEnded?(id: number): boolean;
+
+  /**
+   * Retrieves the number of frames delivered since the stream was opened.
+   * @param id - The ID of the stream.
+   * @returns The number of delivered frames.
+   */
+  getStreamFrameCount(id: number): number;
+
+  /**
+   * Captures the latest frame of the stream as packed RGB24.
+   * @param id - The ID of the stream.
+   * @param buffer - The buffer to store the frame.
+   */
+  captureFrame(id: number, buffer: Uint8Array): void;
+
+  /**
+   * Closes a stream.
+   * @param id - The ID of the stream.
+   */
+  closeStream(id: number): void;
+
+  /**
+   * Releases the backend and all its streams.
+   */
+  releaseContext(): void;
+
+  /**
+   * Creates a new context of the same backend with a fresh device enumeration.
+   * Backends with a fixed device list don't implement it.
+   * @returns The new backend context.
+   */
+  reopen?(): CaptureBackend;
+}
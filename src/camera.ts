@@ -3,10 +3,11 @@
  *
  * @example
  * ```ts
- * import { Camera } from "jsr:@sigma/camera";
+ * import { Camera, encodeNetpbm } from "jsr:@sigma/camera";
  *
  * if (import.meta.main) {
  *   console.log("OpenPnp Camera Test Program");
+ *   await Camera.loadLibrary();
  *   console.log("Using openpnp version:", Camera.getLibraryVersion());
  *   //Camera.setLogLevel(7);
  *   using cam = new Camera();
@@ -17,35 +18,17 @@
  *   console.log("Device name:", device.name());
  *   console.log("Device formats:", device.formats());
  *
- *   // choose the format with the highest resolution
- *   const formatInfo = device.formats().sort((a, b) => b.width - a.width).at(0);
- *   if (!formatInfo) throw new Error("no format found");
- *
- *   using stream = device.stream(formatInfo);
+ *   // choose the format closest to 1080p
+ *   using stream = device.stream({ width: 1920, height: 1080 });
  *   if (!stream) throw new Error("no stream found");
  *
  *   let frameNum = 0;
- *   for await (const frame of stream.next({ delay: 100 })) {
+ *   for await (const frame of stream.next()) {
  *     if (frameNum === 5) break;
- *     writeBufferAsPPM(++frameNum, formatInfo.width, formatInfo.height, frame);
+ *     Deno.writeFileSync(`frame_${++frameNum}.ppm`, encodeNetpbm(frame));
  *     console.log(`Written frame to frame_${frameNum}.ppm`);
  *   }
  * }
- *
- * function writeBufferAsPPM(
- *   frameNum: number,
- *   width: number,
- *   height: number,
- *   buffer: Uint8Array,
- * ): boolean {
- *   const ENCODER = new TextEncoder();
- *   const fname = `frame_${frameNum}.ppm`;
- *   const fout = Deno.createSync(fname);
- *   fout.writeSync(ENCODER.encode(`P6 ${width} ${height} 255\n`));
- *   fout.writeSync(buffer);
- *   fout.close();
- *   return true;
- * }
  * ```
  *
  * This library exports 3 levels of abstractions:
@@ -60,32 +43,186 @@
  */
 
 import { OpenPnp } from "./openpnp.ts";
-import type { DeviceInfo, FormatInfoWithId, LogLevel } from "./types.ts";
+import type { CaptureBackend } from "./backend.ts";
+import { Frame, PIXEL_FORMAT_CHANNELS, releaseFrame } from "./frame.ts";
+import {
+  CameraError,
+  DeviceNotFoundError,
+  FormatNotSupportedError,
+  StreamStalledError,
+} from "./errors.ts";
+import { selectFormat } from "./constraints.ts";
+import { abortable, readableFromFrames } from "./streams.ts";
+import type { FormatConstraints, FormatMatch } from "./constraints.ts";
+import type { LibraryOptions } from "./ffi.ts";
+import type {
+  CapPropertyID,
+  DeviceInfo,
+  DeviceQuery,
+  DropPolicy,
+  FormatInfoWithId,
+  LogLevel,
+  PropertyLimits,
+  ReconnectPolicy,
+  StreamOptions,
+} from "./types.ts";
+
+export * from "./types.ts";
+export * from "./errors.ts";
+export type { LibraryOptions } from "./ffi.ts";
+export type { CaptureBackend } from "./backend.ts";
+export { Frame, PIXEL_FORMAT_CHANNELS } from "./frame.ts";
+export { selectFormat } from "./constraints.ts";
+export type {
+  ConstrainFourcc,
+  ConstrainNumber,
+  FormatConstraints,
+  FormatMatch,
+} from "./constraints.ts";
+export type { FrameInit } from "./frame.ts";
+export { decodePNG, encodePNG } from "./png.ts";
+export type { PNGEncodeOptions } from "./png.ts";
+export {
+  decodeNetpbm,
+  encodeNetpbm,
+  netpbmDecoder,
+  netpbmEncoder,
+} from "./netpbm.ts";
+export type { NetpbmEncodeOptions } from "./netpbm.ts";
+export { encodeJPEG } from "./jpeg.ts";
+export type { ChromaSubsampling, JPEGEncodeOptions } from "./jpeg.ts";
+export { Y4MReader, Y4MWriter } from "./y4m.ts";
+export type { Y4MColorspace, Y4MHeader, Y4MWriterOptions } from "./y4m.ts";
+export { AVIWriter } from "./avi.ts";
+export type { AVIWriterOptions, SeekableWriter } from "./avi.ts";
+export {
+  frameData,
+  limitFrameRate,
+  mapFrames,
+  readableFromFrames,
+} from "./streams.ts";
+
+/**
+ * Represents the devices added and removed between two enumerations.
+ */
+export interface DeviceChange {
+  /** The devices that appeared. */
+  added: Device[];
+  /** The devices that disappeared. */
+  removed: Device[];
+}
+
+/**
+ * Represents the event dispatched by Camera when its device list changes.
+ */
+export class DeviceChangeEvent extends Event implements DeviceChange {
+  /** The devices that appeared. */
+  readonly added: Device[];
+  /** The devices that disappeared. */
+  readonly removed: Device[];
+  /**
+   * Constructs an instance of the DeviceChangeEvent class.
+   * @param change - The added and removed devices.
+   */
+  constructor(change: DeviceChange) {
+    super("devicechange");
+    this.added = change.added;
+    this.removed = change.removed;
+  }
+}
 
 /**
  * Represents a camera device with associated methods for interaction.
+ *
+ * Dispatches a `DeviceChangeEvent` named `devicechange` whenever `refresh()`
+ * finds that devices were plugged or unplugged.
  */
-export class Camera {
-  #pnp: OpenPnp;
+export class Camera extends EventTarget {
+  static #logListeners = new Set<(level: LogLevel, message: string) => void>();
+  #backend: CaptureBackend;
+  /** The contexts replaced by `refresh()` while streams were still open on them. */
+  #retiredBackends: CaptureBackend[] = [];
   #devices: Device[];
   /**
    * Constructs an instance of the Camera class.
+   * @param backend - The capture backend to use, defaults to openpnp-capture.
    */
-  constructor() {
-    this.#pnp = new OpenPnp();
+  constructor(backend: CaptureBackend = new OpenPnp()) {
+    super();
+    this.#backend = backend;
+    this.#devices = enumerateDevices(backend);
+  }
 
-    const devices = [];
-    for (let i = 0; i < this.#pnp.getDeviceCount(); i++) {
-      const name = this.#pnp.getDeviceName(i);
-      const formats = [];
-      for (let j = 0; j < this.#pnp.getNumFormats(i); j++) {
-        const info = this.#pnp.getFormatInfo(i, j);
-        formats.push({ ...info, id: j });
-      }
-      devices.push(new Device(this.#pnp, { name, id: i, formats }));
+  /**
+   * Re-enumerates the devices from a fresh backend context.
+   *
+   * Devices are matched by unique id where available, by name otherwise. When
+   * the list changed, `devices()` is replaced and a `devicechange` event is
+   * dispatched. Streams already open keep running on the previous context,
+   * which is released once the last of them is closed, or when the camera is
+   * disposed. Previous Device instances open their streams through their
+   * fresh counterpart, and throw a DeviceNotFoundError once unplugged.
+   * @returns The added and removed devices, both empty if nothing changed.
+   */
+  refresh(): DeviceChange {
+    const backend = this.#backend.reopen?.();
+    if (!backend) return { added: [], removed: [] };
+
+    const devices = enumerateDevices(backend);
+    const previousKeys = deviceKeys(this.#devices);
+    const currentKeys = deviceKeys(devices);
+    const added = devices.filter((_, i) =>
+      !previousKeys.includes(currentKeys[i])
+    );
+    const removed = this.#devices.filter((_, i) =>
+      !currentKeys.includes(previousKeys[i])
+    );
+
+    if (added.length === 0 && removed.length === 0) {
+      backend.releaseContext();
+      return { added, removed };
     }
 
+    for (const [i, device] of this.#devices.entries()) {
+      staleDevices.set(device, devices[currentKeys.indexOf(previousKeys[i])]);
+    }
+    if (retireBackend(this.#backend)) {
+      this.#retiredBackends.push(this.#backend);
+    }
+    this.#backend = backend;
     this.#devices = devices;
+    const change = { added, removed };
+    this.dispatchEvent(new DeviceChangeEvent(change));
+    return change;
+  }
+
+  /**
+   * Periodically refreshes the devices and yields every change.
+   * @param options - Options for the device watch.
+   * @param options.interval - Delay between two enumerations in milliseconds.
+   * @param options.signal - A signal stopping the watch when aborted.
+   * @returns An asynchronous generator yielding device changes.
+   */
+  async *watchDevices(
+    options: { interval?: number; signal?: AbortSignal } = {},
+  ): AsyncGenerator<DeviceChange, void, unknown> {
+    const { interval = 2000, signal } = options;
+    while (!signal?.aborted) {
+      await new Promise<void>((resolve) => {
+        const onAbort = () => {
+          clearTimeout(timer);
+          resolve();
+        };
+        const timer = setTimeout(() => {
+          signal?.removeEventListener("abort", onAbort);
+          resolve();
+        }, interval);
+        signal?.addEventListener("abort", onAbort, { once: true });
+      });
+      if (signal?.aborted) return;
+      const change = this.refresh();
+      if (change.added.length || change.removed.length) yield change;
+    }
   }
 
   /**
@@ -96,11 +233,39 @@ export class Camera {
     return this.#devices;
   }
 
+  /**
+   * Finds the first device matching a query.
+   * @param query - The unique id, name or predicate to match the device against.
+   * @returns The matching Device instance, undefined if none matches.
+   */
+  findDevice(query: DeviceQuery): Device | undefined {
+    return this.#devices.find((device) => {
+      const info = device.info();
+      if ("uniqueId" in query) return info.uniqueId === query.uniqueId;
+      if ("name" in query) return info.name === query.name;
+      return query.predicate(info);
+    });
+  }
+
   /**
    * Releases the context associated with the camera.
    */
   [Symbol.dispose]() {
-    this.#pnp.releaseContext();
+    for (const backend of this.#retiredBackends) {
+      // the ones whose streams all closed were released already
+      if (retiredBackends.delete(backend)) backend.releaseContext();
+    }
+    this.#retiredBackends = [];
+    this.#backend.releaseContext();
+  }
+
+  /**
+   * Loads the openpnp-capture library, downloading it if it can't be found locally.
+   * Only needed when the library isn't available from `OPENPNP_CAPTURE_LIB` or the system search path.
+   * @param options - Options to locate the library.
+   */
+  static async loadLibrary(options?: LibraryOptions): Promise<void> {
+    await OpenPnp.loadLibrary(options);
   }
 
   /**
@@ -118,21 +283,127 @@ export class Camera {
   static setLogLevel(level: LogLevel) {
     OpenPnp.setLogLevel(level);
   }
+
+  /**
+   * Registers a listener receiving the native log messages of the library.
+   * While at least one listener is registered, messages are no longer printed to stderr.
+   * @param listener - The function to call for each log message.
+   * @returns A function unregistering the listener.
+   */
+  static onLog(
+    listener: (level: LogLevel, message: string) => void,
+  ): () => void {
+    if (Camera.#logListeners.size === 0) {
+      OpenPnp.installCustomLogFunction((level, message) => {
+        for (const listener of Camera.#logListeners) listener(level, message);
+      });
+    }
+    Camera.#logListeners.add(listener);
+    return () => {
+      if (!Camera.#logListeners.delete(listener)) return;
+      if (Camera.#logListeners.size === 0) {
+        OpenPnp.installCustomLogFunction(null);
+      }
+    };
+  }
+}
+
+/** Number of streams open on each backend context shared by a camera. */
+const openStreams = new WeakMap<CaptureBackend, number>();
+
+/** Contexts replaced by `Camera.refresh()`, awaiting the close of their last stream. */
+const retiredBackends = new WeakSet<CaptureBackend>();
+
+/**
+ * Devices enumerated from a context replaced by `Camera.refresh()`, mapped to
+ * the same device in the fresh context, undefined if it was unplugged.
+ */
+const staleDevices = new WeakMap<Device, Device | undefined>();
+
+/**
+ * Counts a stream opened on a shared backend context.
+ * @param backend - The capture backend.
+ */
+function retainBackend(backend: CaptureBackend) {
+  openStreams.set(backend, (openStreams.get(backend) ?? 0) + 1);
+}
+
+/**
+ * Counts a stream closed on a shared backend context, and releases the
+ * context if it was retired and this was its last stream.
+ * @param backend - The capture backend.
+ */
+function releaseBackend(backend: CaptureBackend) {
+  const count = (openStreams.get(backend) ?? 1) - 1;
+  openStreams.set(backend, count);
+  if (count === 0 && retiredBackends.delete(backend)) backend.releaseContext();
+}
+
+/**
+ * Retires a backend context replaced by a fresher one, releasing it right
+ * away unless streams are still open on it.
+ * @param backend - The capture backend.
+ * @returns Whether the context is still in use.
+ */
+function retireBackend(backend: CaptureBackend): boolean {
+  if (!openStreams.get(backend)) {
+    backend.releaseContext();
+    return false;
+  }
+  retiredBackends.add(backend);
+  return true;
+}
+
+/**
+ * Enumerates the devices of a backend context.
+ * @param backend - The capture backend.
+ * @returns An array of Device instances.
+ */
+function enumerateDevices(backend: CaptureBackend): Device[] {
+  const devices = [];
+  for (let i = 0; i < backend.getDeviceCount(); i++) {
+    const name = backend.getDeviceName(i);
+    const uniqueId = backend.getDeviceUniqueId(i);
+    const formats = [];
+    for (let j = 0; j < backend.getNumFormats(i); j++) {
+      const info = backend.getFormatInfo(i, j);
+      formats.push({ ...info, id: j });
+    }
+    devices.push(new Device(backend, { name, id: i, uniqueId, formats }));
+  }
+  return devices;
+}
+
+/**
+ * Computes keys identifying devices across enumerations.
+ * Identical devices without unique id are told apart by their rank among devices of the same name.
+ * @param devices - The devices to identify.
+ * @returns One key per device.
+ */
+function deviceKeys(devices: Device[]): string[] {
+  const seen = new Map<string, number>();
+  return devices.map((device) => {
+    const uniqueId = device.uniqueId();
+    if (uniqueId) return `id:${uniqueId}`;
+    const rank = seen.get(device.name()) ?? 0;
+    seen.set(device.name(), rank + 1);
+    return `name:${device.name()}#${rank}`;
+  });
 }
 
 /**
  * Represents a camera device with associated methods for interaction.
  */
 export class Device {
-  #pnp: OpenPnp;
+  #backend: CaptureBackend;
   #deviceInfo: DeviceInfo;
   /**
    * Constructs an instance of the Device class.
-   * @param pnp - The OpenPnp instance.
+   * @param backend - The capture backend.
    * @param deviceInfo - Information about the device.
    */
-  constructor(pnp: OpenPnp, deviceInfo: DeviceInfo) {
-    this.#pnp = pnp;
+  constructor(backend: CaptureBackend, deviceInfo: DeviceInfo) {
+    this.#backend = backend;
     this.#deviceInfo = deviceInfo;
   }
 
@@ -144,6 +415,22 @@ export class Device {
     return this.#deviceInfo.name;
   }
 
+  /**
+   * Retrieves the unique identifier of the device.
+   * @returns The unique identifier of the device, empty if the driver reports none.
+   */
+  uniqueId(): string {
+    return this.#deviceInfo.uniqueId ?? "";
+  }
+
+  /**
+   * Retrieves information about the device.
+   * @returns The DeviceInfo of the device.
+   */
+  info(): DeviceInfo {
+    return this.#deviceInfo;
+  }
+
   /**
    * Retrieves the formats supported by the device.
    * @returns An array of FormatInfoWithId instances.
@@ -152,63 +439,774 @@ export class Device {
     return this.#deviceInfo.formats;
   }
 
+  /**
+   * Selects the format best satisfying a set of constraints.
+   * @param constraints - The constraints to satisfy.
+   * @returns The best format and its fitness distance.
+   */
+  selectFormat(constraints: FormatConstraints): FormatMatch {
+    return selectFormat(this.#deviceInfo.formats, constraints);
+  }
+
   /**
    * Opens a stream with the specified format.
-   * @param formatInfo - The format information.
+   * @param format - The format information, or constraints to select it with `selectFormat`.
+   * @param options - The frame timeout, reconnect and drop policies of the stream.
    * @returns A Stream instance if successful, undefined otherwise.
+   * @throws {DeviceNotFoundError} If the device was unplugged since `Camera.refresh()` re-enumerated it.
+   * @throws {FormatNotSupportedError} If frames of the format can't be captured, checked before the stream opens.
+   * @throws {RangeError} If the queue of the drop policy has no positive size.
    */
-  stream(formatInfo: FormatInfoWithId): Stream | undefined {
-    console.log(this.#deviceInfo.id, formatInfo.id);
-    const streamId = this.#pnp.openStream(this.#deviceInfo.id, formatInfo.id);
-    if (!this.#pnp.isOpenStream(streamId)) return;
-    return new Stream(this.#pnp, streamId, formatInfo);
+  stream(
+    format: FormatInfoWithId | FormatConstraints,
+    options: StreamOptions = {},
+  ): Stream | undefined {
+    if (staleDevices.has(this)) return this.#streamFresh(format, options);
+    const formatInfo = "id" in format
+      ? format
+      : this.selectFormat(format).format;
+    validateFormat(formatInfo);
+    validateDropPolicy(options.dropPolicy);
+    const streamId = this.#backend.openStream(
+      this.#deviceInfo.id,
+      formatInfo.id,
+    );
+    if (!this.#backend.isOpenStream(streamId)) return;
+    try {
+      return new Stream(
+        this.#backend,
+        streamId,
+        formatInfo,
+        this.#deviceInfo,
+        options,
+      );
+    } catch (error) {
+      this.#backend.closeStream(streamId);
+      throw error;
+    }
+  }
+
+  /**
+   * Opens a stream on the same device in the context that replaced the one
+   * this device was enumerated from, which may have been released already.
+   */
+  #streamFresh(
+    format: FormatInfoWithId | FormatConstraints,
+    options: StreamOptions,
+  ): Stream | undefined {
+    const device = staleDevices.get(this);
+    if (!device) {
+      throw new DeviceNotFoundError(
+        `Device ${this.uniqueId() || this.name()} was unplugged`,
+        { operation: "openStream", deviceId: this.#deviceInfo.id },
+      );
+    }
+    if (!("id" in format)) return device.stream(format, options);
+    const { width, height, fourcc, fps } = format;
+    const fresh = device.formats().find((f) =>
+      f.width === width && f.height === height && f.fourcc === fourcc &&
+      f.fps === fps
+    );
+    if (!fresh) {
+      throw new FormatNotSupportedError(
+        `Format ${width}x${height} ${fourcc} @${fps}fps not found`,
+        { operation: "openStream", deviceId: device.info().id },
+      );
+    }
+    return device.stream(fresh, options);
   }
 }
 
+/** Frame timeout used when reconnecting is enabled without an explicit one. */
+const DEFAULT_RECONNECT_FRAME_TIMEOUT = 5000;
+
+const DEFAULT_RECONNECT_POLICY: Required<ReconnectPolicy> = {
+  retries: Infinity,
+  initialDelay: 500,
+  maxDelay: 10000,
+  factor: 2,
+};
+
+/** Longest backend wait when a frame wait can be aborted, so that no wait lingers after an abort. */
+const ABORTABLE_WAIT_SLICE = 100;
+
+/** Frames a `"block"` stream captures ahead of its consumer before pausing. */
+const BLOCKING_QUEUE_SIZE = 8;
+
+/** Whether the deprecated `delay` option of `Stream.next()` was reported already. */
+let delayDeprecationWarned = false;
+
 /**
  * Represents a stream of video frames.
+ *
+ * With a `frameTimeout`, `next()` throws a StreamStalledError when the device
+ * stops delivering frames. With `reconnect`, the stream instead reopens the
+ * same device and format with an exponential backoff, and restores the
+ * properties set through `properties`.
  */
 export class Stream {
-  #pnp: OpenPnp;
+  #backend: CaptureBackend;
+  /** The backend the stream was opened on, until it closes or reconnects elsewhere. */
+  #sharedBackend: CaptureBackend | undefined;
+  /** The backend created while reconnecting, released along with the stream. */
+  #ownedBackend: CaptureBackend | undefined;
   #streamId: number;
+  #open = true;
   #formatInfo: FormatInfoWithId;
+  #deviceInfo: DeviceInfo | undefined;
+  #frameTimeout: number | undefined;
+  #reconnect: Required<ReconnectPolicy> | undefined;
+  #reconnects = 0;
   #buffer: Uint8Array;
+  #borrowedFrame: Frame | undefined;
+  #properties: StreamProperties;
+  #sequence: number | undefined;
+  /** The sequence number reached before the last reconnect. */
+  #sequenceBase = 0;
+  #droppedFrames = 0;
+  #dropPolicy: DropPolicy;
+  /** The frames captured but not yielded yet, unless the policy is `"latest"`. */
+  #queue: Frame[] = [];
+  #discardedFrames = 0;
+  #readable: ReadableStream<Frame> | undefined;
   /**
    * Constructs an instance of the Stream class.
-   * @param pnp - The OpenPnp instance.
+   * @param backend - The capture backend.
    * @param streamId - The ID of the stream.
    * @param formatInfo - The format information.
+   * @param deviceInfo - The device the stream was opened on, required to reconnect.
+   * @param options - The frame timeout, reconnect and drop policies of the stream.
    */
-  constructor(pnp: OpenPnp, streamId: number, formatInfo: FormatInfoWithId) {
-    this.#pnp = pnp;
+  constructor(
+    backend: CaptureBackend,
+    streamId: number,
+    formatInfo: FormatInfoWithId,
+    deviceInfo?: DeviceInfo,
+    options: StreamOptions = {},
+  ) {
+    this.#backend = backend;
     this.#streamId = streamId;
     this.#formatInfo = formatInfo;
+    this.#deviceInfo = deviceInfo;
+    validateFormat(formatInfo);
+    validateDropPolicy(options.dropPolicy);
+    this.#dropPolicy = options.dropPolicy ?? "latest";
+    if (options.reconnect) {
+      if (!deviceInfo) {
+        throw new CameraError(
+          "Reconnecting requires the device of the stream",
+          { operation: "Stream", streamId },
+        );
+      }
+      this.#reconnect = {
+        ...DEFAULT_RECONNECT_POLICY,
+        ...(options.reconnect === true ? {} : options.reconnect),
+      };
+    }
+    this.#frameTimeout = options.frameTimeout ??
+      (this.#reconnect ? DEFAULT_RECONNECT_FRAME_TIMEOUT : undefined);
+    // backends always convert the native format to packed RGB24
     this.#buffer = new Uint8Array(
-      this.#formatInfo.width * this.#formatInfo.height * 4,
+      formatInfo.width * formatInfo.height * PIXEL_FORMAT_CHANNELS.rgb24,
     );
+    this.#properties = new StreamProperties(backend, streamId);
+    this.#sharedBackend = backend;
+    retainBackend(backend);
+  }
+
+  /**
+   * Retrieves the properties (exposure, focus, gain...) of the stream.
+   * @returns A StreamProperties instance bound to this stream.
+   */
+  get properties(): StreamProperties {
+    return this.#properties;
+  }
+
+  /**
+   * Retrieves the sequence number of the last captured frame.
+   * It keeps increasing across reconnects.
+   * @returns The sequence number, undefined if no frame was yielded yet.
+   */
+  get sequence(): number | undefined {
+    return this.#sequence;
+  }
+
+  /**
+   * Retrieves the number of frames delivered by the driver but never captured,
+   * because they were overwritten before being consumed.
+   * @returns The number of dropped frames.
+   */
+  get droppedFrames(): number {
+    return this.#droppedFrames;
+  }
+
+  /**
+   * Retrieves the number of captured frames discarded because the queue was full.
+   * @returns The number of discarded frames, always 0 unless the drop policy is a bounded queue.
+   */
+  get discardedFrames(): number {
+    return this.#discardedFrames;
+  }
+
+  /**
+   * Retrieves the number of captured frames waiting to be yielded.
+   * @returns The length of the queue, always 0 if the drop policy is `"latest"`.
+   */
+  get queuedFrames(): number {
+    return this.#queue.length;
+  }
+
+  /**
+   * Retrieves the number of times the stream reconnected to its device.
+   * @returns The number of successful reconnects.
+   */
+  get reconnects(): number {
+    return this.#reconnects;
+  }
+
+  /**
+   * Retrieves the frames of the stream as a WHATWG ReadableStream.
+   * With the `"latest"` drop policy, a frame is only captured when the
+   * consumer reads, frames delivered in the meantime are dropped and counted
+   * in `droppedFrames`. With a queue or `"block"`, frames are captured in the
+   * background from the first read on, and buffered as the drop policy says.
+   * Cancelling the readable stops capturing, but doesn't close the stream.
+   * @returns A ReadableStream of frames owning their data.
+   */
+  get readable(): ReadableStream<Frame> {
+    if (!this.#readable) {
+      const controller = new AbortController();
+      this.#readable = readableFromFrames(
+        this.next({ signal: controller.signal }),
+        controller,
+      );
+    }
+    return this.#readable;
   }
 
   /**
    * Retrieves the next frame from the stream.
+   * Frames are yielded as soon as the backend reports them, without polling
+   * the event loop. openpnp-capture has no blocking wait, so its backend
+   * checks for frames every millisecond in a worker instead.
+   *
+   * Each frame owns its data by default. With `zeroCopy`, every frame shares
+   * the same buffer and is only valid until the next one is captured, which
+   * avoids an allocation per frame in performance-sensitive loops.
+   *
+   * Unless the drop policy is `"latest"`, frames are captured in the background
+   * while the generator runs, and `zeroCopy` is ignored since queued frames
+   * are copies.
    * @param options - Options for frame retrieval.
-   * @param delay - Delay between frame captures in milliseconds.
-   * @returns An asynchronous generator yielding frames.
+   * @param options.zeroCopy - Whether to yield borrowed frames sharing one buffer.
+   * @param options.signal - Aborts the pending wait, the generator then throws the abort reason.
+   * @param options.timeout - Maximum time to wait for each frame in milliseconds, defaults to the frame timeout of the stream.
+   * @param options.delay - Deprecated and ignored, frames are no longer polled on the event loop. Passing it logs a warning once.
+   * @returns An asynchronous generator yielding frames, ending after the last frame of a finite stream.
+   * @throws {StreamStalledError} If no frame arrives within the timeout and reconnecting is disabled.
    */
-  async *next({ delay = 100 } = {}): AsyncGenerator<Uint8Array, void, unknown> {
+  async *next(
+    { zeroCopy = false, signal, timeout = this.#frameTimeout, delay }: {
+      zeroCopy?: boolean;
+      signal?: AbortSignal;
+      timeout?: number;
+      /** @deprecated frames are yielded as soon as they arrive */
+      delay?: number;
+    } = {},
+  ): AsyncGenerator<Frame, void, unknown> {
+    if (delay !== undefined && !delayDeprecationWarned) {
+      delayDeprecationWarned = true;
+      console.warn(
+        "Stream.next(): the delay option is deprecated and ignored, frames are yielded as soon as they arrive",
+      );
+    }
+    if (this.#dropPolicy === "latest") {
+      yield* this.#latest(zeroCopy, timeout, signal);
+    } else {
+      yield* this.#queued(timeout, signal);
+    }
+  }
+
+  async *#latest(
+    zeroCopy: boolean,
+    timeout: number | undefined,
+    signal?: AbortSignal,
+  ): AsyncGenerator<Frame, void, unknown> {
     while (true) {
+      let frame: Frame;
+      try {
+        if (!await this.#waitForFrame("Stream.next", timeout, signal)) return;
+        frame = this.#capture(zeroCopy);
+      } catch (error) {
+        if (!this.#reconnect || signal?.aborted) throw error;
+        await this.#recover(error, signal);
+        continue;
+      }
+      yield frame;
+    }
+  }
+
+  async *#queued(
+    timeout: number | undefined,
+    signal?: AbortSignal,
+  ): AsyncGenerator<Frame, void, unknown> {
+    const block = this.#dropPolicy === "block";
+    const limit = block
+      ? BLOCKING_QUEUE_SIZE
+      : (this.#dropPolicy as { queue: number }).queue;
+    const stop = new AbortController();
+    let failure: { error: unknown } | undefined;
+    let ended = false;
+    let wake: (() => void) | undefined;
+    let space: (() => void) | undefined;
+    const producer = (async () => {
+      try {
+        for await (
+          const frame of this.#latest(
+            false,
+            timeout,
+            signal ? AbortSignal.any([signal, stop.signal]) : stop.signal,
+          )
+        ) {
+          this.#queue.push(frame);
+          if (this.#queue.length > limit) {
+            this.#queue.shift();
+            this.#discardedFrames++;
+          }
+          wake?.();
+          // stop capturing until the consumer takes a frame, the frames the
+          // device delivers meanwhile are dropped by the driver
+          while (block && this.#queue.length >= limit && !stop.signal.aborted) {
+            await new Promise<void>((resolve) => space = resolve);
+            space = undefined;
+          }
+        }
+        ended = true;
+        wake?.();
+      } catch (error) {
+        if (!stop.signal.aborted) failure = { error };
+        wake?.();
+      }
+    })();
+
+    try {
       while (true) {
-        if (this.#pnp.hasNewFrame(this.#streamId)) break;
-        await new Promise((r) => setTimeout(r, delay));
+        if (this.#queue.length) {
+          const frame = this.#queue.shift()!;
+          space?.();
+          yield frame;
+          continue;
+        }
+        if (failure) throw failure.error;
+        if (ended) return;
+        await new Promise<void>((resolve) => wake = resolve);
+        wake = undefined;
       }
-      this.#pnp.captureFrame(this.#streamId, this.#buffer);
-      yield this.#buffer;
+    } finally {
+      stop.abort();
+      space?.();
+      await producer;
+      this.#queue.length = 0;
     }
   }
 
+  /**
+   * Captures exactly one frame that was not yielded before.
+   * It doesn't reconnect, even if the stream has a reconnect policy.
+   * @param options - Options for frame retrieval.
+   * @param options.signal - Aborts the pending wait, the promise then rejects with the abort reason.
+   * @param options.timeout - Maximum time to wait in milliseconds, defaults to the frame timeout of the stream.
+   * @returns A promise resolving to a frame owning its data.
+   * @throws {StreamStalledError} If no frame arrives within the timeout.
+   */
+  async capture(
+    { signal, timeout = this.#frameTimeout }: {
+      signal?: AbortSignal;
+      timeout?: number;
+    } = {},
+  ): Promise<Frame> {
+    if (!await this.#waitForFrame("Stream.capture", timeout, signal)) {
+      throw new CameraError("The stream delivered its last frame", {
+        operation: "Stream.capture",
+        deviceId: this.#deviceInfo?.id,
+        streamId: this.#streamId,
+      });
+    }
+    return this.#capture(false);
+  }
+
+  /**
+   * Waits until a new frame is available.
+   * @returns A promise resolving to false if the stream ended instead.
+   */
+  async #waitForFrame(
+    operation: string,
+    timeout: number | undefined,
+    signal?: AbortSignal,
+  ): Promise<boolean> {
+    signal?.throwIfAborted();
+    const deadline = timeout === undefined
+      ? undefined
+      : performance.now() + timeout;
+    while (!this.#backend.hasNewFrame(this.#streamId)) {
+      if (this.#backend.isStreamEnded?.(this.#streamId)) return false;
+      const remaining = deadline === undefined
+        ? undefined
+        : deadline - performance.now();
+      if (remaining !== undefined && remaining <= 0) {
+        throw new StreamStalledError(`No frame received for ${timeout} ms`, {
+          operation,
+          deviceId: this.#deviceInfo?.id,
+          streamId: this.#streamId,
+        });
+      }
+      // bound each wait when it can be aborted, the backend can't cancel it
+      const slice = signal
+        ? Math.min(remaining ?? Infinity, ABORTABLE_WAIT_SLICE)
+        : remaining;
+      await abortable(
+        this.#backend.waitForNewFrame(this.#streamId, slice),
+        signal,
+      );
+    }
+    return true;
+  }
+
+  async #recover(cause: unknown, signal?: AbortSignal) {
+    const { retries, initialDelay, maxDelay, factor } = this.#reconnect!;
+    let lastError = cause;
+    for (let attempt = 0; attempt < retries; attempt++) {
+      const delay = Math.min(maxDelay, initialDelay * factor ** attempt);
+      await abortable(
+        new Promise((resolve) => setTimeout(resolve, delay)),
+        signal,
+      );
+      try {
+        this.#reopen();
+        this.#reconnects++;
+        return;
+      } catch (error) {
+        lastError = error;
+      }
+    }
+    const { name } = this.#deviceInfo!;
+    throw new CameraError(
+      `Could not reconnect to ${name} after ${retries} attempts`,
+      { operation: "Stream.reconnect", cause: lastError },
+    );
+  }
+
+  /**
+   * Reopens the same device and format, on a fresh backend context if the backend supports it.
+   */
+  #reopen() {
+    const { name, uniqueId } = this.#deviceInfo!;
+    const { width, height, fourcc, fps } = this.#formatInfo;
+    if (this.#open) {
+      this.#open = false;
+      try {
+        this.#backend.closeStream(this.#streamId);
+      } catch {
+        // the device is gone, its stream may already be invalid
+      }
+    }
+
+    const backend = this.#backend.reopen?.() ?? this.#backend;
+    try {
+      const deviceIds = Array.from(
+        { length: backend.getDeviceCount() },
+        (_, i) => i,
+      );
+      const deviceId = deviceIds.find((i) =>
+        uniqueId
+          ? backend.getDeviceUniqueId(i) === uniqueId
+          : backend.getDeviceName(i) === name
+      );
+      if (deviceId === undefined) {
+        throw new DeviceNotFoundError(`Device ${uniqueId || name} not found`, {
+          operation: "Stream.reconnect",
+        });
+      }
+      const formatId = Array.from(
+        { length: backend.getNumFormats(deviceId) },
+        (_, i) => i,
+      ).find((i) => {
+        const format = backend.getFormatInfo(deviceId, i);
+        return format.width === width && format.height === height &&
+          format.fourcc === fourcc && format.fps === fps;
+      });
+      if (formatId === undefined) {
+        throw new FormatNotSupportedError(
+          `Format ${width}x${height} ${fourcc} @${fps}fps not found`,
+          { operation: "Stream.reconnect", deviceId },
+        );
+      }
+      const streamId = backend.openStream(deviceId, formatId);
+      if (!backend.isOpenStream(streamId)) {
+        throw new CameraError("Stream could not be opened", {
+          operation: "Stream.reconnect",
+          deviceId,
+          streamId,
+        });
+      }
+      rebindProperties(this.#properties, backend, streamId);
+
+      if (backend !== this.#backend) {
+        this.#ownedBackend?.releaseContext();
+        this.#ownedBackend = backend;
+        this.#backend = backend;
+        this.#releaseSharedBackend();
+      }
+      this.#streamId = streamId;
+      this.#open = true;
+      this.#deviceInfo = { ...this.#deviceInfo!, id: deviceId };
+      this.#formatInfo = { ...this.#formatInfo, id: formatId };
+      this.#sequenceBase = this.#sequence ?? 0;
+    } catch (error) {
+      if (backend !== this.#backend) backend.releaseContext();
+      throw error;
+    }
+  }
+
+  #releaseSharedBackend() {
+    if (!this.#sharedBackend) return;
+    releaseBackend(this.#sharedBackend);
+    this.#sharedBackend = undefined;
+  }
+
+  #capture(zeroCopy: boolean): Frame {
+    if (this.#borrowedFrame) {
+      releaseFrame(this.#borrowedFrame);
+      this.#borrowedFrame = undefined;
+    }
+    const data = zeroCopy
+      ? this.#buffer
+      : new Uint8Array(this.#buffer.byteLength);
+    this.#backend.captureFrame(this.#streamId, data);
+    const timestamp = performance.timeOrigin + performance.now();
+    this.#updateSequence();
+    const frame = new Frame({
+      data,
+      width: this.#formatInfo.width,
+      height: this.#formatInfo.height,
+      pixelFormat: "rgb24",
+      timestamp,
+      sequence: this.#sequence!,
+      borrowed: zeroCopy,
+    });
+    if (zeroCopy) this.#borrowedFrame = frame;
+    return frame;
+  }
+
+  #updateSequence() {
+    const count = this.#backend.getStreamFrameCount(this.#streamId);
+    // the driver counter is a u32 and can wrap around
+    const sequence = (this.#sequenceBase + count) >>> 0;
+    if (this.#sequence !== undefined) {
+      const delta = (sequence - this.#sequence) >>> 0;
+      if (delta > 1) this.#droppedFrames += delta - 1;
+    }
+    this.#sequence = sequence;
+  }
+
   /**
    * Releases the resources associated with the stream.
    */
   [Symbol.dispose]() {
-    this.#pnp.closeStream(this.#streamId);
+    if (this.#open) {
+      this.#open = false;
+      this.#backend.closeStream(this.#streamId);
+    }
+    this.#releaseSharedBackend();
+    this.#ownedBackend?.releaseContext();
+  }
+}
+
+/** Bits per pixel of the uncompressed native formats, by fourcc. */
+const FOURCC_BPP: Readonly<Record<string, number>> = {
+  GREY: 8,
+  Y800: 8,
+  Y16: 16,
+  NV12: 12,
+  NV21: 12,
+  I420: 12,
+  YU12: 12,
+  YV12: 12,
+  YUYV: 16,
+  YUY2: 16,
+  YVYU: 16,
+  UYVY: 16,
+  "422P": 16,
+  RGB3: 24,
+  BGR3: 24,
+  "444P": 24,
+  RGB4: 32,
+  BGR4: 32,
+  RGBA: 32,
+  BGRA: 32,
+  AR24: 32,
+  XR24: 32,
+};
+
+/** Compressed native formats, whose bits per pixel are only indicative. */
+const COMPRESSED_FOURCCS = new Set(["MJPG", "JPEG", "H264", "H265", "HEVC"]);
+
+/** Packed formats sharing chroma samples between two horizontal pixels. */
+const PACKED_422_FOURCCS = new Set(["YUYV", "YUY2", "YVYU", "UYVY"]);
+
+/**
+ * Checks that frames of a format can be captured as packed RGB24, and that
+ * its bits per pixel match the layout of its native format.
+ * @param formatInfo - The format to check.
+ */
+function validateFormat(formatInfo: FormatInfoWithId) {
+  const { width, height, bpp } = formatInfo;
+  const fourcc = formatInfo.fourcc.trim();
+  const fail = (message: string) =>
+    new FormatNotSupportedError(message, { operation: "Stream" });
+  if (
+    !(Number.isInteger(width) && width > 0) ||
+    !(Number.isInteger(height) && height > 0)
+  ) {
+    throw fail(`Invalid frame size ${width}x${height}`);
+  }
+  if (!Number.isInteger(bpp) || bpp < 0) {
+    throw fail(`Invalid bits per pixel ${bpp} for ${fourcc}`);
+  }
+  if (COMPRESSED_FOURCCS.has(fourcc)) return;
+
+  // some drivers report 0 when they don't know, whatever the format
+  const expected = FOURCC_BPP[fourcc];
+  if (expected !== undefined && bpp !== 0 && bpp !== expected) {
+    throw fail(`${fourcc} has ${expected} bits per pixel, not ${bpp}`);
+  }
+  // unknown formats are converted by the driver, if it can: they report 0
+  // when compressed, or else describe width x height x bpp / 8 whole bytes
+  if (
+    expected === undefined &&
+    (bpp > 32 || (width * height * bpp) % 8 !== 0)
+  ) {
+    throw fail(
+      `Unsupported bits per pixel ${bpp} for ${fourcc} at ${width}x${height}`,
+    );
+  }
+  if (PACKED_422_FOURCCS.has(fourcc) && width % 2 !== 0) {
+    throw fail(`${fourcc} frames need an even width, not ${width}`);
+  }
+}
+
+/**
+ * Checks that the queue of a drop policy has a positive size.
+ * @param dropPolicy - The drop policy to check.
+ */
+function validateDropPolicy(dropPolicy: DropPolicy | undefined) {
+  if (
+    typeof dropPolicy === "object" &&
+    !(Number.isInteger(dropPolicy.queue) && dropPolicy.queue > 0)
+  ) {
+    throw new RangeError(
+      `Queue size must be a positive integer, got ${dropPolicy.queue}`,
+    );
+  }
+}
+
+/**
+ * Points a StreamProperties instance to a reopened stream and applies its recorded settings again.
+ */
+let rebindProperties: (
+  properties: StreamProperties,
+  backend: CaptureBackend,
+  streamId: number,
+) => void;
+
+/**
+ * Provides access to the camera properties of an open stream.
+ */
+export class StreamProperties {
+  static {
+    rebindProperties = (properties, backend, streamId) =>
+      properties.#rebind(backend, streamId);
+  }
+
+  #backend: CaptureBackend;
+  #streamId: number;
+  /** The settings applied so far, restored when the stream reconnects. */
+  #applied = new Map<CapPropertyID, { value?: number; auto?: boolean }>();
+  /**
+   * Constructs an instance of the StreamProperties class.
+   * @param backend - The capture backend.
+   * @param streamId - The ID of the stream.
+   */
+  constructor(backend: CaptureBackend, streamId: number) {
+    this.#backend = backend;
+    this.#streamId = streamId;
+  }
+
+  /**
+   * Retrieves the current value of a property.
+   * @param propertyId - The ID of the property.
+   * @returns The value of the property.
+   */
+  get(propertyId: CapPropertyID): number {
+    return this.#backend.getProperty(this.#streamId, propertyId);
+  }
+
+  /**
+   * Sets the value of a property.
+   * Automatic mode usually needs to be disabled first with `setAuto`.
+   * @param propertyId - The ID of the property.
+   * @param value - The value to set.
+   */
+  set(propertyId: CapPropertyID, value: number) {
+    this.#backend.setProperty(this.#streamId, propertyId, value);
+    this.#applied.set(propertyId, { ...this.#applied.get(propertyId), value });
+  }
+
+  /**
+   * Checks if automatic mode is enabled for a property.
+   * @param propertyId - The ID of the property.
+   * @returns A boolean indicating if automatic mode is enabled.
+   */
+  isAuto(propertyId: CapPropertyID): boolean {
+    return this.#backend.getAutoProperty(this.#streamId, propertyId);
+  }
+
+  /**
+   * Enables or disables automatic mode for a property.
+   * @param propertyId - The ID of the property.
+   * @param enabled - Whether automatic mode should be enabled.
+   */
+  setAuto(propertyId: CapPropertyID, enabled: boolean) {
+    this.#backend.setAutoProperty(this.#streamId, propertyId, enabled ? 1 : 0);
+    this.#applied.set(propertyId, {
+      ...this.#applied.get(propertyId),
+      auto: enabled,
+    });
+  }
+
+  /**
+   * Retrieves the limits of a property.
+   * @param propertyId - The ID of the property.
+   * @returns The minimum, maximum and default values of the property.
+   */
+  limits(propertyId: CapPropertyID): PropertyLimits {
+    const { exmin, exmax, edefault } = this.#backend.getPropertyLimits(
+      this.#streamId,
+      propertyId,
+    );
+    return { min: exmin, max: exmax, default: edefault };
+  }
+
+  #rebind(backend: CaptureBackend, streamId: number) {
+    for (const [propertyId, { value, auto }] of this.#applied) {
+      if (auto !== undefined) {
+        backend.setAutoProperty(streamId, propertyId, auto ? 1 : 0);
+      }
+      if (value !== undefined && !auto) {
+        backend.setProperty(streamId, propertyId, value);
+      }
+    }
+    this.#backend = backend;
+    this.#streamId = streamId;
   }
 }
@@ -0,0 +1,379 @@
+/**
+ * Provides a client for the WebSocket frame server, usable in Deno and in browsers.
+ *
+ * @example
+ * ```ts
+ * import { CapPropertyID } from "jsr:@sigma/camera";
+ * import { FrameClient } from "jsr:@sigma/camera/client";
+ *
+ * using client = await FrameClient.connect("ws://line-pc:8080/", {
+ *   encoding: "raw",
+ * });
+ * console.log("streaming", client.format);
+ *
+ * await client.setFormat({ width: 640, height: 480 });
+ * await client.setAuto(CapPropertyID.Exposure, false);
+ * await client.setProperty(CapPropertyID.Exposure, -6);
+ *
+ * for await (const frame of client.frames()) {
+ *   console.log(frame.sequence, frame.width, frame.height);
+ * }
+ * ```
+ *
+ * @module
+ */
+
+import { Frame } from "./frame.ts";
+import { decodePNG } from "./png.ts";
+import { decodeFrameMessage } from "./protocol.ts";
+import { abortable } from "./streams.ts";
+import type {
+  ControlRequest,
+  FrameEncoding,
+  FrameHeader,
+  PropertyState,
+  ServerMessage,
+} from "./protocol.ts";
+import type { FormatConstraints } from "./constraints.ts";
+import type { CapPropertyID, FormatInfoWithId } from "./types.ts";
+
+export { decodeFrameMessage, FRAME_HEADER_SIZE } from "./protocol.ts";
+export type {
+  ControlMessage,
+  ControlRequest,
+  FrameEncoding,
+  FrameHeader,
+  PropertyState,
+  ServerMessage,
+} from "./protocol.ts";
+
+/**
+ * Represents a function turning the payload of a frame message into a frame.
+ */
+export type FrameDecoder = (
+  payload: Uint8Array,
+  header: FrameHeader,
+) => Frame | Promise<Frame>;
+
+/**
+ * Represents the options of a frame client.
+ */
+export interface FrameClientOptions {
+  /** The encoding to ask the server for once connected, the server default otherwise. */
+  encoding?: FrameEncoding;
+  /** The JPEG quality from 1 to 100 to ask the server for along with the encoding. */
+  quality?: number;
+  /**
+   * Decoders of the payloads, by encoding. Raw and PNG payloads are decoded
+   * by default, JPEG payloads need one, such as a canvas in a browser.
+   */
+  decoders?: Partial<Record<FrameEncoding, FrameDecoder>>;
+}
+
+/**
+ * Represents an error reported by the frame server, carrying the name of the
+ * error thrown there, such as `PropertyNotSupportedError`.
+ */
+export class RemoteError extends Error {
+  /** The CapResult code, if the server threw a CameraError. */
+  readonly code?: number;
+  /** The operation that failed, if the server threw a CameraError. */
+  readonly operation?: string;
+  /**
+   * Constructs an instance of the RemoteError class.
+   * @param message - The error message sent by the server.
+   */
+  constructor(message: ServerMessage & { type: "error" }) {
+    super(message.message);
+    this.name = message.name;
+    this.code = message.code;
+    this.operation = message.operation;
+  }
+}
+
+/**
+ * Represents a connection to a WebSocket frame server.
+ *
+ * Frames are delivered live: if the consumer is slower than the server, only
+ * the latest frame received is kept.
+ */
+export class FrameClient {
+  #socket: WebSocket;
+  #decoders: Partial<Record<FrameEncoding, FrameDecoder>>;
+  #hello: ServerMessage & { type: "hello" };
+  #format: FormatInfoWithId;
+  #nextId = 1;
+  #pending = new Map<number, PromiseWithResolvers<unknown>>();
+  #latest?: { header: FrameHeader; payload: Uint8Array };
+  #changed = Promise.withResolvers<void>();
+  #closed?: { error?: Error };
+
+  /**
+   * Connects to a frame server.
+   * @param url - The URL of the WebSocket endpoint.
+   * @param options - The encoding to request and the decoders of the frames.
+   * @returns A promise resolving to the client once the server described the device.
+   */
+  static async connect(
+    url: string | URL,
+    options: FrameClientOptions = {},
+  ): Promise<FrameClient> {
+    const socket = new WebSocket(url);
+    socket.binaryType = "arraybuffer";
+    const hello = await new Promise<ServerMessage & { type: "hello" }>(
+      (resolve, reject) => {
+        socket.onmessage = (event) => {
+          if (typeof event.data !== "string") return;
+          const message = JSON.parse(event.data) as ServerMessage;
+          if (message.type === "hello") resolve(message);
+        };
+        socket.onerror = () =>
+          reject(new Error(`Could not connect to ${url}`));
+        socket.onclose = (event) =>
+          reject(new Error(`Connection to ${url} closed: ${event.reason}`));
+      },
+    );
+    const client = new FrameClient(socket, hello, options.decoders);
+    if (options.encoding) {
+      await client.setEncoding(options.encoding, options.quality);
+    }
+    return client;
+  }
+
+  /**
+   * Constructs an instance of the FrameClient class, use `connect` instead.
+   * @param socket - The open WebSocket.
+   * @param hello - The first message sent by the server.
+   * @param decoders - The decoders of the payloads, by encoding.
+   */
+  constructor(
+    socket: WebSocket,
+    hello: ServerMessage & { type: "hello" },
+    decoders: Partial<Record<FrameEncoding, FrameDecoder>> = {},
+  ) {
+    this.#socket = socket;
+    this.#hello = hello;
+    this.#format = hello.format;
+    this.#decoders = { raw: decodeRaw, png: decodePNG, ...decoders };
+    socket.onmessage = (event) => this.#receive(event.data);
+    socket.onerror = () => {};
+    socket.onclose = (event) => {
+      this.#close(
+        event.code === 1000 ? undefined : new Error(
+          `Connection closed with code ${event.code}: ${event.reason}`,
+        ),
+      );
+    };
+  }
+
+  /**
+   * Retrieves the device streamed by the server.
+   * @returns The name and unique identifier of the device.
+   */
+  get device(): { name: string; uniqueId: string } {
+    return this.#hello.device;
+  }
+
+  /**
+   * Retrieves the formats supported by the device.
+   * @returns An array of FormatInfoWithId instances.
+   */
+  get formats(): FormatInfoWithId[] {
+    return this.#hello.formats;
+  }
+
+  /**
+   * Retrieves the current format of the stream, kept up to date when any client changes it.
+   * @returns The format of the stream.
+   */
+  get format(): FormatInfoWithId {
+    return this.#format;
+  }
+
+  /**
+   * Retrieves the frames sent by the server with their metadata, without decoding them.
+   * @param signal - Aborts the pending wait, the generator then throws the abort reason.
+   * @returns An async generator of frame headers and payloads.
+   */
+  async *messages(
+    signal?: AbortSignal,
+  ): AsyncGenerator<{ header: FrameHeader; payload: Uint8Array }, void> {
+    while (true) {
+      while (!this.#latest) {
+        if (this.#closed) {
+          if (this.#closed.error) throw this.#closed.error;
+          return;
+        }
+        await abortable(this.#changed.promise, signal);
+      }
+      const message = this.#latest;
+      this.#latest = undefined;
+      yield message;
+    }
+  }
+
+  /**
+   * Retrieves the frames sent by the server, decoded with the decoder of their encoding.
+   * @param signal - Aborts the pending wait, the generator then throws the abort reason.
+   * @returns An async generator of frames.
+   * @throws {Error} If no decoder handles the encoding of a frame.
+   */
+  async *frames(signal?: AbortSignal): AsyncGenerator<Frame, void> {
+    for await (const { header, payload } of this.messages(signal)) {
+      const decoder = this.#decoders[header.encoding];
+      if (!decoder) {
+        throw new Error(
+          `No decoder for ${header.encoding} frames, pass one in decoders`,
+        );
+      }
+      const decoded = await decoder(payload, header);
+      // encoded images carry no metadata, restore it from the header
+      yield new Frame({
+        data: decoded.data,
+        width: decoded.width,
+        height: decoded.height,
+        pixelFormat: decoded.pixelFormat,
+        stride: decoded.stride,
+        timestamp: header.timestamp,
+        sequence: header.sequence,
+      });
+    }
+  }
+
+  /**
+   * Reopens the stream of the server with another format, for all its clients.
+   * @param format - The format with this ID, or the one best satisfying the constraints.
+   * @returns A promise resolving to the new format.
+   */
+  async setFormat(
+    format: FormatConstraints | { id: number },
+  ): Promise<FormatInfoWithId> {
+    const result = await this.#request({ type: "setFormat", format }) as {
+      format: FormatInfoWithId;
+    };
+    this.#format = result.format;
+    return result.format;
+  }
+
+  /**
+   * Retrieves the value, automatic mode and limits of a property.
+   * @param property - The ID of the property.
+   * @returns A promise resolving to the state of the property.
+   */
+  getProperty(property: CapPropertyID): Promise<PropertyState> {
+    return this.#request({ type: "getProperty", property }) as Promise<
+      PropertyState
+    >;
+  }
+
+  /**
+   * Sets the value of a property.
+   * @param property - The ID of the property.
+   * @param value - The value to set.
+   */
+  async setProperty(property: CapPropertyID, value: number) {
+    await this.#request({ type: "setProperty", property, value });
+  }
+
+  /**
+   * Enables or disables automatic mode for a property.
+   * @param property - The ID of the property.
+   * @param enabled - Whether automatic mode should be enabled.
+   */
+  async setAuto(property: CapPropertyID, enabled: boolean) {
+    await this.#request({ type: "setAuto", property, enabled });
+  }
+
+  /**
+   * Changes the encoding of the frames sent to this client.
+   * @param encoding - The encoding of the frames.
+   * @param quality - The JPEG quality from 1 to 100, unchanged by default.
+   */
+  async setEncoding(encoding: FrameEncoding, quality?: number) {
+    await this.#request({ type: "setEncoding", encoding, quality });
+  }
+
+  /**
+   * Closes the connection.
+   */
+  close() {
+    this.#socket.close(1000);
+    this.#close();
+  }
+
+  [Symbol.dispose]() {
+    this.close();
+  }
+
+  #request(request: ControlRequest): Promise<unknown> {
+    if (this.#closed) {
+      return Promise.reject(
+        this.#closed.error ?? new Error("Connection closed"),
+      );
+    }
+    const id = this.#nextId++;
+    const pending = Promise.withResolvers<unknown>();
+    this.#pending.set(id, pending);
+    this.#socket.send(JSON.stringify({ ...request, id }));
+    return pending.promise;
+  }
+
+  #receive(data: unknown) {
+    if (data instanceof ArrayBuffer) {
+      this.#latest = decodeFrameMessage(new Uint8Array(data));
+      this.#notify();
+      return;
+    }
+    const message = JSON.parse(String(data)) as ServerMessage;
+    switch (message.type) {
+      case "format":
+        this.#format = message.format;
+        break;
+      case "result":
+        this.#pending.get(message.id)?.resolve(message.result);
+        this.#pending.delete(message.id);
+        break;
+      case "error": {
+        const error = new RemoteError(message);
+        if (message.id === undefined) {
+          this.#close(error);
+          break;
+        }
+        this.#pending.get(message.id)?.reject(error);
+        this.#pending.delete(message.id);
+        break;
+      }
+      case "protocolError":
+        // requests always carry an id, this only answers foreign messages
+        break;
+    }
+  }
+
+  #close(error?: Error) {
+    if (this.#closed) return;
+    this.#closed = { error };
+    for (const pending of this.#pending.values()) {
+      pending.reject(error ?? new Error("Connection closed"));
+    }
+    this.#pending.clear();
+    this.#notify();
+  }
+
+  #notify() {
+    const { resolve } = this.#changed;
+    this.#changed = Promise.withResolvers();
+    resolve();
+  }
+}
+
+function decodeRaw(payload: Uint8Array, header: FrameHeader): Frame {
+  const { width, height, pixelFormat, timestamp, sequence } = header;
+  return new Frame({
+    data: payload,
+    width,
+    height,
+    pixelFormat,
+    timestamp,
+    sequence,
+  });
+}
@@ -0,0 +1,220 @@
+/**
+ * Provides format negotiation modeled after the W3C MediaTrackConstraints algorithm.
+ *
+ * Each constraint is either a bare value, treated as an ideal, or an object
+ * with `min`, `max`, `exact` and `ideal` members. Formats violating a `min`,
+ * `max` or `exact` member are rejected, the remaining ones are ranked by their
+ * fitness distance to the ideal values.
+ *
+ * @example
+ * ```ts
+ * import { Camera } from "jsr:@sigma/camera";
+ *
+ * using cam = new Camera();
+ * const device = cam.devices().at(0);
+ * if (!device) throw new Error("no device found");
+ *
+ * const { format, distance } = device.selectFormat({
+ *   width: { min: 1280, ideal: 1920 },
+ *   fps: { min: 15 },
+ *   fourcc: ["MJPG", "YUYV"],
+ * });
+ * console.log("selected", format, "at distance", distance);
+ * ```
+ *
+ * @module
+ */
+
+import { FormatNotSupportedError } from "./errors.ts";
+import type { DeviceInfo, FormatInfoWithId } from "./types.ts";
+
+/**
+ * Represents a constraint on a numeric format property.
+ * A bare number is treated as `{ ideal: value }`.
+ */
+export type ConstrainNumber = number | {
+  /** The smallest accepted value. */
+  min?: number;
+  /** The largest accepted value. */
+  max?: number;
+  /** The only accepted value. */
+  exact?: number;
+  /** The preferred value. */
+  ideal?: number;
+};
+
+/**
+ * Represents a constraint on the four-character code of a format.
+ * A bare string or list is treated as `{ ideal: value }`, earlier entries being preferred.
+ * Codes are compared without their padding spaces, `"Y16"` matching `"Y16 "`.
+ */
+export type ConstrainFourcc = string | string[] | {
+  /** The only accepted codes. */
+  exact?: string | string[];
+  /** The preferred codes, in order of preference. */
+  ideal?: string | string[];
+};
+
+/**
+ * Represents the constraints used to select a format.
+ */
+export interface FormatConstraints {
+  /** The width of the video frame in pixels. */
+  width?: ConstrainNumber;
+  /** The height of the video frame in pixels. */
+  height?: ConstrainNumber;
+  /** The frames per second of the video stream. */
+  fps?: ConstrainNumber;
+  /** The width divided by the height of the video frame. */
+  aspectRatio?: ConstrainNumber;
+  /** The four-character code of the video format. */
+  fourcc?: ConstrainFourcc;
+}
+
+/**
+ * Represents a format satisfying a set of constraints.
+ */
+export interface FormatMatch {
+  /** The selected format. */
+  format: FormatInfoWithId;
+  /** The fitness distance to the ideal values, 0 being a perfect match. */
+  distance: number;
+}
+
+/** Relative tolerance when comparing aspect ratios, which are rarely exact. */
+const ASPECT_RATIO_TOLERANCE = 0.01;
+
+/** Number of rejected formats listed when nothing matches. */
+const NEAREST_CANDIDATES = 3;
+
+/**
+ * Selects the format best satisfying a set of constraints.
+ * @param formats - The candidate formats.
+ * @param constraints - The constraints to satisfy.
+ * @returns The best format and its fitness distance.
+ * @throws {FormatNotSupportedError} If no format satisfies the constraints, listing the nearest ones.
+ */
+export function selectFormat(
+  formats: FormatInfoWithId[],
+  constraints: FormatConstraints,
+): FormatMatch {
+  const matches: FormatMatch[] = [];
+  const rejected: { format: FormatInfoWithId; violation: number }[] = [];
+  for (const format of formats) {
+    const { violation, distance } = evaluate(format, constraints);
+    if (violation > 0) rejected.push({ format, violation });
+    else matches.push({ format, distance });
+  }
+
+  if (matches.length === 0) {
+    const nearest = rejected
+      .sort((a, b) => a.violation - b.violation)
+      .slice(0, NEAREST_CANDIDATES)
+      .map(({ format }) => describeFormat(format));
+    throw new FormatNotSupportedError(
+      `No format satisfies ${JSON.stringify(constraints)}` +
+        (nearest.length ? `, nearest candidates: ${nearest.join(", ")}` : ""),
+      { operation: "selectFormat" },
+    );
+  }
+
+  // ties go to the largest resolution, then to the highest frame rate
+  return matches.sort((a, b) =>
+    a.distance - b.distance ||
+    b.format.width * b.format.height - a.format.width * a.format.height ||
+    b.format.fps - a.format.fps
+  )[0];
+}
+
+/**
+ * Finds a format of a device by its ID, or the one best satisfying constraints.
+ * @param device - The device whose formats are searched.
+ * @param format - The ID of the format, or the constraints to satisfy.
+ * @param operation - The operation reported when no format is found.
+ * @returns The format.
+ * @throws {FormatNotSupportedError} If the device has no format with the ID, or none satisfies the constraints.
+ */
+export function resolveFormat(
+  device: DeviceInfo,
+  format: FormatConstraints | { id: number },
+  operation: string,
+): FormatInfoWithId {
+  if (!("id" in format)) return selectFormat(device.formats, format).format;
+  const found = device.formats.find(({ id }) => id === format.id);
+  if (!found) {
+    throw new FormatNotSupportedError(
+      `${device.name} has no format ${format.id}`,
+      { operation, deviceId: device.id },
+    );
+  }
+  return found;
+}
+
+function evaluate(
+  format: FormatInfoWithId,
+  constraints: FormatConstraints,
+): { violation: number; distance: number } {
+  const numeric: [ConstrainNumber | undefined, number, number][] = [
+    [constraints.width, format.width, 0],
+    [constraints.height, format.height, 0],
+    [constraints.fps, format.fps, 0],
+    [
+      constraints.aspectRatio,
+      format.width / format.height,
+      ASPECT_RATIO_TOLERANCE,
+    ],
+  ];
+
+  let violation = 0;
+  let distance = 0;
+  for (const [constraint, actual, tolerance] of numeric) {
+    if (constraint === undefined) continue;
+    const { min, max, exact, ideal } = typeof constraint === "number"
+      ? { ideal: constraint }
+      : constraint;
+    if (min !== undefined && actual < min * (1 - tolerance)) {
+      violation += relative(actual, min);
+    }
+    if (max !== undefined && actual > max * (1 + tolerance)) {
+      violation += relative(actual, max);
+    }
+    if (exact !== undefined && relative(actual, exact) > tolerance) {
+      violation += relative(actual, exact);
+    }
+    if (ideal !== undefined) distance += relative(actual, ideal);
+  }
+
+  if (constraints.fourcc !== undefined) {
+    // drivers pad short codes with spaces, like "Y16 "
+    const fourcc = format.fourcc.trim();
+    const { exact, ideal } = typeof constraints.fourcc === "string" ||
+        Array.isArray(constraints.fourcc)
+      ? { ideal: constraints.fourcc }
+      : constraints.fourcc;
+    if (exact !== undefined && !toList(exact).includes(fourcc)) {
+      violation += 1;
+    }
+    if (ideal !== undefined) {
+      const preferred = toList(ideal);
+      const rank = preferred.indexOf(fourcc);
+      distance += rank < 0 ? 1 : rank / preferred.length;
+    }
+  }
+
+  return { violation, distance };
+}
+
+/** Fitness distance between two values, as defined by the W3C algorithm. */
+function relative(actual: number, expected: number): number {
+  if (actual === expected) return 0;
+  return Math.abs(actual - expected) /
+    Math.max(Math.abs(actual), Math.abs(expected));
+}
+
+function toList(value: string | string[]): string[] {
+  return (typeof value === "string" ? [value] : value).map((v) => v.trim());
+}
+
+function describeFormat(format: FormatInfoWithId): string {
+  return `${format.width}x${format.height} ${format.fourcc} @${format.fps}fps (id ${format.id})`;
+}
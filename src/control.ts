@@ -0,0 +1,802 @@
+/**
+ * Provides an HTTP/JSON API to control the devices of a camera from another
+ * process: list devices and formats, open and close streams, and read or set
+ * their properties. The API is described by an OpenAPI document served at
+ * `/openapi.json`.
+ *
+ * @example
+ * ```ts
+ * import { Camera } from "jsr:@sigma/camera";
+ * import { serveControl } from "jsr:@sigma/camera/control";
+ *
+ * using cam = new Camera();
+ * serveControl(cam, { port: 8082 });
+ *
+ * // from another process
+ * const base = "http://localhost:8082";
+ * const [device] = await (await fetch(`${base}/devices`)).json();
+ * const stream = await (await fetch(
+ *   `${base}/devices/${encodeURIComponent(device.uniqueId)}/streams`,
+ *   { method: "POST", body: JSON.stringify({ format: { width: 1280 } }) },
+ * )).json();
+ * await fetch(`${base}/streams/${stream.id}/properties/exposure`, {
+ *   method: "PUT",
+ *   body: JSON.stringify({ auto: false, value: -6 }),
+ * });
+ * ```
+ *
+ * @module
+ */
+
+import type { Camera, Device, Stream } from "./camera.ts";
+import { type FormatConstraints, resolveFormat } from "./constraints.ts";
+import {
+  CameraError,
+  DeviceNotFoundError,
+  FormatNotSupportedError,
+  PropertyNotSupportedError,
+} from "./errors.ts";
+import type { ServeOptions } from "./server.ts";
+import { CapPropertyID } from "./types.ts";
+import type {
+  FormatInfoWithId,
+  PropertyLimits,
+  StreamOptions,
+} from "./types.ts";
+
+/**
+ * Represents the options of a control API request handler.
+ */
+export interface ControlHandlerOptions {
+  /** The path prefix of all endpoints, such as `/camera`, none by default. */
+  basePath?: string;
+  /** Closes the streams opened through the API when aborted. */
+  signal?: AbortSignal;
+}
+
+/**
+ * Represents the options of a control API server.
+ */
+export interface ControlServeOptions
+  extends Omit<ControlHandlerOptions, "signal">, ServeOptions {}
+
+/**
+ * Represents a stream opened through the API.
+ */
+export interface StreamResource {
+  /** The ID of the stream in the API. */
+  id: number;
+  /** The device the stream was opened on. */
+  device: { name: string; uniqueId: string };
+  /** The format of the stream. */
+  format: FormatInfoWithId;
+  /** The sequence number of the last captured frame, if any. */
+  sequence?: number;
+  /** The number of frames dropped so far. */
+  droppedFrames: number;
+  /** The number of times the stream reconnected. */
+  reconnects: number;
+}
+
+/**
+ * Represents the state of a property.
+ */
+export interface PropertyResource {
+  /** The name of the property, such as `exposure`. */
+  name: string;
+  /** The ID of the property. */
+  id: CapPropertyID;
+  /** The current value of the property. */
+  value: number;
+  /** Whether automatic mode is enabled. */
+  auto: boolean;
+  /** The range of values supported by the property. */
+  limits: PropertyLimits;
+}
+
+/**
+ * Represents a property that could not be read while listing properties.
+ */
+export interface PropertyFailure {
+  /** The name of the property, such as `exposure`. */
+  name: string;
+  /** The ID of the property. */
+  id: CapPropertyID;
+  /** Why the property could not be read. */
+  error: ErrorBody;
+}
+
+/**
+ * Represents an error in a response body.
+ */
+export interface ErrorBody {
+  /** The name of the error, such as `PropertyNotSupportedError`. */
+  name: string;
+  /** The error message. */
+  message: string;
+  /** The CapResult code, if a CameraError was thrown. */
+  code?: number;
+  /** The operation that failed, if a CameraError was thrown. */
+  operation?: string;
+}
+
+/** Names of the properties in URLs, the enum keys in camel case. */
+const PROPERTY_NAMES = new Map<string, CapPropertyID>(
+  Object.entries(CapPropertyID)
+    .filter((entry): entry is [string, CapPropertyID] =>
+      typeof entry[1] === "number" && entry[1] !== CapPropertyID.Last
+    )
+    .map(([name, id]) => [name[0].toLowerCase() + name.slice(1), id]),
+);
+
+/**
+ * Represents a failed request, answered with its status.
+ */
+class HttpError extends Error {
+  readonly status: number;
+  readonly headers?: HeadersInit;
+  constructor(status: number, message: string, headers?: HeadersInit) {
+    super(message);
+    this.name = "HttpError";
+    this.status = status;
+    this.headers = headers;
+  }
+}
+
+/**
+ * Creates a request handler exposing the devices of a camera as a REST API.
+ *
+ * | method | path                                         | action            |
+ * | ------ | -------------------------------------------- | ----------------- |
+ * | GET    | `/devices`                                   | list devices      |
+ * | GET    | `/devices/{device}`                          | describe a device |
+ * | POST   | `/devices/{device}/streams`                  | open a stream     |
+ * | GET    | `/streams`                                   | list streams      |
+ * | GET    | `/streams/{id}`                              | describe a stream |
+ * | DELETE | `/streams/{id}`                              | close a stream    |
+ * | GET    | `/streams/{id}/properties`                   | read properties   |
+ * | GET    | `/streams/{id}/properties/{property}`        | read a property   |
+ * | PUT    | `/streams/{id}/properties/{property}`        | set a property    |
+ * | GET    | `/streams/{id}/properties/{property}/limits` | read its limits   |
+ * | GET    | `/openapi.json`                              | describe the API  |
+ *
+ * Devices are addressed by their unique id, URL encoded, or by their `id`
+ * when the driver reports no unique id. Streams are opened with a
+ * `{ format, options }` body, the format being a format ID or constraints.
+ * Properties are named after `CapPropertyID` in camel case, such as
+ * `whiteBalance`, or given by their numeric ID, and set with an
+ * `{ auto, value }` body, automatic mode being applied first. Listing the
+ * properties skips the unsupported ones, and reports the others that fail
+ * with an `error` instead of their state. Errors are answered with a JSON
+ * `{ error: { name, message } }` body.
+ * @param camera - The camera whose devices are controlled.
+ * @param options - The path prefix and the signal closing the streams.
+ * @returns A handler responding to the endpoints, and with 404 otherwise.
+ */
+export function controlHandler(
+  camera: Camera,
+  options: ControlHandlerOptions = {},
+): (request: Request) => Promise<Response> {
+  const basePath = (options.basePath ?? "").replace(/\/+$/, "");
+  const streams = new Map<number, StreamRecord>();
+  let nextStreamId = 1;
+  options.signal?.addEventListener("abort", () => {
+    for (const { stream } of streams.values()) stream[Symbol.dispose]();
+    streams.clear();
+  }, { once: true });
+
+  const findDevice = (key: string): Device => {
+    const device = camera.findDevice({ uniqueId: key }) ??
+      camera.findDevice({
+        predicate: (info) => !info.uniqueId && String(info.id) === key,
+      });
+    if (!device) {
+      throw new DeviceNotFoundError(`No device with unique id or id ${key}`, {
+        operation: "findDevice",
+      });
+    }
+    return device;
+  };
+  const findStream = (id: string): StreamRecord => {
+    const record = streams.get(Number(id));
+    if (!record) throw new HttpError(404, `No stream with id ${id}`);
+    return record;
+  };
+  const describeStream = (
+    { id, stream, device, format }: StreamRecord,
+  ): StreamResource => ({
+    id,
+    device: { name: device.name(), uniqueId: device.uniqueId() },
+    format,
+    sequence: stream.sequence,
+    droppedFrames: stream.droppedFrames,
+    reconnects: stream.reconnects,
+  });
+
+  const route = async (
+    method: string,
+    segments: string[],
+    request: Request,
+  ): Promise<Response> => {
+    const [collection, key, sub, property, leaf] = segments;
+    const allow = (...methods: string[]) => {
+      if (!methods.includes(method)) {
+        throw new HttpError(405, `Method ${method} not allowed`, {
+          allow: methods.join(", "),
+        });
+      }
+    };
+
+    if (collection === "openapi.json" && segments.length === 1) {
+      allow("GET");
+      return json(openApiDocument(basePath));
+    }
+
+    if (collection === "devices") {
+      if (segments.length === 1) {
+        allow("GET");
+        return json(camera.devices().map((device) => device.info()));
+      }
+      const device = findDevice(key);
+      if (segments.length === 2) {
+        allow("GET");
+        return json(device.info());
+      }
+      if (sub === "streams" && segments.length === 3) {
+        allow("POST");
+        const body = await readBody(request) as OpenStreamBody;
+        const format = parseFormat(device, body.format ?? {});
+        const stream = device.stream(format, body.options ?? {});
+        if (!stream) {
+          throw new CameraError(
+            `Could not open a stream on ${device.name()}`,
+            { operation: "Device.stream", deviceId: device.info().id },
+          );
+        }
+        const record = { id: nextStreamId++, stream, device, format };
+        streams.set(record.id, record);
+        return json(describeStream(record), 201, {
+          location: `${basePath}/streams/${record.id}`,
+        });
+      }
+    }
+
+    if (collection === "streams") {
+      if (segments.length === 1) {
+        allow("GET");
+        return json([...streams.values()].map(describeStream));
+      }
+      const record = findStream(key);
+      if (segments.length === 2) {
+        allow("GET", "DELETE");
+        if (method === "DELETE") {
+          streams.delete(record.id);
+          record.stream[Symbol.dispose]();
+          return new Response(null, { status: 204 });
+        }
+        return json(describeStream(record));
+      }
+      if (sub === "properties") {
+        const { properties } = record.stream;
+        if (segments.length === 3) {
+          allow("GET");
+          const states: (PropertyResource | PropertyFailure)[] = [];
+          for (const [name, id] of PROPERTY_NAMES) {
+            try {
+              states.push(readProperty(record.stream, name, id));
+            } catch (error) {
+              if (error instanceof PropertyNotSupportedError) continue;
+              states.push({ name, id, error: errorBody(error) });
+            }
+          }
+          return json(states);
+        }
+        const [name, id] = parseProperty(property);
+        if (segments.length === 4) {
+          allow("GET", "PUT");
+          if (method === "PUT") {
+            const body = await readBody(request) as PropertyUpdateBody;
+            if (body.auto !== undefined && typeof body.auto !== "boolean") {
+              throw new HttpError(400, "auto must be a boolean");
+            }
+            if (body.value !== undefined && !Number.isFinite(body.value)) {
+              throw new HttpError(400, "value must be a finite number");
+            }
+            if (body.auto !== undefined) properties.setAuto(id, body.auto);
+            if (body.value !== undefined) properties.set(id, body.value);
+          }
+          return json(readProperty(record.stream, name, id));
+        }
+        if (leaf === "limits" && segments.length === 5) {
+          allow("GET");
+          return json(properties.limits(id));
+        }
+      }
+    }
+    throw new HttpError(404, "Not found");
+  };
+
+  return async (request) => {
+    const { pathname } = new URL(request.url);
+    try {
+      if (pathname !== basePath && !pathname.startsWith(`${basePath}/`)) {
+        throw new HttpError(404, "Not found");
+      }
+      const segments = pathname.slice(basePath.length).split("/")
+        .filter((segment) => segment !== "")
+        .map(decodePathSegment);
+      return await route(request.method, segments, request);
+    } catch (error) {
+      return errorResponse(error);
+    }
+  };
+}
+
+/**
+ * Serves the control API of a camera over HTTP with `Deno.serve`.
+ * See `controlHandler` for the endpoints. The streams opened through the API
+ * are closed when the server shuts down. It listens on port 8082 of
+ * `127.0.0.1` by default, next to the frame servers on port 8080.
+ * @param camera - The camera whose devices are controlled.
+ * @param options - The address to listen on and the path prefix.
+ * @returns The server, which can be shut down with `shutdown()`.
+ */
+export function serveControl(
+  camera: Camera,
+  options: ControlServeOptions = {},
+): Deno.HttpServer<Deno.NetAddr> {
+  const {
+    port = 8082,
+    hostname = "127.0.0.1",
+    signal,
+    onListen,
+    basePath,
+  } = options;
+  const closed = new AbortController();
+  const server = Deno.serve(
+    { port, hostname, signal, onListen },
+    controlHandler(camera, { basePath, signal: closed.signal }),
+  );
+  server.finished.finally(() => closed.abort());
+  return server;
+}
+
+interface StreamRecord {
+  id: number;
+  stream: Stream;
+  device: Device;
+  format: FormatInfoWithId;
+}
+
+interface OpenStreamBody {
+  format?: FormatConstraints | { id: number };
+  options?: StreamOptions;
+}
+
+interface PropertyUpdateBody {
+  value?: number;
+  auto?: boolean;
+}
+
+function json(body: unknown, status = 200, headers?: HeadersInit): Response {
+  return Response.json(body, { status, headers });
+}
+
+function decodePathSegment(segment: string): string {
+  try {
+    return decodeURIComponent(segment);
+  } catch {
+    throw new HttpError(400, `Malformed URL encoding in ${segment}`);
+  }
+}
+
+async function readBody(request: Request): Promise<Record<string, unknown>> {
+  const text = await request.text();
+  if (!text) return {};
+  let body: unknown;
+  try {
+    body = JSON.parse(text);
+  } catch {
+    throw new HttpError(400, "The body is not valid JSON");
+  }
+  if (typeof body !== "object" || body === null || Array.isArray(body)) {
+    throw new HttpError(400, "The body must be a JSON object");
+  }
+  return body as Record<string, unknown>;
+}
+
+function parseFormat(
+  device: Device,
+  format: FormatConstraints | { id: number },
+): FormatInfoWithId {
+  if (typeof format !== "object" || format === null) {
+    throw new HttpError(400, "format must be an object");
+  }
+  return resolveFormat(device.info(), format, "selectFormat");
+}
+
+/** Finds a property by name or numeric ID. */
+function parseProperty(property: string): [string, CapPropertyID] {
+  const byName = PROPERTY_NAMES.get(property);
+  if (byName !== undefined) return [property, byName];
+  for (const [name, id] of PROPERTY_NAMES) {
+    if (String(id) === property) return [name, id];
+  }
+  throw new HttpError(404, `Unknown property ${property}`);
+}
+
+function readProperty(
+  stream: Stream,
+  name: string,
+  id: CapPropertyID,
+): PropertyResource {
+  const { properties } = stream;
+  return {
+    name,
+    id,
+    value: properties.get(id),
+    auto: properties.isAuto(id),
+    limits: properties.limits(id),
+  };
+}
+
+function errorBody(error: unknown): ErrorBody {
+  const { name, message } = error instanceof Error
+    ? error
+    : { name: "Error", message: String(error) };
+  return error instanceof CameraError
+    ? { name, message, code: error.code, operation: error.operation }
+    : { name, message };
+}
+
+function errorResponse(error: unknown): Response {
+  let status = 500;
+  let headers: HeadersInit | undefined;
+  if (error instanceof HttpError) {
+    status = error.status;
+    headers = error.headers;
+  } else if (error instanceof DeviceNotFoundError) {
+    status = 404;
+  } else if (
+    error instanceof FormatNotSupportedError ||
+    error instanceof PropertyNotSupportedError
+  ) {
+    status = 422;
+  } else if (error instanceof RangeError || error instanceof TypeError) {
+    status = 400;
+  }
+  return json({ error: errorBody(error) }, status, headers);
+}
+
+/**
+ * Creates the OpenAPI description of the control API.
+ * @param basePath - The path prefix of all endpoints.
+ * @returns An OpenAPI 3.1 document.
+ */
+export function openApiDocument(basePath = ""): Record<string, unknown> {
+  const ref = (name: string) => ({ $ref: `#/components/schemas/${name}` });
+  const content = (schema: unknown) => ({
+    "application/json": { schema },
+  });
+  const ok = (description: string, schema: unknown) => ({
+    description,
+    content: content(schema),
+  });
+  const error = (description: string) => ({
+    description,
+    content: content(ref("Error")),
+  });
+  const deviceParameter = {
+    name: "device",
+    in: "path",
+    required: true,
+    description:
+      "The unique id of the device, URL encoded, or its id if it has none.",
+    schema: { type: "string" },
+  };
+  const streamParameter = {
+    name: "id",
+    in: "path",
+    required: true,
+    schema: { type: "integer" },
+  };
+  const propertyParameter = {
+    name: "property",
+    in: "path",
+    required: true,
+    description: "The camel case name or the numeric ID of the property.",
+    schema: {
+      oneOf: [
+        { type: "string", enum: [...PROPERTY_NAMES.keys()] },
+        { type: "integer" },
+      ],
+    },
+  };
+  const range = {
+    type: "object",
+    properties: {
+      exact: { type: "number" },
+      ideal: { type: "number" },
+      min: { type: "number" },
+      max: { type: "number" },
+    },
+  };
+  const constrainNumber = { oneOf: [{ type: "number" }, range] };
+  const codes = {
+    oneOf: [{ type: "string" }, { type: "array", items: { type: "string" } }],
+  };
+
+  return {
+    openapi: "3.1.0",
+    info: {
+      title: "Camera control API",
+      version: "1.0.0",
+      description:
+        "Lists camera devices, opens streams and controls their properties.",
+    },
+    servers: [{ url: basePath || "/" }],
+    paths: {
+      "/devices": {
+        get: {
+          summary: "List the devices and their formats",
+          responses: {
+            200: ok("The devices", { type: "array", items: ref("Device") }),
+          },
+        },
+      },
+      "/devices/{device}": {
+        parameters: [deviceParameter],
+        get: {
+          summary: "Describe a device",
+          responses: {
+            200: ok("The device", ref("Device")),
+            400: error("The device is not URL encoded properly"),
+            404: error("No device has this unique id"),
+          },
+        },
+      },
+      "/devices/{device}/streams": {
+        parameters: [deviceParameter],
+        post: {
+          summary: "Open a stream on a device",
+          requestBody: { content: content(ref("OpenStream")) },
+          responses: {
+            201: ok("The opened stream", ref("Stream")),
+            400: error("The body or the device is invalid"),
+            404: error("No device has this unique id"),
+            422: error("No format satisfies the constraints"),
+          },
+        },
+      },
+      "/streams": {
+        get: {
+          summary: "List the streams opened through the API",
+          responses: {
+            200: ok("The streams", { type: "array", items: ref("Stream") }),
+          },
+        },
+      },
+      "/streams/{id}": {
+        parameters: [streamParameter],
+        get: {
+          summary: "Describe a stream",
+          responses: {
+            200: ok("The stream", ref("Stream")),
+            404: error("No stream has this id"),
+          },
+        },
+        delete: {
+          summary: "Close a stream",
+          responses: {
+            204: { description: "The stream was closed" },
+            404: error("No stream has this id"),
+          },
+        },
+      },
+      "/streams/{id}/properties": {
+        parameters: [streamParameter],
+        get: {
+          summary: "Read the properties supported by the device",
+          responses: {
+            200: ok("The properties, or why they could not be read", {
+              type: "array",
+              items: { oneOf: [ref("Property"), ref("PropertyFailure")] },
+            }),
+            404: error("No stream has this id"),
+          },
+        },
+      },
+      "/streams/{id}/properties/{property}": {
+        parameters: [streamParameter, propertyParameter],
+        get: {
+          summary: "Read a property",
+          responses: {
+            200: ok("The property", ref("Property")),
+            404: error("No stream or property has this id"),
+            422: error("The device doesn't support the property"),
+          },
+        },
+        put: {
+          summary: "Set the automatic mode, then the value of a property",
+          requestBody: { content: content(ref("PropertyUpdate")) },
+          responses: {
+            200: ok("The updated property", ref("Property")),
+            400: error("The body is invalid"),
+            404: error("No stream or property has this id"),
+            422: error("The device doesn't support the property"),
+          },
+        },
+      },
+      "/streams/{id}/properties/{property}/limits": {
+        parameters: [streamParameter, propertyParameter],
+        get: {
+          summary: "Read the limits of a property",
+          responses: {
+            200: ok("The limits", ref("PropertyLimits")),
+            404: error("No stream or property has this id"),
+            422: error("The device doesn't support the property"),
+          },
+        },
+      },
+    },
+    components: {
+      schemas: {
+        Format: {
+          type: "object",
+          required: ["id", "width", "height", "fourcc", "fps", "bpp"],
+          properties: {
+            id: { type: "integer" },
+            width: { type: "integer" },
+            height: { type: "integer" },
+            fourcc: { type: "string" },
+            fps: { type: "number" },
+            bpp: { type: "integer" },
+          },
+        },
+        Device: {
+          type: "object",
+          required: ["id", "name", "formats"],
+          properties: {
+            id: { type: "integer" },
+            name: { type: "string" },
+            uniqueId: { type: "string" },
+            formats: { type: "array", items: ref("Format") },
+          },
+        },
+        OpenStream: {
+          type: "object",
+          properties: {
+            format: {
+              description:
+                "The ID of a format, or constraints selecting the best one.",
+              oneOf: [
+                {
+                  type: "object",
+                  required: ["id"],
+                  properties: { id: { type: "integer" } },
+                },
+                {
+                  type: "object",
+                  properties: {
+                    width: constrainNumber,
+                    height: constrainNumber,
+                    fps: constrainNumber,
+                    aspectRatio: constrainNumber,
+                    fourcc: {
+                      oneOf: [
+                        ...codes.oneOf,
+                        {
+                          type: "object",
+                          properties: { exact: codes, ideal: codes },
+                        },
+                      ],
+                    },
+                  },
+                },
+              ],
+            },
+            options: {
+              type: "object",
+              properties: {
+                frameTimeout: { type: "number" },
+                reconnect: {
+                  oneOf: [
+                    { type: "boolean" },
+                    {
+                      type: "object",
+                      properties: {
+                        retries: { type: "number" },
+                        initialDelay: { type: "number" },
+                        maxDelay: { type: "number" },
+                        factor: { type: "number" },
+                      },
+                    },
+                  ],
+                },
+                dropPolicy: {
+                  oneOf: [
+                    { type: "string", enum: ["latest", "block"] },
+                    {
+                      type: "object",
+                      required: ["queue"],
+                      properties: { queue: { type: "integer" } },
+                    },
+                  ],
+                },
+              },
+            },
+          },
+        },
+        Stream: {
+          type: "object",
+          required: ["id", "device", "format", "droppedFrames", "reconnects"],
+          properties: {
+            id: { type: "integer" },
+            device: {
+              type: "object",
+              properties: {
+                name: { type: "string" },
+                uniqueId: { type: "string" },
+              },
+            },
+            format: ref("Format"),
+            sequence: { type: "integer" },
+            droppedFrames: { type: "integer" },
+            reconnects: { type: "integer" },
+          },
+        },
+        PropertyLimits: {
+          type: "object",
+          required: ["min", "max", "default"],
+          properties: {
+            min: { type: "number" },
+            max: { type: "number" },
+            default: { type: "number" },
+          },
+        },
+        Property: {
+          type: "object",
+          required: ["name", "id", "value", "auto", "limits"],
+          properties: {
+            name: { type: "string" },
+            id: { type: "integer" },
+            value: { type: "number" },
+            auto: { type: "boolean" },
+            limits: ref("PropertyLimits"),
+          },
+        },
+        PropertyFailure: {
+          type: "object",
+          required: ["name", "id", "error"],
+          properties: {
+            name: { type: "string" },
+            id: { type: "integer" },
+            error: ref("ErrorBody"),
+          },
+        },
+        PropertyUpdate: {
+          type: "object",
+          properties: {
+            auto: { type: "boolean" },
+            value: { type: "number" },
+          },
+        },
+        ErrorBody: {
+          type: "object",
+          required: ["name", "message"],
+          properties: {
+            name: { type: "string" },
+            message: { type: "string" },
+            code: { type: "integer" },
+            operation: { type: "string" },
+          },
+        },
+        Error: {
+          type: "object",
+          required: ["error"],
+          properties: { error: ref("ErrorBody") },
+        },
+      },
+    },
+  };
+}
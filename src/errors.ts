@@ -0,0 +1,158 @@
+/**
+ * Provides the errors thrown by the library, mapped from openpnp-capture result codes.
+ *
+ * @example
+ * ```ts
+ * import { Camera, FormatNotSupportedError } from "jsr:@sigma/camera";
+ *
+ * using cam = new Camera();
+ * const device = cam.devices().at(0);
+ * if (!device) throw new Error("no device found");
+ * try {
+ *   using stream = device.stream(device.formats()[0]);
+ * } catch (e) {
+ *   if (e instanceof FormatNotSupportedError) {
+ *     console.log("format rejected by", e.operation, "with code", e.code);
+ *   } else {
+ *     throw e;
+ *   }
+ * }
+ * ```
+ *
+ * @module
+ */
+
+import {
+  CAPRESULT_DEVICENOTFOUND,
+  CAPRESULT_ERR,
+  CAPRESULT_FORMATNOTSUPPORTED,
+  CAPRESULT_PROPERTYNOTSUPPORTED,
+} from "./ffi.ts";
+
+/**
+ * Represents the context attached to a CameraError.
+ */
+export interface CameraErrorOptions extends ErrorOptions {
+  /** The raw CapResult code, defaults to `CAPRESULT_ERR`. */
+  code?: number;
+  /** The operation that failed, usually the name of the native function. */
+  operation: string;
+  /** The ID of the device involved, if any. */
+  deviceId?: number;
+  /** The ID of the stream involved, if any. */
+  streamId?: number;
+}
+
+/**
+ * Represents an error reported by a capture backend.
+ */
+export class CameraError extends Error {
+  /** The raw CapResult code. */
+  readonly code: number;
+  /** The operation that failed. */
+  readonly operation: string;
+  /** The ID of the device involved, if any. */
+  readonly deviceId?: number;
+  /** The ID of the stream involved, if any. */
+  readonly streamId?: number;
+  /**
+   * Constructs an instance of the CameraError class.
+   * @param message - The description of the error.
+   * @param options - The context of the error.
+   */
+  constructor(message: string, options: CameraErrorOptions) {
+    super(message, options);
+    this.name = new.target.name;
+    this.code = options.code ?? CAPRESULT_ERR;
+    this.operation = options.operation;
+    this.deviceId = options.deviceId;
+    this.streamId = options.streamId;
+  }
+}
+
+/**
+ * Represents an error raised when a device doesn't exist (anymore).
+ */
+export class DeviceNotFoundError extends CameraError {
+  /**
+   * Constructs an instance of the DeviceNotFoundError class.
+   * @param message - The description of the error.
+   * @param options - The context of the error.
+   */
+  constructor(message: string, options: CameraErrorOptions) {
+    super(message, { code: CAPRESULT_DEVICENOTFOUND, ...options });
+  }
+}
+
+/**
+ * Represents an error raised when a device doesn't support a format.
+ */
+export class FormatNotSupportedError extends CameraError {
+  /**
+   * Constructs an instance of the FormatNotSupportedError class.
+   * @param message - The description of the error.
+   * @param options - The context of the error.
+   */
+  constructor(message: string, options: CameraErrorOptions) {
+    super(message, { code: CAPRESULT_FORMATNOTSUPPORTED, ...options });
+  }
+}
+
+/**
+ * Represents an error raised when a device doesn't support a property.
+ */
+export class PropertyNotSupportedError extends CameraError {
+  /**
+   * Constructs an instance of the PropertyNotSupportedError class.
+   * @param message - The description of the error.
+   * @param options - The context of the error.
+   */
+  constructor(message: string, options: CameraErrorOptions) {
+    super(message, { code: CAPRESULT_PROPERTYNOTSUPPORTED, ...options });
+  }
+}
+
+/**
+ * Represents an error raised when a stream delivered no frame within its frame timeout.
+ */
+export class StreamStalledError extends CameraError {}
+
+/**
+ * Creates the error matching a CapResult code.
+ * @param code - The CapResult code returned by the operation.
+ * @param options - The context of the error.
+ * @returns A CameraError, or the subclass matching the code.
+ */
+export function errorFromResult(
+  code: number,
+  options: Omit<CameraErrorOptions, "code">,
+): CameraError {
+  const context = [
+    `code ${code}`,
+    options.deviceId !== undefined ? `device ${options.deviceId}` : undefined,
+    options.streamId !== undefined ? `stream ${options.streamId}` : undefined,
+  ].filter((part) => part !== undefined).join(", ");
+
+  switch (code) {
+    case CAPRESULT_DEVICENOTFOUND:
+      return new DeviceNotFoundError(
+        `${options.operation} failed: device not found (${context})`,
+        { ...options, code },
+      );
+    case CAPRESULT_FORMATNOTSUPPORTED:
+      return new FormatNotSupportedError(
+        `${options.operation} failed: format not supported (${context})`,
+        { ...options, code },
+      );
+    case CAPRESULT_PROPERTYNOTSUPPORTED:
+      return new PropertyNotSupportedError(
+        `${options.operation} failed: property not supported (${context})`,
+        { ...options, code },
+      );
+    default:
+      return new CameraError(`${options.operation} failed (${context})`, {
+        ...options,
+        code,
+      });
+  }
+}
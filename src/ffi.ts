@@ -11,10 +11,13 @@
  *   CAPPROPID_WHITEBALANCE,
  *   CAPRESULT_OK,
  *   LIBRARY,
+ *   loadLibrary,
  * } from "jsr:@sigma/camera/ffi";
+ * import { encodeNetpbm, Frame } from "jsr:@sigma/camera";
  *
  * if (import.meta.main) {
  *   console.log("OpenPnp Capture Test Program");
+ *   await loadLibrary();
  *   const versionPtr = LIBRARY.symbols.Cap_getLibraryVersion();
  *   if (!versionPtr) throw new Error("getLibraryVersion returned null ptr");
  *   const version = Deno.UnsafePointerView.getCString(versionPtr);
@@ -137,18 +140,19 @@
  *         CAPRESULT_OK
  *     ) {
  *       console.log("Frame captured");
- *       if (
- *         writeBufferAsPPM(
- *           ++frameWriteCounter,
- *           finfo.width,
- *           finfo.height,
- *           buffer,
- *         )
- *       ) {
- *         console.log(`Written frame to frame_${frameWriteCounter}.ppm`);
- *       } else {
- *         console.log(`Failed to write frame to frame_${frameWriteCounter}.ppm`);
- *       }
+ *       const frame = new Frame({
+ *         data: buffer,
+ *         width: finfo.width,
+ *         height: finfo.height,
+ *         pixelFormat: "rgb24",
+ *         timestamp: Date.now(),
+ *         sequence: ++frameWriteCounter,
+ *       });
+ *       Deno.writeFileSync(
+ *         `frame_${frameWriteCounter}.ppm`,
+ *         encodeNetpbm(frame),
+ *       );
+ *       console.log(`Written frame to frame_${frameWriteCounter}.ppm`);
  *     }
  *   }
  *
@@ -168,21 +172,6 @@
  *   }
  *   return v;
  * }
- *
- * function writeBufferAsPPM(
- *   frameNum: number,
- *   width: number,
- *   height: number,
- *   buffer: Uint8Array,
- * ): boolean {
- *   const ENCODER = new TextEncoder();
- *   const fname = `frame_${frameNum}.ppm`;
- *   const fout = Deno.createSync(fname);
- *   fout.writeSync(ENCODER.encode(`P6 ${width} ${height} 255\n`));
- *   fout.writeSync(buffer);
- *   fout.close();
- *   return true;
- * }
  * ```
  *
  * @module
@@ -317,6 +306,14 @@ export const CAPRESULT_FORMATNOTSUPPORTED = 3;
  */
 export const CAPRESULT_PROPERTYNOTSUPPORTED = 4;
 
+/**
+ * Represents the signature of a custom log function.
+ */
+export const CapCustomLogFunc = {
+  parameters: ["u32", "pointer"],
+  result: "void",
+} as const;
+
 /**
  * Represents the context of capability operations.
  */
@@ -333,6 +330,10 @@ const SYMBOLS = {
     parameters: [CapContext, CapDeviceID],
     result: "pointer",
   },
+  Cap_getDeviceUniqueID: {
+    parameters: [CapContext, CapDeviceID],
+    result: "pointer",
+  },
   Cap_getNumFormats: {
     parameters: [CapContext, CapDeviceID],
     result: "i32",
@@ -349,6 +350,10 @@ const SYMBOLS = {
     parameters: ["u32"],
     result: "void",
   },
+  Cap_installCustomLogFunction: {
+    parameters: ["function"],
+    result: "void",
+  },
   Cap_openStream: {
     parameters: [CapContext, CapDeviceID, CapFormatId],
     result: CapStream,
@@ -357,10 +362,22 @@ const SYMBOLS = {
     parameters: [CapContext, CapStream],
     result: "u32",
   },
+  Cap_setProperty: {
+    parameters: [CapContext, CapStream, CapPropertyID, "i32"],
+    result: CapResult,
+  },
+  Cap_getProperty: {
+    parameters: [CapContext, CapStream, CapPropertyID, "buffer"],
+    result: CapResult,
+  },
   Cap_setAutoProperty: {
     parameters: [CapContext, CapStream, CapPropertyID, "u32"],
     result: CapResult,
   },
+  Cap_getAutoProperty: {
+    parameters: [CapContext, CapStream, CapPropertyID, "buffer"],
+    result: CapResult,
+  },
   Cap_getPropertyLimits: {
     parameters: [
       CapContext,
@@ -388,45 +405,196 @@ const SYMBOLS = {
     parameters: [CapContext, CapStream],
     result: "u32",
   },
+  Cap_getStreamFrameCount: {
+    parameters: [CapContext, CapStream],
+    result: "u32",
+  },
 } as const;
 
+/**
+ * Represents the loaded openpnp-capture library.
+ */
+export type Library = Deno.DynamicLibrary<typeof SYMBOLS>;
+
+/**
+ * Represents the options used to locate the openpnp-capture library.
+ */
+export interface LibraryOptions {
+  /** Explicit path to the libopenpnp-capture shared library. */
+  path?: string;
+  /** Whether to download the library when it can't be found locally, defaults to true. */
+  download?: boolean;
+}
+
+/**
+ * Represents the environment variable holding the path to the library.
+ */
+export const LIBRARY_PATH_ENV = "OPENPNP_CAPTURE_LIB";
+
+interface LoadedLibrary {
+  instance: Library;
+  path: string;
+}
+
+let library: LoadedLibrary | undefined;
+/** Whether symbols of the library were accessed, so native handles may point into it. */
+let libraryUsed = false;
+
 /**
  * Represents the dynamic library instance.
+ *
+ * The library is opened lazily on first use from the path in
+ * `OPENPNP_CAPTURE_LIB` or the system library search path. Call
+ * `loadLibrary` beforehand to use an explicit path or to download it.
  */
-export const LIBRARY: Deno.DynamicLibrary<typeof SYMBOLS> = await instantiate();
+export const LIBRARY: Pick<Library, "symbols"> = {
+  get symbols() {
+    const { symbols } = loadedLibrary().instance;
+    libraryUsed = true;
+    return symbols;
+  },
+};
+
+/**
+ * Retrieves the path `LIBRARY` was opened from, so other threads can open the same library.
+ * @returns The path of the library, or its name when found in the system search path.
+ */
+export function libraryPath(): string {
+  return loadedLibrary().path;
+}
+
+/**
+ * Loads the library used by `LIBRARY`, if it wasn't already loaded.
+ *
+ * Another path replaces the loaded library only as long as none of its
+ * symbols were used, since contexts and streams point into it.
+ * @param options - Options to locate the library.
+ * @returns A promise resolving to the loaded library.
+ * @throws {Error} If another library is already in use.
+ */
+export async function loadLibrary(
+  options: LibraryOptions = {},
+): Promise<Library> {
+  if (
+    library && (options.path === undefined || options.path === library.path)
+  ) {
+    return library.instance;
+  }
+  if (library && libraryUsed) {
+    throw new Error(
+      `libopenpnp-capture is already in use from ${library.path}, call loadLibrary() before creating any context`,
+    );
+  }
+  const loaded = await locate(options);
+  library?.instance.close();
+  library = loaded;
+  return library.instance;
+}
 
 /**
  * Instantiates the dynamic library.
+ *
+ * The library is looked up in order from `options.path`, the
+ * `OPENPNP_CAPTURE_LIB` environment variable and the system library search
+ * path, and is downloaded from the openpnp-capture releases as a last resort.
+ * @param options - Options to locate the library.
  * @returns A promise resolving to a dynamic library instance.
  */
-export async function instantiate(): Promise<
-  Deno.DynamicLibrary<typeof SYMBOLS>
-> {
+export async function instantiate(
+  options: LibraryOptions = {},
+): Promise<Library> {
+  return (await locate(options)).instance;
+}
+
+function loadedLibrary(): LoadedLibrary {
+  library ??= openLocal();
+  if (!library) {
+    throw new Error(
+      `libopenpnp-capture not found, set ${LIBRARY_PATH_ENV} or call loadLibrary() to download it`,
+    );
+  }
+  return library;
+}
+
+async function locate(options: LibraryOptions): Promise<LoadedLibrary> {
+  const local = openLocal(options.path);
+  if (local) return local;
+  if (options.download === false) {
+    throw new Error(
+      `libopenpnp-capture not found locally and download is disabled`,
+    );
+  }
+  const path = await download();
+  return { instance: Deno.dlopen(path, SYMBOLS), path };
+}
+
+/**
+ * Opens the library from the file system without touching the network.
+ * @param path - Explicit path to the library.
+ * @returns The library, undefined if it wasn't found in the system search path.
+ */
+function openLocal(path?: string): LoadedLibrary | undefined {
+  // explicitly requested paths must exist, so errors are not swallowed
+  path ??= envLibraryPath();
+  if (path !== undefined) return { instance: Deno.dlopen(path, SYMBOLS), path };
+
+  for (const name of systemLibraryNames()) {
+    try {
+      return { instance: Deno.dlopen(name, SYMBOLS), path: name };
+    } catch {
+      // not in the search path
+    }
+  }
+  return undefined;
+}
+
+function envLibraryPath(): string | undefined {
+  try {
+    return Deno.env.get(LIBRARY_PATH_ENV) || undefined;
+  } catch {
+    // env access not granted
+    return undefined;
+  }
+}
+
+function systemLibraryNames(): string[] {
+  switch (Deno.build.os) {
+    case "windows":
+      return ["openpnp-capture.dll", "libopenpnp-capture.dll"];
+    case "darwin":
+      return ["libopenpnp-capture.dylib"];
+    default:
+      return ["libopenpnp-capture.so"];
+  }
+}
+
+/**
+ * Downloads the library from the openpnp-capture releases, or reuses the cached download.
+ * @returns A promise resolving to the path of the library.
+ */
+async function download(): Promise<string> {
   const name = "libopenpnp-capture";
   const version = "v0.0.28";
   const url =
     `https://github.com/openpnp/openpnp-capture/releases/download/${version}`;
 
-  return await plug.dlopen(
-    {
-      name,
-      url,
-      suffixes: {
-        linux: {
-          x86_64: "-ubuntu-20.04-x86_64",
-          aarch64: "-ubuntu-20.04-arm64",
-        },
-        darwin: {
-          x86_64: "-macos-latest-x86_64",
-          aarch64: "-macos-latest-arm64",
-        },
-        windows: {
-          x86_64: "-windows-latest-x86_64",
-        },
+  return await plug.download({
+    name,
+    url,
+    suffixes: {
+      linux: {
+        x86_64: "-ubuntu-20.04-x86_64",
+        aarch64: "-ubuntu-20.04-arm64",
+      },
+      darwin: {
+        x86_64: "-macos-latest-x86_64",
+        aarch64: "-macos-latest-arm64",
+      },
+      windows: {
+        x86_64: "-windows-latest-x86_64",
       },
-      // this line erases the default prefixes
-      prefixes: {},
     },
-    SYMBOLS,
-  );
+    // this line erases the default prefixes
+    prefixes: {},
+  });
 }
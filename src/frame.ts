@@ -0,0 +1,163 @@
+/**
+ * Provides the Frame class carrying captured pixels along with their metadata.
+ *
+ * @module
+ */
+
+import { encodeJPEG } from "./jpeg.ts";
+import type { JPEGEncodeOptions } from "./jpeg.ts";
+import { encodePNG } from "./png.ts";
+import type { PNGEncodeOptions } from "./png.ts";
+import type { PixelFormat } from "./types.ts";
+
+/**
+ * Represents the values used to construct a Frame.
+ */
+export interface FrameInit {
+  /** The pixel data. */
+  data: Uint8Array;
+  /** The width of the frame in pixels. */
+  width: number;
+  /** The height of the frame in pixels. */
+  height: number;
+  /** The layout of the pixel data. */
+  pixelFormat: PixelFormat;
+  /** The number of bytes between the start of two rows, defaults to a packed layout. */
+  stride?: number;
+  /** The capture time in milliseconds since the Unix epoch. */
+  timestamp: number;
+  /** The sequence number of the frame, as counted by the driver. */
+  sequence: number;
+  /** Whether the data is a buffer reused by the stream for the next frames. */
+  borrowed?: boolean;
+}
+
+/**
+ * Number of 8-bit samples per pixel of each pixel format.
+ */
+export const PIXEL_FORMAT_CHANNELS: Readonly<Record<PixelFormat, number>> = {
+  rgb24: 3,
+  rgba32: 4,
+  gray8: 1,
+};
+
+const released = new WeakSet<Frame>();
+
+/**
+ * Represents a captured video frame.
+ *
+ * A frame owns its data unless it was yielded by a stream in zero-copy mode. A
+ * borrowed frame is only valid until the stream captures the next frame,
+ * accessing its data afterwards throws; call `detach()` to keep it.
+ */
+export class Frame {
+  /** The width of the frame in pixels. */
+  readonly width: number;
+  /** The height of the frame in pixels. */
+  readonly height: number;
+  /** The layout of the pixel data. */
+  readonly pixelFormat: PixelFormat;
+  /** The number of samples per pixel. */
+  readonly channels: number;
+  /** The number of bytes between the start of two rows. */
+  readonly stride: number;
+  /** The capture time in milliseconds since the Unix epoch. */
+  readonly timestamp: number;
+  /** The sequence number of the frame, as counted by the driver. */
+  readonly sequence: number;
+  #data: Uint8Array;
+  #borrowed: boolean;
+  /**
+   * Constructs an instance of the Frame class.
+   * @param init - The pixel data and metadata of the frame.
+   */
+  constructor(init: FrameInit) {
+    this.#data = init.data;
+    this.width = init.width;
+    this.height = init.height;
+    this.pixelFormat = init.pixelFormat;
+    this.channels = PIXEL_FORMAT_CHANNELS[init.pixelFormat];
+    this.stride = init.stride ?? init.width * this.channels;
+    const minByteLength = this.stride * (init.height - 1) +
+      init.width * this.channels;
+    if (init.data.byteLength < minByteLength) {
+      throw new Error(
+        `Frame data is ${init.data.byteLength} bytes, too small for ${init.width}x${init.height} ${init.pixelFormat}`,
+      );
+    }
+    this.timestamp = init.timestamp;
+    this.sequence = init.sequence;
+    this.#borrowed = init.borrowed ?? false;
+  }
+
+  /**
+   * Retrieves the pixel data of the frame.
+   * @returns The pixel data.
+   */
+  get data(): Uint8Array {
+    if (released.has(this)) {
+      throw new Error(
+        "Frame data was overwritten by a newer frame, call detach() to keep it",
+      );
+    }
+    return this.#data;
+  }
+
+  /**
+   * Checks if the data is a buffer reused by the stream.
+   * @returns A boolean indicating if the frame is borrowed.
+   */
+  get borrowed(): boolean {
+    return this.#borrowed;
+  }
+
+  /**
+   * Copies the frame.
+   * @returns A new Frame owning a copy of the data.
+   */
+  clone(): Frame {
+    return new Frame({
+      data: this.data.slice(),
+      width: this.width,
+      height: this.height,
+      pixelFormat: this.pixelFormat,
+      stride: this.stride,
+      timestamp: this.timestamp,
+      sequence: this.sequence,
+    });
+  }
+
+  /**
+   * Retrieves a frame that stays valid after the stream moves on.
+   * @returns The frame itself if it owns its data, a copy otherwise.
+   */
+  detach(): Frame {
+    return this.#borrowed ? this.clone() : this;
+  }
+
+  /**
+   * Encodes the frame as a PNG image.
+   * @param options - The options of the encoder.
+   * @returns A promise resolving to the PNG file contents.
+   */
+  toPNG(options?: PNGEncodeOptions): Promise<Uint8Array> {
+    return encodePNG(this, options);
+  }
+
+  /**
+   * Encodes the frame as a baseline JPEG image.
+   * @param options - The options of the encoder.
+   * @returns The JPEG file contents.
+   */
+  toJPEG(options?: JPEGEncodeOptions): Uint8Array {
+    return encodeJPEG(this, options);
+  }
+}
+
+/**
+ * Marks a borrowed frame as no longer valid, because its buffer is about to be reused.
+ * @param frame - The frame to release.
+ */
+export function releaseFrame(frame: Frame) {
+  if (frame.borrowed) released.add(frame);
+}
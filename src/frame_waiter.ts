@@ -0,0 +1,96 @@
+/**
+ * Waits for new openpnp-capture frames from a worker, so the main event loop is never polled.
+ *
+ * openpnp-capture has no blocking wait nor frame callback, so this is not
+ * event-driven: the worker polls `Cap_hasNewFrame` every `POLL_INTERVAL` (1 ms)
+ * while a wait is pending, sleeping on a shared buffer otherwise, and wakes
+ * the main thread through that buffer once a frame is available. A frame is
+ * thus yielded at most about 1 ms after the driver received it, instead of up
+ * to the `delay` of the former `setTimeout` loop, at the cost of one thread
+ * per stream waking up every millisecond while waiting. A wait that times
+ * out stops the polling, so a stalled device leaves the worker asleep until
+ * the next wait. `bench/frame_latency_bench.ts`
+ * compares both on a real camera.
+ *
+ * @module
+ */
+
+import { libraryPath } from "./ffi.ts";
+
+/** Index of the flag set by the main thread when it waits for a frame. */
+export const REQUESTED = 0;
+/** Index of the flag set by the worker when a frame is available. */
+export const READY = 1;
+/** Index of the flag set by the main thread to stop the worker. */
+export const CLOSED = 2;
+
+/**
+ * Represents the message starting a frame waiter worker.
+ */
+export interface FrameWaiterInit {
+  /** The path of the openpnp-capture library. */
+  path: string;
+  /** The address of the openpnp-capture context. */
+  ctx: bigint;
+  /** The ID of the stream. */
+  streamId: number;
+  /** The flags shared with the main thread. */
+  flags: SharedArrayBuffer;
+}
+
+/**
+ * Represents a worker waiting for the frames of one stream.
+ */
+export class FrameWaiter {
+  #worker: Worker;
+  #flags: Int32Array;
+  /**
+   * Constructs an instance of the FrameWaiter class.
+   * @param ctx - The openpnp-capture context.
+   * @param streamId - The ID of the stream.
+   */
+  constructor(ctx: Deno.PointerValue<unknown>, streamId: number) {
+    const flags = new SharedArrayBuffer(3 * Int32Array.BYTES_PER_ELEMENT);
+    this.#flags = new Int32Array(flags);
+    this.#worker = new Worker(
+      new URL("./frame_waiter_worker.ts", import.meta.url).href,
+      { type: "module" },
+    );
+    this.#worker.postMessage(
+      {
+        path: libraryPath(),
+        ctx: BigInt(Deno.UnsafePointer.value(ctx)),
+        streamId,
+        flags,
+      } satisfies FrameWaiterInit,
+    );
+  }
+
+  /**
+   * Waits until a new frame is available.
+   * @param timeout - Maximum time to wait in milliseconds, unbounded by default.
+   * @returns A promise resolving to false if the timeout elapsed first.
+   */
+  async wait(timeout?: number): Promise<boolean> {
+    Atomics.store(this.#flags, READY, 0);
+    Atomics.store(this.#flags, REQUESTED, 1);
+    Atomics.notify(this.#flags, REQUESTED);
+    const result = Atomics.waitAsync(this.#flags, READY, 0, timeout);
+    const outcome = result.async ? await result.value : result.value;
+    if (outcome === "timed-out") {
+      Atomics.store(this.#flags, REQUESTED, 0);
+      return false;
+    }
+    return true;
+  }
+
+  /**
+   * Stops the worker.
+   */
+  close() {
+    Atomics.store(this.#flags, CLOSED, 1);
+    Atomics.notify(this.#flags, REQUESTED);
+    Atomics.notify(this.#flags, READY);
+    this.#worker.terminate();
+  }
+}
@@ -0,0 +1,47 @@
+/// <reference lib="deno.worker" />
+
+/**
+ * Worker polling `Cap_hasNewFrame` for a FrameWaiter.
+ *
+ * @module
+ */
+
+import { CapContext, CapStream } from "./ffi.ts";
+import { CLOSED, READY, REQUESTED } from "./frame_waiter.ts";
+import type { FrameWaiterInit } from "./frame_waiter.ts";
+
+/** Interval between two `Cap_hasNewFrame` calls while a wait is pending, in milliseconds. */
+const POLL_INTERVAL = 1;
+
+self.onmessage = (event: MessageEvent<FrameWaiterInit>) => {
+  const { path, ctx, streamId } = event.data;
+  const flags = new Int32Array(event.data.flags);
+  // opening an already loaded library shares its state, including the context
+  const library = Deno.dlopen(path, {
+    Cap_hasNewFrame: {
+      parameters: [CapContext, CapStream],
+      result: "u32",
+    },
+  });
+  const ctxPtr = Deno.UnsafePointer.create(ctx);
+
+  while (true) {
+    Atomics.wait(flags, REQUESTED, 0);
+    if (Atomics.load(flags, CLOSED)) break;
+    while (
+      library.symbols.Cap_hasNewFrame(ctxPtr, streamId) !== 1 &&
+      Atomics.load(flags, REQUESTED) &&
+      !Atomics.load(flags, CLOSED)
+    ) {
+      Atomics.wait(flags, CLOSED, 0, POLL_INTERVAL);
+    }
+    // the wait timed out, a stalled device is not polled until the next one
+    if (!Atomics.load(flags, REQUESTED)) continue;
+    Atomics.store(flags, REQUESTED, 0);
+    Atomics.store(flags, READY, 1);
+    Atomics.notify(flags, READY);
+  }
+
+  library.close();
+  self.close();
+};
@@ -0,0 +1,589 @@
+/**
+ * Provides a baseline JPEG encoder for frames, written in TypeScript.
+ *
+ * Images are encoded as sequential baseline JFIF with the standard Huffman
+ * tables of the JPEG specification, in color with 4:2:0 or 4:4:4 chroma
+ * subsampling, or in grayscale.
+ *
+ * @example
+ * ```ts
+ * import { Camera, encodeJPEG } from "jsr:@sigma/camera";
+ *
+ * using cam = new Camera();
+ * const device = cam.devices().at(0);
+ * if (!device) throw new Error("no device found");
+ * using stream = device.stream({ width: 1280, height: 720 });
+ * if (!stream) throw new Error("no stream found");
+ *
+ * const frame = await stream.capture();
+ * Deno.writeFileSync("frame.jpg", frame.toJPEG({ quality: 90 }));
+ * Deno.writeFileSync(
+ *   "frame-gray.jpg",
+ *   encodeJPEG(frame, { grayscale: true, subsampling: "4:4:4" }),
+ * );
+ * ```
+ *
+ * @module
+ */
+
+import type { Frame } from "./frame.ts";
+
+/**
+ * Represents how the chroma planes are sampled relative to the luma plane.
+ *
+ * - `4:2:0`: one chroma sample per 2x2 pixels, the usual trade-off for photos and video
+ * - `4:4:4`: one chroma sample per pixel, sharper colored edges at a larger size
+ */
+export type ChromaSubsampling = "4:2:0" | "4:4:4";
+
+/**
+ * Represents the options of the JPEG encoder.
+ */
+export interface JPEGEncodeOptions {
+  /** The quality from 1 to 100, scaling the standard quantization tables, defaults to 80. */
+  quality?: number;
+  /** The chroma subsampling of color images, defaults to `4:2:0`. */
+  subsampling?: ChromaSubsampling;
+  /** Whether to encode only the luma, which `gray8` frames always do. */
+  grayscale?: boolean;
+}
+
+/** Natural order index of each coefficient in zigzag order. */
+// deno-fmt-ignore
+const ZIGZAG = new Uint8Array([
+  0, 1, 8, 16, 9, 2, 3, 10, 17, 24, 32, 25, 18, 11, 4, 5,
+  12, 19, 26, 33, 40, 48, 41, 34, 27, 20, 13, 6, 7, 14, 21, 28,
+  35, 42, 49, 56, 57, 50, 43, 36, 29, 22, 15, 23, 30, 37, 44, 51,
+  58, 59, 52, 45, 38, 31, 39, 46, 53, 60, 61, 54, 47, 55, 62, 63,
+]);
+
+/** Luma quantization table of the specification (Annex K), in natural order. */
+// deno-fmt-ignore
+const LUMA_QUANTIZATION = [
+  16, 11, 10, 16, 24, 40, 51, 61,
+  12, 12, 14, 19, 26, 58, 60, 55,
+  14, 13, 16, 24, 40, 57, 69, 56,
+  14, 17, 22, 29, 51, 87, 80, 62,
+  18, 22, 37, 56, 68, 109, 103, 77,
+  24, 35, 55, 64, 81, 104, 113, 92,
+  49, 64, 78, 87, 103, 121, 120, 101,
+  72, 92, 95, 98, 112, 100, 103, 99,
+];
+
+/** Chroma quantization table of the specification (Annex K), in natural order. */
+// deno-fmt-ignore
+const CHROMA_QUANTIZATION = [
+  17, 18, 24, 47, 99, 99, 99, 99,
+  18, 21, 26, 66, 99, 99, 99, 99,
+  24, 26, 56, 99, 99, 99, 99, 99,
+  47, 66, 99, 99, 99, 99, 99, 99,
+  99, 99, 99, 99, 99, 99, 99, 99,
+  99, 99, 99, 99, 99, 99, 99, 99,
+  99, 99, 99, 99, 99, 99, 99, 99,
+  99, 99, 99, 99, 99, 99, 99, 99,
+];
+
+/** Number of codes of each length from 1 to 16 bits, and the symbols they encode. */
+interface HuffmanSpec {
+  counts: number[];
+  symbols: number[];
+}
+
+const LUMA_DC: HuffmanSpec = {
+  counts: [0, 1, 5, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0],
+  symbols: [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11],
+};
+
+const CHROMA_DC: HuffmanSpec = {
+  counts: [0, 3, 1, 1, 1, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0],
+  symbols: [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11],
+};
+
+const LUMA_AC: HuffmanSpec = {
+  counts: [0, 2, 1, 3, 3, 2, 4, 3, 5, 5, 4, 4, 0, 0, 1, 0x7D],
+  // deno-fmt-ignore
+  symbols: [
+    0x01, 0x02, 0x03, 0x00, 0x04, 0x11, 0x05, 0x12,
+    0x21, 0x31, 0x41, 0x06, 0x13, 0x51, 0x61, 0x07,
+    0x22, 0x71, 0x14, 0x32, 0x81, 0x91, 0xA1, 0x08,
+    0x23, 0x42, 0xB1, 0xC1, 0x15, 0x52, 0xD1, 0xF0,
+    0x24, 0x33, 0x62, 0x72, 0x82, 0x09, 0x0A, 0x16,
+    0x17, 0x18, 0x19, 0x1A, 0x25, 0x26, 0x27, 0x28,
+    0x29, 0x2A, 0x34, 0x35, 0x36, 0x37, 0x38, 0x39,
+    0x3A, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48, 0x49,
+    0x4A, 0x53, 0x54, 0x55, 0x56, 0x57, 0x58, 0x59,
+    0x5A, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68, 0x69,
+    0x6A, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78, 0x79,
+    0x7A, 0x83, 0x84, 0x85, 0x86, 0x87, 0x88, 0x89,
+    0x8A, 0x92, 0x93, 0x94, 0x95, 0x96, 0x97, 0x98,
+    0x99, 0x9A, 0xA2, 0xA3, 0xA4, 0xA5, 0xA6, 0xA7,
+    0xA8, 0xA9, 0xAA, 0xB2, 0xB3, 0xB4, 0xB5, 0xB6,
+    0xB7, 0xB8, 0xB9, 0xBA, 0xC2, 0xC3, 0xC4, 0xC5,
+    0xC6, 0xC7, 0xC8, 0xC9, 0xCA, 0xD2, 0xD3, 0xD4,
+    0xD5, 0xD6, 0xD7, 0xD8, 0xD9, 0xDA, 0xE1, 0xE2,
+    0xE3, 0xE4, 0xE5, 0xE6, 0xE7, 0xE8, 0xE9, 0xEA,
+    0xF1, 0xF2, 0xF3, 0xF4, 0xF5, 0xF6, 0xF7, 0xF8,
+    0xF9, 0xFA,
+  ],
+};
+
+const CHROMA_AC: HuffmanSpec = {
+  counts: [0, 2, 1, 2, 4, 4, 3, 4, 7, 5, 4, 4, 0, 1, 2, 0x77],
+  // deno-fmt-ignore
+  symbols: [
+    0x00, 0x01, 0x02, 0x03, 0x11, 0x04, 0x05, 0x21,
+    0x31, 0x06, 0x12, 0x41, 0x51, 0x07, 0x61, 0x71,
+    0x13, 0x22, 0x32, 0x81, 0x08, 0x14, 0x42, 0x91,
+    0xA1, 0xB1, 0xC1, 0x09, 0x23, 0x33, 0x52, 0xF0,
+    0x15, 0x62, 0x72, 0xD1, 0x0A, 0x16, 0x24, 0x34,
+    0xE1, 0x25, 0xF1, 0x17, 0x18, 0x19, 0x1A, 0x26,
+    0x27, 0x28, 0x29, 0x2A, 0x35, 0x36, 0x37, 0x38,
+    0x39, 0x3A, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48,
+    0x49, 0x4A, 0x53, 0x54, 0x55, 0x56, 0x57, 0x58,
+    0x59, 0x5A, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68,
+    0x69, 0x6A, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78,
+    0x79, 0x7A, 0x82, 0x83, 0x84, 0x85, 0x86, 0x87,
+    0x88, 0x89, 0x8A, 0x92, 0x93, 0x94, 0x95, 0x96,
+    0x97, 0x98, 0x99, 0x9A, 0xA2, 0xA3, 0xA4, 0xA5,
+    0xA6, 0xA7, 0xA8, 0xA9, 0xAA, 0xB2, 0xB3, 0xB4,
+    0xB5, 0xB6, 0xB7, 0xB8, 0xB9, 0xBA, 0xC2, 0xC3,
+    0xC4, 0xC5, 0xC6, 0xC7, 0xC8, 0xC9, 0xCA, 0xD2,
+    0xD3, 0xD4, 0xD5, 0xD6, 0xD7, 0xD8, 0xD9, 0xDA,
+    0xE2, 0xE3, 0xE4, 0xE5, 0xE6, 0xE7, 0xE8, 0xE9,
+    0xEA, 0xF2, 0xF3, 0xF4, 0xF5, 0xF6, 0xF7, 0xF8,
+    0xF9, 0xFA,
+  ],
+};
+
+/** Scale factors of the AAN forward DCT, which leaves them in its output. */
+const AAN_SCALES = [
+  1.0,
+  1.387039845,
+  1.306562965,
+  1.175875602,
+  1.0,
+  0.785694958,
+  0.5411961,
+  0.275899379,
+];
+
+/** Symbol ending a block whose remaining coefficients are all zero. */
+const EOB = 0x00;
+/** Symbol skipping 16 zero coefficients. */
+const ZRL = 0xF0;
+/** Largest magnitude of an AC coefficient in baseline JPEG, of size 10. */
+const MAX_AC = 1023;
+
+interface HuffmanTable {
+  codes: Uint16Array;
+  sizes: Uint8Array;
+}
+
+interface Component {
+  id: number;
+  /** Horizontal and vertical sampling factors. */
+  sampling: number;
+  plane: Float32Array;
+  planeWidth: number;
+  /** Quantization table index. */
+  table: number;
+  divisors: Float32Array;
+  dc: HuffmanTable;
+  ac: HuffmanTable;
+}
+
+/**
+ * Encodes a frame as a baseline JPEG image.
+ * @param frame - The frame to encode, in any pixel format. The alpha channel is ignored.
+ * @param options - The options of the encoder.
+ * @returns The JPEG file contents.
+ */
+export function encodeJPEG(
+  frame: Frame,
+  options: JPEGEncodeOptions = {},
+): Uint8Array {
+  const { quality = 80, subsampling = "4:2:0" } = options;
+  if (!(Number.isInteger(quality) && quality >= 1 && quality <= 100)) {
+    throw new RangeError(
+      `Quality must be an integer from 1 to 100, got ${quality}`,
+    );
+  }
+  if (subsampling !== "4:2:0" && subsampling !== "4:4:4") {
+    throw new RangeError(`Unsupported chroma subsampling ${subsampling}`);
+  }
+  const { width, height } = frame;
+  if (width > 65535 || height > 65535) {
+    throw new RangeError(`${width}x${height} is too large for JPEG`);
+  }
+
+  const grayscale = options.grayscale || frame.pixelFormat === "gray8";
+  // the luma of an MCU spans 2x2 blocks when the chroma is subsampled
+  const lumaSampling = !grayscale && subsampling === "4:2:0" ? 2 : 1;
+  const mcuSize = 8 * lumaSampling;
+  const mcusX = Math.ceil(width / mcuSize);
+  const mcusY = Math.ceil(height / mcuSize);
+  const planeWidth = mcusX * mcuSize;
+  const planeHeight = mcusY * mcuSize;
+  const planes = toYCbCr(frame, planeWidth, planeHeight, grayscale);
+
+  const lumaTable = scaleQuantization(LUMA_QUANTIZATION, quality);
+  const chromaTable = scaleQuantization(CHROMA_QUANTIZATION, quality);
+  const components: Component[] = [{
+    id: 1,
+    sampling: lumaSampling,
+    plane: planes[0],
+    planeWidth,
+    table: 0,
+    divisors: divisors(lumaTable),
+    dc: buildHuffman(LUMA_DC),
+    ac: buildHuffman(LUMA_AC),
+  }];
+  if (!grayscale) {
+    const chromaWidth = planeWidth / lumaSampling;
+    const chromaDivisors = divisors(chromaTable);
+    const chromaDC = buildHuffman(CHROMA_DC);
+    const chromaAC = buildHuffman(CHROMA_AC);
+    for (const [i, plane] of planes.slice(1).entries()) {
+      components.push({
+        id: i + 2,
+        sampling: 1,
+        plane: lumaSampling === 2
+          ? downsample(plane, planeWidth, planeHeight)
+          : plane,
+        planeWidth: chromaWidth,
+        table: 1,
+        divisors: chromaDivisors,
+        dc: chromaDC,
+        ac: chromaAC,
+      });
+    }
+  }
+
+  const writer = new JPEGWriter(width * height);
+  writer.bytes([0xFF, 0xD8]);
+  // JFIF 1.01 APP0 segment, square pixels without thumbnail
+  // deno-fmt-ignore
+  writer.segment(0xE0, [
+    0x4A, 0x46, 0x49, 0x46, 0x00, 0x01, 0x01, 0x00,
+    0x00, 0x01, 0x00, 0x01, 0x00, 0x00,
+  ]);
+  const tables = grayscale ? [lumaTable] : [lumaTable, chromaTable];
+  writer.segment(
+    0xDB,
+    tables.flatMap((table, i) => [i, ...Array.from(ZIGZAG, (z) => table[z])]),
+  );
+  writer.segment(0xC0, [
+    8,
+    height >> 8,
+    height & 0xFF,
+    width >> 8,
+    width & 0xFF,
+    components.length,
+    ...components.flatMap((c) => [c.id, c.sampling * 0x11, c.table]),
+  ]);
+  // table class and id, then the table
+  const specs: [number, HuffmanSpec][] = [[0x00, LUMA_DC], [0x10, LUMA_AC]];
+  if (!grayscale) specs.push([0x01, CHROMA_DC], [0x11, CHROMA_AC]);
+  writer.segment(
+    0xC4,
+    specs.flatMap(([id, spec]) => [id, ...spec.counts, ...spec.symbols]),
+  );
+  writer.segment(0xDA, [
+    components.length,
+    ...components.flatMap((c) => [c.id, c.table * 0x11]),
+    0,
+    63,
+    0,
+  ]);
+
+  const block = new Float32Array(64);
+  const coefficients = new Int32Array(64);
+  const predictions = components.map(() => 0);
+  for (let my = 0; my < mcusY; my++) {
+    for (let mx = 0; mx < mcusX; mx++) {
+      for (const [i, c] of components.entries()) {
+        for (let by = 0; by < c.sampling; by++) {
+          for (let bx = 0; bx < c.sampling; bx++) {
+            const x = (mx * c.sampling + bx) * 8;
+            const y = (my * c.sampling + by) * 8;
+            for (let i = 0; i < 64; i++) {
+              block[i] = c.plane[(y + (i >> 3)) * c.planeWidth + x + (i & 7)];
+            }
+            forwardDCT(block, c.divisors, coefficients);
+            predictions[i] = encodeBlock(
+              writer,
+              coefficients,
+              predictions[i],
+              c.dc,
+              c.ac,
+            );
+          }
+        }
+      }
+    }
+  }
+  writer.flushBits();
+  writer.bytes([0xFF, 0xD9]);
+  return writer.result();
+}
+
+/**
+ * Converts a frame to level shifted Y, Cb and Cr planes, replicating the
+ * last column and row to fill whole MCUs.
+ */
+function toYCbCr(
+  frame: Frame,
+  planeWidth: number,
+  planeHeight: number,
+  grayscale: boolean,
+): Float32Array[] {
+  const { width, height, channels, stride } = frame;
+  const data = frame.data;
+  const size = planeWidth * planeHeight;
+  const y = new Float32Array(size);
+  const cb = grayscale ? y : new Float32Array(size);
+  const cr = grayscale ? y : new Float32Array(size);
+  for (let py = 0; py < planeHeight; py++) {
+    const row = Math.min(py, height - 1) * stride;
+    for (let px = 0; px < planeWidth; px++) {
+      const o = row + Math.min(px, width - 1) * channels;
+      const i = py * planeWidth + px;
+      if (channels === 1) {
+        y[i] = data[o] - 128;
+        continue;
+      }
+      const r = data[o];
+      const g = data[o + 1];
+      const b = data[o + 2];
+      y[i] = 0.299 * r + 0.587 * g + 0.114 * b - 128;
+      if (grayscale) continue;
+      cb[i] = -0.168736 * r - 0.331264 * g + 0.5 * b;
+      cr[i] = 0.5 * r - 0.418688 * g - 0.081312 * b;
+    }
+  }
+  return grayscale ? [y] : [y, cb, cr];
+}
+
+/** Averages each 2x2 square of a plane. */
+function downsample(
+  plane: Float32Array,
+  width: number,
+  height: number,
+): Float32Array {
+  const out = new Float32Array(width * height / 4);
+  const halfWidth = width / 2;
+  for (let y = 0; y < height / 2; y++) {
+    for (let x = 0; x < halfWidth; x++) {
+      const i = 2 * y * width + 2 * x;
+      out[y * halfWidth + x] = (plane[i] + plane[i + 1] + plane[i + width] +
+        plane[i + width + 1]) / 4;
+    }
+  }
+  return out;
+}
+
+/** Scales a quantization table to a quality, as the IJG reference encoder does. */
+function scaleQuantization(table: number[], quality: number): Uint8Array {
+  const scale = quality < 50 ? 5000 / quality : 200 - quality * 2;
+  return Uint8Array.from(
+    table,
+    (q) => Math.min(255, Math.max(1, Math.floor((q * scale + 50) / 100))),
+  );
+}
+
+/** Multipliers quantizing the output of the AAN forward DCT, in natural order. */
+function divisors(table: Uint8Array): Float32Array {
+  return Float32Array.from(
+    table,
+    (q, i) => 1 / (q * AAN_SCALES[i >> 3] * AAN_SCALES[i & 7] * 8),
+  );
+}
+
+function buildHuffman(spec: HuffmanSpec): HuffmanTable {
+  const codes = new Uint16Array(256);
+  const sizes = new Uint8Array(256);
+  let code = 0;
+  let k = 0;
+  for (let length = 1; length <= 16; length++) {
+    for (let i = 0; i < spec.counts[length - 1]; i++) {
+      const symbol = spec.symbols[k++];
+      codes[symbol] = code++;
+      sizes[symbol] = length;
+    }
+    code <<= 1;
+  }
+  return { codes, sizes };
+}
+
+/**
+ * Computes the quantized DCT of a level shifted 8x8 block with the AAN algorithm.
+ * @param block - The samples in natural order, overwritten.
+ * @param divisors - The quantization multipliers.
+ * @param out - The quantized coefficients in natural order.
+ */
+function forwardDCT(
+  block: Float32Array,
+  divisors: Float32Array,
+  out: Int32Array,
+) {
+  for (let pass = 0; pass < 2; pass++) {
+    // rows first, then columns
+    const step = pass === 0 ? 1 : 8;
+    const next = pass === 0 ? 8 : 1;
+    for (let line = 0; line < 8; line++) {
+      const o = line * next;
+      const d0 = block[o];
+      const d1 = block[o + step];
+      const d2 = block[o + 2 * step];
+      const d3 = block[o + 3 * step];
+      const d4 = block[o + 4 * step];
+      const d5 = block[o + 5 * step];
+      const d6 = block[o + 6 * step];
+      const d7 = block[o + 7 * step];
+
+      const tmp0 = d0 + d7;
+      const tmp7 = d0 - d7;
+      const tmp1 = d1 + d6;
+      const tmp6 = d1 - d6;
+      const tmp2 = d2 + d5;
+      const tmp5 = d2 - d5;
+      const tmp3 = d3 + d4;
+      const tmp4 = d3 - d4;
+
+      // even part
+      const tmp10 = tmp0 + tmp3;
+      const tmp13 = tmp0 - tmp3;
+      const tmp11 = tmp1 + tmp2;
+      const tmp12 = tmp1 - tmp2;
+      block[o] = tmp10 + tmp11;
+      block[o + 4 * step] = tmp10 - tmp11;
+      const z1 = (tmp12 + tmp13) * 0.707106781;
+      block[o + 2 * step] = tmp13 + z1;
+      block[o + 6 * step] = tmp13 - z1;
+
+      // odd part
+      const odd10 = tmp4 + tmp5;
+      const odd11 = tmp5 + tmp6;
+      const odd12 = tmp6 + tmp7;
+      const z5 = (odd10 - odd12) * 0.382683433;
+      const z2 = 0.5411961 * odd10 + z5;
+      const z4 = 1.306562965 * odd12 + z5;
+      const z3 = odd11 * 0.707106781;
+      const z11 = tmp7 + z3;
+      const z13 = tmp7 - z3;
+      block[o + 5 * step] = z13 + z2;
+      block[o + 3 * step] = z13 - z2;
+      block[o + step] = z11 + z4;
+      block[o + 7 * step] = z11 - z4;
+    }
+  }
+  out[0] = Math.round(block[0] * divisors[0]);
+  // baseline AC coefficients have Huffman codes up to size 10, which rounding
+  // can exceed at quality 100
+  for (let i = 1; i < 64; i++) {
+    const coefficient = Math.round(block[i] * divisors[i]);
+    out[i] = Math.max(-MAX_AC, Math.min(MAX_AC, coefficient));
+  }
+}
+
+/**
+ * Entropy codes a block of quantized coefficients.
+ * @returns The DC coefficient, predicting the next block of the component.
+ */
+function encodeBlock(
+  writer: JPEGWriter,
+  coefficients: Int32Array,
+  prediction: number,
+  dc: HuffmanTable,
+  ac: HuffmanTable,
+): number {
+  const value = coefficients[0];
+  const diff = value - prediction;
+  const size = magnitude(diff);
+  writer.bits(dc.codes[size], dc.sizes[size]);
+  if (size) writer.bits(diff < 0 ? diff + (1 << size) - 1 : diff, size);
+
+  let run = 0;
+  for (let k = 1; k < 64; k++) {
+    const coefficient = coefficients[ZIGZAG[k]];
+    if (coefficient === 0) {
+      run++;
+      continue;
+    }
+    for (; run >= 16; run -= 16) writer.bits(ac.codes[ZRL], ac.sizes[ZRL]);
+    const size = magnitude(coefficient);
+    const symbol = (run << 4) | size;
+    writer.bits(ac.codes[symbol], ac.sizes[symbol]);
+    writer.bits(
+      coefficient < 0 ? coefficient + (1 << size) - 1 : coefficient,
+      size,
+    );
+    run = 0;
+  }
+  if (run > 0) writer.bits(ac.codes[EOB], ac.sizes[EOB]);
+  return value;
+}
+
+/** Number of bits needed to represent the absolute value. */
+function magnitude(value: number): number {
+  return 32 - Math.clz32(Math.abs(value));
+}
+
+/**
+ * Represents a growable byte buffer with a bit writer for entropy coded data.
+ */
+class JPEGWriter {
+  #data: Uint8Array;
+  #length = 0;
+  #buffer = 0;
+  #count = 0;
+
+  constructor(capacity: number) {
+    this.#data = new Uint8Array(Math.max(capacity, 1024));
+  }
+
+  bytes(values: ArrayLike<number>) {
+    this.#reserve(values.length);
+    for (let i = 0; i < values.length; i++) {
+      this.#data[this.#length++] = values[i];
+    }
+  }
+
+  segment(marker: number, payload: number[]) {
+    const length = payload.length + 2;
+    this.bytes([0xFF, marker, length >> 8, length & 0xFF]);
+    this.bytes(payload);
+  }
+
+  /** Appends the lowest `size` bits of a value, stuffing a zero byte after each 0xFF. */
+  bits(value: number, size: number) {
+    this.#buffer = (this.#buffer << size) | (value & ((1 << size) - 1));
+    this.#count += size;
+    while (this.#count >= 8) {
+      this.#count -= 8;
+      const byte = (this.#buffer >>> this.#count) & 0xFF;
+      this.#reserve(2);
+      this.#data[this.#length++] = byte;
+      if (byte === 0xFF) this.#data[this.#length++] = 0;
+    }
+    this.#buffer &= (1 << this.#count) - 1;
+  }
+
+  /** Pads the last byte with ones. */
+  flushBits() {
+    if (this.#count > 0) this.bits(0x7F, 8 - this.#count);
+  }
+
+  result(): Uint8Array {
+    return this.#data.slice(0, this.#length);
+  }
+
+  #reserve(size: number) {
+    if (this.#length + size <= this.#data.length) return;
+    const data = new Uint8Array(
+      Math.max(this.#data.length * 2, this.#length + size),
+    );
+    data.set(this.#data.subarray(0, this.#length));
+    this.#data = data;
+  }
+}
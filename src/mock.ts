@@ -0,0 +1,379 @@
+/**
+ * Provides an in-memory capture backend serving synthetic frames, to test vision code without a physical camera.
+ *
+ * @example
+ * ```ts
+ * import { Camera } from "jsr:@sigma/camera";
+ * import { MockBackend } from "jsr:@sigma/camera/mock";
+ *
+ * using cam = new Camera(
+ *   new MockBackend({
+ *     devices: [{
+ *       name: "Mock Camera",
+ *       pattern: "counter",
+ *       formats: [{ width: 320, height: 240, fourcc: "RGB3", fps: 30, bpp: 24 }],
+ *     }],
+ *   }),
+ * );
+ *
+ * const device = cam.devices().at(0);
+ * if (!device) throw new Error("no device found");
+ * using stream = device.stream(device.formats()[0]);
+ * if (!stream) throw new Error("no stream found");
+ *
+ * for await (const frame of stream.next()) {
+ *   console.log("frame", frame.sequence, frame.data.byteLength);
+ *   if (frame.sequence >= 3) break;
+ * }
+ * ```
+ *
+ * @module
+ */
+
+import type { CaptureBackend } from "./backend.ts";
+import {
+  CameraError,
+  DeviceNotFoundError,
+  FormatNotSupportedError,
+} from "./errors.ts";
+import type { CapPropertyID, FormatInfo } from "./types.ts";
+
+/**
+ * Represents the synthetic images a mock device can produce.
+ *
+ * - `colorBars`: static vertical bars (white, yellow, cyan, green, magenta, red, blue, black)
+ * - `gradient`: a diagonal gradient moving by a few pixels every frame
+ * - `counter`: the frame sequence number encoded as 32 black/white blocks, MSB first, over a gray background
+ */
+export type MockPattern = "colorBars" | "gradient" | "counter";
+
+/**
+ * Represents the configuration of a mock device.
+ */
+export interface MockDeviceOptions {
+  /** The name of the device, defaults to `Mock Camera <index>`. */
+  name?: string;
+  /** The unique identifier of the device, defaults to `mock:<index>`. */
+  uniqueId?: string;
+  /** The formats supported by the device, defaults to 640x480 at 30 fps. */
+  formats?: FormatInfo[];
+  /** The image produced by the device, defaults to `colorBars`. */
+  pattern?: MockPattern;
+}
+
+/**
+ * Represents the configuration of a MockBackend.
+ */
+export interface MockBackendOptions {
+  /** The devices exposed by the backend, defaults to one device with default options. */
+  devices?: MockDeviceOptions[];
+  /** The clock in milliseconds used to pace frames, defaults to `performance.now`. */
+  now?: () => number;
+}
+
+interface MockStream {
+  device: Required<MockDeviceOptions>;
+  format: FormatInfo;
+  openedAt: number;
+  captured: number;
+  properties: Map<CapPropertyID, { value: number; auto: boolean }>;
+}
+
+const DEFAULT_FORMAT: FormatInfo = {
+  width: 640,
+  height: 480,
+  fourcc: "RGB3",
+  fps: 30,
+  bpp: 24,
+};
+
+const PROPERTY_LIMITS = { exmin: 0, exmax: 255, edefault: 128 };
+
+const COLOR_BARS = [
+  [255, 255, 255],
+  [255, 255, 0],
+  [0, 255, 255],
+  [0, 255, 0],
+  [255, 0, 255],
+  [255, 0, 0],
+  [0, 0, 255],
+  [0, 0, 0],
+];
+
+/**
+ * Represents a capture backend serving synthetic test patterns at the pace of the selected format.
+ */
+export class MockBackend implements CaptureBackend {
+  /** The devices currently plugged, shared with the reopened contexts. */
+  #plugged: { devices: Required<MockDeviceOptions>[]; count: number };
+  /** The devices enumerated when this context was created. */
+  #devices: Required<MockDeviceOptions>[];
+  #now: () => number;
+  #streams = new Map<number, MockStream>();
+  #nextStreamId = 0;
+  /**
+   * Constructs an instance of the MockBackend class.
+   * @param options - The devices to expose and the clock to use.
+   */
+  constructor(options: MockBackendOptions = {}) {
+    this.#plugged = { devices: [], count: 0 };
+    for (const device of options.devices ?? [{}]) this.plug(device);
+    this.#devices = [...this.#plugged.devices];
+    this.#now = options.now ?? (() => performance.now());
+  }
+
+  /**
+   * Simulates plugging a device, visible to contexts created afterwards with `reopen()`.
+   * @param device - The configuration of the device.
+   */
+  plug(device: MockDeviceOptions = {}) {
+    const i = this.#plugged.count++;
+    this.#plugged.devices.push({
+      name: device.name ?? `Mock Camera ${i}`,
+      uniqueId: device.uniqueId ?? `mock:${i}`,
+      formats: device.formats ?? [DEFAULT_FORMAT],
+      pattern: device.pattern ?? "colorBars",
+    });
+  }
+
+  /**
+   * Simulates unplugging a device, invisible to contexts created afterwards with `reopen()`.
+   * Its open streams fail with a DeviceNotFoundError, even if it is plugged again.
+   * @param uniqueId - The unique identifier of the device.
+   */
+  unplug(uniqueId: string) {
+    this.#plugged.devices = this.#plugged.devices.filter((device) =>
+      device.uniqueId !== uniqueId
+    );
+  }
+
+  /**
+   * Creates a new context enumerating the devices plugged now.
+   * @returns A new MockBackend sharing the plugged devices and the clock.
+   */
+  reopen(): MockBackend {
+    const backend = new MockBackend({ devices: [], now: this.#now });
+    backend.#plugged = this.#plugged;
+    backend.#devices = [...this.#plugged.devices];
+    return backend;
+  }
+
+  getDeviceCount(): number {
+    return this.#devices.length;
+  }
+
+  getDeviceName(id: number): string {
+    return this.#device(id).name;
+  }
+
+  getDeviceUniqueId(id: number): string {
+    return this.#device(id).uniqueId;
+  }
+
+  getNumFormats(id: number): number {
+    return this.#device(id).formats.length;
+  }
+
+  getFormatInfo(id: number, formatId: number): FormatInfo {
+    const format = this.#device(id).formats[formatId];
+    if (!format) {
+      throw new FormatNotSupportedError(
+        `MockBackend: unknown format ${formatId}`,
+        { operation: "getFormatInfo", deviceId: id },
+      );
+    }
+    return { ...format };
+  }
+
+  setProperty(id: number, propertyId: CapPropertyID, value: number) {
+    this.#property(id, propertyId).value = value;
+  }
+
+  getProperty(id: number, propertyId: CapPropertyID): number {
+    return this.#property(id, propertyId).value;
+  }
+
+  setAutoProperty(id: number, propertyId: CapPropertyID, value: number) {
+    this.#property(id, propertyId).auto = value !== 0;
+  }
+
+  getAutoProperty(id: number, propertyId: CapPropertyID): boolean {
+    return this.#property(id, propertyId).auto;
+  }
+
+  getPropertyLimits(
+    id: number,
+    _propertyId: CapPropertyID,
+  ): { exmax: number; exmin: number; edefault: number } {
+    this.#stream(id);
+    return { ...PROPERTY_LIMITS };
+  }
+
+  openStream(deviceId: number, deviceFormatId: number): number {
+    const device = this.#device(deviceId);
+    const format = this.getFormatInfo(deviceId, deviceFormatId);
+    const streamId = this.#nextStreamId++;
+    this.#streams.set(streamId, {
+      device,
+      format,
+      openedAt: this.#now(),
+      captured: 0,
+      properties: new Map(),
+    });
+    return streamId;
+  }
+
+  isOpenStream(id: number): boolean {
+    return this.#streams.has(id);
+  }
+
+  hasNewFrame(id: number): boolean {
+    const stream = this.#stream(id);
+    if (!this.#connected(stream)) return false;
+    return this.#frameCount(stream) > stream.captured;
+  }
+
+  waitForNewFrame(id: number, timeout?: number): Promise<boolean> {
+    const stream = this.#stream(id);
+    if (!this.#connected(stream)) {
+      return Promise.reject(
+        new DeviceNotFoundError(
+          `MockBackend: device ${stream.device.uniqueId} was unplugged`,
+          { operation: "waitForNewFrame", streamId: id },
+        ),
+      );
+    }
+    const frameCount = this.#frameCount(stream);
+    if (frameCount > stream.captured) return Promise.resolve(true);
+    // frames are paced by the clock, so the next one is due at a known time
+    const due = stream.openedAt + frameCount * 1000 / stream.format.fps;
+    const delay = Math.max(0, due - this.#now());
+    return new Promise((resolve) => {
+      if (timeout !== undefined && timeout < delay) {
+        setTimeout(() => resolve(false), timeout);
+      } else {
+        setTimeout(() => resolve(true), delay);
+      }
+    });
+  }
+
+  getStreamFrameCount(id: number): number {
+    return this.#frameCount(this.#stream(id));
+  }
+
+  captureFrame(id: number, buffer: Uint8Array): void {
+    const stream = this.#stream(id);
+    if (!this.#connected(stream)) {
+      throw new DeviceNotFoundError(
+        `MockBackend: device ${stream.device.uniqueId} was unplugged`,
+        { operation: "captureFrame", streamId: id },
+      );
+    }
+    const frameCount = this.#frameCount(stream);
+    stream.captured = frameCount;
+    renderPattern(stream.device.pattern, stream.format, frameCount, buffer);
+  }
+
+  closeStream(id: number) {
+    if (!this.#streams.delete(id)) {
+      throw new CameraError(`MockBackend: unknown stream ${id}`, {
+        operation: "closeStream",
+        streamId: id,
+      });
+    }
+  }
+
+  releaseContext() {
+    this.#streams.clear();
+  }
+
+  #device(id: number): Required<MockDeviceOptions> {
+    const device = this.#devices[id];
+    if (!device) {
+      throw new DeviceNotFoundError(`MockBackend: unknown device ${id}`, {
+        operation: "getDevice",
+        deviceId: id,
+      });
+    }
+    return device;
+  }
+
+  #stream(id: number): MockStream {
+    const stream = this.#streams.get(id);
+    if (!stream) {
+      throw new CameraError(`MockBackend: unknown stream ${id}`, {
+        operation: "getStream",
+        streamId: id,
+      });
+    }
+    return stream;
+  }
+
+  #property(id: number, propertyId: CapPropertyID) {
+    const properties = this.#stream(id).properties;
+    let property = properties.get(propertyId);
+    if (!property) {
+      property = { value: PROPERTY_LIMITS.edefault, auto: false };
+      properties.set(propertyId, property);
+    }
+    return property;
+  }
+
+  /** Whether the device of the stream is still plugged. */
+  #connected(stream: MockStream): boolean {
+    return this.#plugged.devices.includes(stream.device);
+  }
+
+  /** Number of frames delivered since the stream was opened, the first one is delivered right away. */
+  #frameCount(stream: MockStream): number {
+    const elapsed = this.#now() - stream.openedAt;
+    return Math.floor(elapsed * stream.format.fps / 1000) + 1;
+  }
+}
+
+/**
+ * Renders a test pattern as packed RGB24.
+ * @param pattern - The pattern to render.
+ * @param format - The format of the frame.
+ * @param sequence - The sequence number of the frame, starting at 1.
+ * @param buffer - The buffer to render into.
+ */
+function renderPattern(
+  pattern: MockPattern,
+  format: FormatInfo,
+  sequence: number,
+  buffer: Uint8Array,
+) {
+  const { width, height } = format;
+  const pixels = Math.min(width * height, Math.floor(buffer.byteLength / 3));
+  for (let i = 0; i < pixels; i++) {
+    const x = i % width;
+    const y = Math.floor(i / width);
+    let r = 0, g = 0, b = 0;
+    switch (pattern) {
+      case "colorBars": {
+        [r, g, b] = COLOR_BARS[Math.floor(x * COLOR_BARS.length / width)];
+        break;
+      }
+      case "gradient": {
+        const offset = sequence * 4;
+        r = Math.floor((x + offset) * 255 / width) & 0xFF;
+        g = Math.floor((y + offset) * 255 / height) & 0xFF;
+        b = (sequence * 8) & 0xFF;
+        break;
+      }
+      case "counter": {
+        if (y < height / 2) {
+          const bit = 31 - Math.floor(x * 32 / width);
+          r = g = b = ((sequence >>> bit) & 1) ? 255 : 0;
+        } else {
+          r = g = b = 128;
+        }
+        break;
+      }
+    }
+    buffer[i * 3] = r;
+    buffer[i * 3 + 1] = g;
+    buffer[i * 3 + 2] = b;
+  }
+}
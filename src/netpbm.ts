@@ -0,0 +1,412 @@
+/**
+ * Provides a reader and writer of netpbm images (PGM and PPM) for frames.
+ *
+ * Binary P5/P6 and plain text P2/P3 images are supported, with a maxval up to
+ * 65535. Frames are always 8-bit, samples are scaled from and to the maxval.
+ *
+ * @example
+ * ```ts
+ * import { Camera, decodeNetpbm, encodeNetpbm } from "jsr:@sigma/camera";
+ *
+ * using cam = new Camera();
+ * const device = cam.devices().at(0);
+ * if (!device) throw new Error("no device found");
+ * using stream = device.stream({ width: 640, height: 480 });
+ * if (!stream) throw new Error("no stream found");
+ *
+ * let frameNum = 0;
+ * for await (const frame of stream.next()) {
+ *   if (frameNum === 5) break;
+ *   Deno.writeFileSync(`frame_${++frameNum}.ppm`, encodeNetpbm(frame));
+ * }
+ *
+ * const frame = decodeNetpbm(Deno.readFileSync("frame_1.ppm"));
+ * console.log(frame.width, frame.height, frame.pixelFormat);
+ * ```
+ *
+ * @module
+ */
+
+import { Frame, PIXEL_FORMAT_CHANNELS } from "./frame.ts";
+
+/**
+ * Represents the options of the netpbm encoder.
+ */
+export interface NetpbmEncodeOptions {
+  /** Whether to write a plain text P2/P3 image instead of a binary P5/P6 one. */
+  plain?: boolean;
+  /** The maximum sample value from 1 to 65535, defaults to 255. Above 255, binary samples take 2 bytes. */
+  maxval?: number;
+}
+
+/** Longest line of a plain image allowed by the specification. */
+const PLAIN_LINE_LENGTH = 70;
+
+const ENCODER = new TextEncoder();
+const DECODER = new TextDecoder();
+
+/**
+ * Encodes a frame as a netpbm image.
+ * `gray8` frames become PGM (P5/P2) images, others become PPM (P6/P3) images
+ * and lose their alpha channel.
+ * @param frame - The frame to encode.
+ * @param options - The options of the encoder.
+ * @returns The image file contents.
+ */
+export function encodeNetpbm(
+  frame: Frame,
+  options: NetpbmEncodeOptions = {},
+): Uint8Array {
+  const { plain = false, maxval = 255 } = options;
+  if (!(Number.isInteger(maxval) && maxval >= 1 && maxval <= 65535)) {
+    throw new RangeError(
+      `maxval must be an integer from 1 to 65535, got ${maxval}`,
+    );
+  }
+
+  const { width, height, stride } = frame;
+  const data = frame.data;
+  const gray = frame.pixelFormat === "gray8";
+  const channels = gray ? 1 : 3;
+  const magic = gray ? (plain ? "P2" : "P5") : (plain ? "P3" : "P6");
+  const header = ENCODER.encode(`${magic}\n${width} ${height}\n${maxval}\n`);
+  const scale = (value: number) =>
+    maxval === 255 ? value : Math.round(value * maxval / 255);
+
+  if (plain) {
+    const lines = [];
+    let line = "";
+    for (let y = 0; y < height; y++) {
+      for (let x = 0; x < width; x++) {
+        const pixel = y * stride + x * frame.channels;
+        for (let c = 0; c < channels; c++) {
+          const sample = String(scale(data[pixel + c]));
+          if (line.length + 1 + sample.length > PLAIN_LINE_LENGTH) {
+            lines.push(line);
+            line = sample;
+          } else {
+            line = line ? `${line} ${sample}` : sample;
+          }
+        }
+      }
+    }
+    lines.push(line);
+    return concat([header, ENCODER.encode(lines.join("\n") + "\n")]);
+  }
+
+  const sampleSize = maxval > 255 ? 2 : 1;
+  const image = new Uint8Array(
+    header.length + width * height * channels * sampleSize,
+  );
+  image.set(header);
+  let offset = header.length;
+  for (let y = 0; y < height; y++) {
+    for (let x = 0; x < width; x++) {
+      const pixel = y * stride + x * frame.channels;
+      for (let c = 0; c < channels; c++) {
+        const sample = scale(data[pixel + c]);
+        if (sampleSize === 2) image[offset++] = sample >> 8;
+        image[offset++] = sample & 0xFF;
+      }
+    }
+  }
+  return image;
+}
+
+/**
+ * Decodes a netpbm image to a frame.
+ * PGM images become `gray8` frames, PPM images become `rgb24` frames.
+ * @param bytes - The image file contents.
+ * @returns A frame with a timestamp and sequence of 0.
+ * @throws {Error} If the header is invalid or the image is truncated.
+ */
+export function decodeNetpbm(bytes: Uint8Array): Frame {
+  const parsed = parseImage(bytes, 0, true);
+  if (!("frame" in parsed)) throw new Error("Truncated netpbm image");
+  return parsed.frame;
+}
+
+/**
+ * Creates a TransformStream encoding each frame as a netpbm image.
+ * @param options - The options of the encoder.
+ * @returns A TransformStream from frames to image file contents.
+ */
+export function netpbmEncoder(
+  options: NetpbmEncodeOptions = {},
+): TransformStream<Frame, Uint8Array> {
+  return new TransformStream({
+    transform(frame, controller) {
+      controller.enqueue(encodeNetpbm(frame, options));
+    },
+  });
+}
+
+/**
+ * Creates a TransformStream decoding consecutive netpbm images from chunks of bytes,
+ * such as a file where several images were concatenated.
+ * @returns A TransformStream from bytes to frames.
+ */
+export function netpbmDecoder(): TransformStream<Uint8Array, Frame> {
+  let chunks: Uint8Array[] = [];
+  let buffered = 0;
+  // chunks are only joined once enough bytes arrived, so that a large raster
+  // split in many chunks isn't copied again for every chunk
+  let needed = 0;
+  // the samples of a plain image read so far, so that they aren't parsed again
+  // for every chunk
+  let raster: PlainRaster | undefined;
+  const drain = (
+    controller: TransformStreamDefaultController<Frame>,
+    final: boolean,
+  ) => {
+    if (buffered < needed && !final) return;
+    needed = 0;
+    const pending = concat(chunks);
+    let offset = 0;
+    while (true) {
+      if (!raster) {
+        offset = skipWhitespace(pending, offset);
+        // a comment without its end of line may continue in the next chunk
+        if (offset === pending.length || pending[offset] === 0x23) break;
+      }
+      const parsed = parseImage(pending, offset, final, raster);
+      raster = undefined;
+      if (!("frame" in parsed)) {
+        if ("raster" in parsed) {
+          raster = parsed.raster;
+          offset = parsed.end;
+        }
+        needed = parsed.needed - offset;
+        break;
+      }
+      controller.enqueue(parsed.frame);
+      offset = parsed.end;
+    }
+    chunks = [pending.subarray(offset)];
+    buffered = pending.length - offset;
+  };
+  return new TransformStream({
+    transform(chunk, controller) {
+      chunks.push(chunk);
+      buffered += chunk.length;
+      drain(controller, false);
+    },
+    flush(controller) {
+      drain(controller, true);
+    },
+  });
+}
+
+/**
+ * Represents a plain image whose samples are partially read.
+ */
+interface PlainRaster {
+  width: number;
+  height: number;
+  maxval: number;
+  pixelFormat: "rgb24" | "gray8";
+  data: Uint8Array;
+  /** The number of samples read so far. */
+  read: number;
+}
+
+/**
+ * Parses the image starting at an offset.
+ * @param bytes - The available bytes.
+ * @param start - The offset of the magic number, or of the next sample when resuming a plain image.
+ * @param final - Whether no more bytes will follow.
+ * @param resume - The plain image to continue reading.
+ * @returns The frame and the offset following it, or the number of bytes needed to go further along with the plain samples read up to `end`.
+ */
+function parseImage(
+  bytes: Uint8Array,
+  start: number,
+  final: boolean,
+  resume?: PlainRaster,
+):
+  | { frame: Frame; end: number }
+  | { needed: number }
+  | { needed: number; raster: PlainRaster; end: number } {
+  const more = { needed: bytes.length + 1 };
+  if (resume) return readPlainRaster(bytes, start, final, resume);
+  if (bytes.length - start < 3) {
+    if (final) throw new Error("Truncated netpbm header");
+    return more;
+  }
+  const magic = DECODER.decode(bytes.subarray(start, start + 2));
+  const separator = bytes[start + 2];
+  if (
+    !["P2", "P3", "P5", "P6"].includes(magic) ||
+    !(isWhitespace(separator) || separator === 0x23)
+  ) {
+    throw new Error(`Unsupported netpbm type ${JSON.stringify(magic)}`);
+  }
+  const plain = magic === "P2" || magic === "P3";
+  const channels = magic === "P3" || magic === "P6" ? 3 : 1;
+
+  let offset = start + 2;
+  const header = [];
+  for (const field of ["width", "height", "maxval"]) {
+    const token = readToken(bytes, offset, final);
+    if (!token) return more;
+    if (!/^\d+$/.test(token.value)) {
+      throw new Error(
+        `Invalid netpbm ${field} ${JSON.stringify(token.value)}`,
+      );
+    }
+    header.push(Number.parseInt(token.value));
+    offset = token.end;
+  }
+  const [width, height, maxval] = header;
+  if (width === 0 || height === 0) {
+    throw new Error(`Invalid netpbm size ${width}x${height}`);
+  }
+  if (maxval === 0 || maxval > 65535) {
+    throw new Error(`Invalid netpbm maxval ${maxval}`);
+  }
+
+  const pixelFormat = channels === 3 ? "rgb24" : "gray8";
+  const data = new Uint8Array(
+    width * height * PIXEL_FORMAT_CHANNELS[pixelFormat],
+  );
+  const raster: PlainRaster = {
+    width,
+    height,
+    maxval,
+    pixelFormat,
+    data,
+    read: 0,
+  };
+  if (plain) return readPlainRaster(bytes, offset, final, raster);
+
+  // a single whitespace separates the header from the raster
+  if (offset === bytes.length) {
+    if (final) throw new Error("Truncated netpbm header");
+    return more;
+  }
+  if (!isWhitespace(bytes[offset])) {
+    throw new Error("Missing whitespace after the netpbm header");
+  }
+  offset++;
+  const sampleSize = maxval > 255 ? 2 : 1;
+  const end = offset + data.length * sampleSize;
+  if (end > bytes.length) {
+    if (final) throw new Error("Truncated netpbm raster");
+    return { needed: end };
+  }
+  for (let i = 0; i < data.length; i++) {
+    const at = offset + i * sampleSize;
+    data[i] = scaleSample(
+      sampleSize === 2 ? (bytes[at] << 8) | bytes[at + 1] : bytes[at],
+      maxval,
+    );
+  }
+  return { frame: rasterFrame(raster), end };
+}
+
+/**
+ * Reads the remaining samples of a plain image.
+ * @param bytes - The available bytes.
+ * @param offset - The offset of the next sample.
+ * @param final - Whether no more bytes will follow.
+ * @param raster - The image being read, updated in place.
+ * @returns The frame and the offset following it, or the samples read so far up to `end`.
+ */
+function readPlainRaster(
+  bytes: Uint8Array,
+  offset: number,
+  final: boolean,
+  raster: PlainRaster,
+):
+  | { frame: Frame; end: number }
+  | { needed: number; raster: PlainRaster; end: number } {
+  const { data, maxval } = raster;
+  for (; raster.read < data.length; raster.read++) {
+    const token = readToken(bytes, offset, final);
+    if (!token) return { needed: bytes.length + 1, raster, end: offset };
+    if (!/^\d+$/.test(token.value)) {
+      throw new Error(`Invalid netpbm sample ${JSON.stringify(token.value)}`);
+    }
+    data[raster.read] = scaleSample(Number.parseInt(token.value), maxval);
+    offset = token.end;
+  }
+  return { frame: rasterFrame(raster), end: offset };
+}
+
+/** Scales a sample from 0..maxval to 0..255. */
+function scaleSample(value: number, maxval: number): number {
+  if (value > maxval) {
+    throw new Error(`Netpbm sample ${value} exceeds maxval ${maxval}`);
+  }
+  return maxval === 255 ? value : Math.round(value * 255 / maxval);
+}
+
+function rasterFrame(raster: PlainRaster): Frame {
+  const { data, width, height, pixelFormat } = raster;
+  return new Frame({
+    data,
+    width,
+    height,
+    pixelFormat,
+    timestamp: 0,
+    sequence: 0,
+  });
+}
+
+function isWhitespace(byte: number): boolean {
+  return byte === 0x20 || (byte >= 0x09 && byte <= 0x0D);
+}
+
+/**
+ * Skips whitespace and comments, which run from `#` to the end of the line.
+ * Stops at the `#` of a comment whose end of line wasn't read yet.
+ */
+function skipWhitespace(bytes: Uint8Array, offset: number): number {
+  while (offset < bytes.length) {
+    if (bytes[offset] === 0x23 /* # */) {
+      const end = bytes.indexOf(0x0A, offset);
+      if (end === -1) break;
+      offset = end;
+    } else if (isWhitespace(bytes[offset])) {
+      offset++;
+    } else {
+      break;
+    }
+  }
+  return offset;
+}
+
+/**
+ * Reads the next token, a token at the end of the bytes being complete only if no more bytes follow.
+ * @returns The token and the offset of the byte following it, undefined if more bytes are needed.
+ */
+function readToken(
+  bytes: Uint8Array,
+  offset: number,
+  final: boolean,
+): { value: string; end: number } | undefined {
+  const start = skipWhitespace(bytes, offset);
+  if (bytes[start] === 0x23) {
+    if (final) throw new Error("Truncated netpbm image");
+    return;
+  }
+  let end = start;
+  while (
+    end < bytes.length && !isWhitespace(bytes[end]) && bytes[end] !== 0x23
+  ) {
+    end++;
+  }
+  if (end === bytes.length && !final) return;
+  if (end === start) throw new Error("Truncated netpbm image");
+  return { value: DECODER.decode(bytes.subarray(start, end)), end };
+}
+
+function concat(chunks: Uint8Array[]): Uint8Array {
+  const out = new Uint8Array(chunks.reduce((sum, c) => sum + c.length, 0));
+  let offset = 0;
+  for (const chunk of chunks) {
+    out.set(chunk, offset);
+    offset += chunk.length;
+  }
+  return out;
+}
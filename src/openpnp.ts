@@ -9,10 +9,12 @@
  *   CAPPROPID_GAIN,
  *   CAPPROPID_WHITEBALANCE,
  * } from "jsr:@sigma/camera/ffi";
+ * import { encodeNetpbm, Frame } from "jsr:@sigma/camera";
  * import { OpenPnp } from "jsr:@sigma/camera/openpnp";
  *
  * if (import.meta.main) {
  *   console.log("OpenPnp Capture Test Program");
+ *   await OpenPnp.loadLibrary();
  *   console.log("Version:", OpenPnp.getLibraryVersion());
  *   OpenPnp.setLogLevel(7);
  *
@@ -58,47 +60,51 @@
  *     if (pnp.hasNewFrame(streamId)) {
  *       pnp.captureFrame(streamId, buffer);
  *       console.log("Frame captured");
- *       writeBufferAsPPM(
- *         ++frameWriteCounter,
- *         formatInfo.width,
- *         formatInfo.height,
- *         buffer,
+ *       const frame = new Frame({
+ *         data: buffer,
+ *         width: formatInfo.width,
+ *         height: formatInfo.height,
+ *         pixelFormat: "rgb24",
+ *         timestamp: Date.now(),
+ *         sequence: ++frameWriteCounter,
+ *       });
+ *       Deno.writeFileSync(
+ *         `frame_${frameWriteCounter}.ppm`,
+ *         encodeNetpbm(frame),
  *       );
  *       console.log(`Written frame to frame_${frameWriteCounter}.ppm`);
  *     }
  *     await new Promise((r) => setTimeout(r, 100));
  *   }
  * }
- *
- * function writeBufferAsPPM(
- *   frameNum: number,
- *   width: number,
- *   height: number,
- *   buffer: Uint8Array,
- * ): boolean {
- *   const ENCODER = new TextEncoder();
- *   const fname = `frame_${frameNum}.ppm`;
- *   const fout = Deno.createSync(fname);
- *   fout.writeSync(ENCODER.encode(`P6 ${width} ${height} 255\n`));
- *   fout.writeSync(buffer);
- *   fout.close();
- *   return true;
- * }
  * ```
  * @module
  */
 
 import * as byte from "@denosaurs/byte-type";
-import { LIBRARY } from "./ffi.ts";
+import { LIBRARY, loadLibrary } from "./ffi.ts";
+import type { LibraryOptions } from "./ffi.ts";
+import type { CaptureBackend } from "./backend.ts";
+import { FrameWaiter } from "./frame_waiter.ts";
 import type { FormatInfo, LogLevel } from "./types.ts";
 import type { CapPropertyID } from "./types.ts";
-import { CAPRESULT_OK } from "./ffi.ts";
+import { CAPRESULT_OK, CapCustomLogFunc } from "./ffi.ts";
+import {
+  CameraError,
+  DeviceNotFoundError,
+  errorFromResult,
+  FormatNotSupportedError,
+} from "./errors.ts";
 
 /**
  * Represents the OpenPnp class for interacting with the library.
  */
-export class OpenPnp {
+export class OpenPnp implements CaptureBackend {
+  static #logCallback:
+    | Deno.UnsafeCallback<typeof CapCustomLogFunc>
+    | undefined;
   #ctx: Deno.PointerValue<unknown>;
+  #waiters = new Map<number, FrameWaiter>();
   /**
    * Constructs an instance of the OpenPnp class.
    */
@@ -106,13 +112,26 @@ export class OpenPnp {
     this.#ctx = LIBRARY.symbols.Cap_createContext();
   }
 
+  /**
+   * Loads the openpnp-capture library, downloading it if it can't be found locally.
+   * Only needed when the library isn't available from `OPENPNP_CAPTURE_LIB` or the system search path.
+   * @param options - Options to locate the library.
+   */
+  static async loadLibrary(options?: LibraryOptions): Promise<void> {
+    await loadLibrary(options);
+  }
+
   /**
    * Retrieves the version of the library.
    * @returns The version of the library.
    */
   static getLibraryVersion(): string {
     const versionPtr = LIBRARY.symbols.Cap_getLibraryVersion();
-    if (!versionPtr) throw new Error("getLibraryVersion returned null ptr");
+    if (!versionPtr) {
+      throw new CameraError("Cap_getLibraryVersion returned null ptr", {
+        operation: "Cap_getLibraryVersion",
+      });
+    }
     const version = Deno.UnsafePointerView.getCString(versionPtr);
     return version;
   }
@@ -125,6 +144,37 @@ export class OpenPnp {
     LIBRARY.symbols.Cap_setLogLevel(level);
   }
 
+  /**
+   * Installs a function receiving the log messages of the library instead of stderr.
+   * Installing a new function replaces the previous one, `null` restores the default stderr output.
+   * @param logFunction - The function to call for each log message.
+   */
+  static installCustomLogFunction(
+    logFunction: ((level: LogLevel, message: string) => void) | null,
+  ) {
+    if (logFunction === null) {
+      LIBRARY.symbols.Cap_installCustomLogFunction(null);
+      OpenPnp.#logCallback?.close();
+      OpenPnp.#logCallback = undefined;
+      return;
+    }
+    // the library logs from its capture threads too, so the callback must be thread safe
+    const callback = Deno.UnsafeCallback.threadSafe(
+      CapCustomLogFunc,
+      (level, messagePtr) => {
+        const message = messagePtr
+          ? Deno.UnsafePointerView.getCString(messagePtr)
+          : "";
+        logFunction(level, message.trimEnd());
+      },
+    );
+    // don't keep the process alive only to receive logs
+    callback.unref();
+    LIBRARY.symbols.Cap_installCustomLogFunction(callback.pointer);
+    OpenPnp.#logCallback?.close();
+    OpenPnp.#logCallback = callback;
+  }
+
   /**
    * Retrieves the number of available devices.
    * @returns The number of available devices.
@@ -140,11 +190,34 @@ export class OpenPnp {
    */
   getDeviceName(id: number): string {
     const namePtr = LIBRARY.symbols.Cap_getDeviceName(this.#ctx, id);
-    if (!namePtr) throw new Error("getDeviceName returned null ptr");
+    if (!namePtr) {
+      throw new DeviceNotFoundError("Cap_getDeviceName returned null ptr", {
+        operation: "Cap_getDeviceName",
+        deviceId: id,
+      });
+    }
     const name = Deno.UnsafePointerView.getCString(namePtr);
     return name;
   }
 
+  /**
+   * Retrieves the unique identifier of a device.
+   * Unlike the device ID, it stays the same across replugs and reboots.
+   * @param id - The ID of the device.
+   * @returns The unique identifier of the device.
+   */
+  getDeviceUniqueId(id: number): string {
+    const uniqueIdPtr = LIBRARY.symbols.Cap_getDeviceUniqueID(this.#ctx, id);
+    if (!uniqueIdPtr) {
+      throw new DeviceNotFoundError("Cap_getDeviceUniqueID returned null ptr", {
+        operation: "Cap_getDeviceUniqueID",
+        deviceId: id,
+      });
+    }
+    const uniqueId = Deno.UnsafePointerView.getCString(uniqueIdPtr);
+    return uniqueId;
+  }
+
   /**
    * Retrieves the number of formats supported by a device.
    * @param id - The ID of the device.
@@ -171,8 +244,11 @@ export class OpenPnp {
       formatId,
       infoBuffer,
     );
-    if (res !== 0) {
-      throw new Error("Cap_getNumFormats failed");
+    if (res !== CAPRESULT_OK) {
+      throw errorFromResult(res, {
+        operation: "Cap_getFormatInfo",
+        deviceId: id,
+      });
     }
 
     const rawFormatInfo = new byte.Struct({
@@ -191,6 +267,57 @@ export class OpenPnp {
     return formatInfo;
   }
 
+  /**
+   * Sets the value of a property.
+   * @param id - The ID of the stream.
+   * @param propertyId - The ID of the property.
+   * @param value - The value to set.
+   */
+  setProperty(
+    id: number,
+    propertyId: CapPropertyID,
+    value: number,
+  ) {
+    const res = LIBRARY.symbols.Cap_setProperty(
+      this.#ctx,
+      id,
+      propertyId,
+      value,
+    );
+    if (res !== CAPRESULT_OK) {
+      throw errorFromResult(res, {
+        operation: "Cap_setProperty",
+        streamId: id,
+      });
+    }
+  }
+
+  /**
+   * Retrieves the value of a property.
+   * @param id - The ID of the stream.
+   * @param propertyId - The ID of the property.
+   * @returns The current value of the property.
+   */
+  getProperty(
+    id: number,
+    propertyId: CapPropertyID,
+  ): number {
+    const value = new ArrayBuffer(4 /*i32 byte*/);
+    const res = LIBRARY.symbols.Cap_getProperty(
+      this.#ctx,
+      id,
+      propertyId,
+      value,
+    );
+    if (res !== CAPRESULT_OK) {
+      throw errorFromResult(res, {
+        operation: "Cap_getProperty",
+        streamId: id,
+      });
+    }
+    return byte.i32.read(new DataView(value));
+  }
+
   /**
    * Sets the automatic property for a device.
    * @param id - The ID of the device.
@@ -202,12 +329,44 @@ export class OpenPnp {
     propertyId: CapPropertyID,
     value: number,
   ) {
-    LIBRARY.symbols.Cap_setAutoProperty(
+    const res = LIBRARY.symbols.Cap_setAutoProperty(
       this.#ctx,
       id,
       propertyId,
       value,
     );
+    if (res !== CAPRESULT_OK) {
+      throw errorFromResult(res, {
+        operation: "Cap_setAutoProperty",
+        streamId: id,
+      });
+    }
+  }
+
+  /**
+   * Retrieves whether the automatic mode of a property is enabled.
+   * @param id - The ID of the stream.
+   * @param propertyId - The ID of the property.
+   * @returns A boolean indicating if automatic mode is enabled.
+   */
+  getAutoProperty(
+    id: number,
+    propertyId: CapPropertyID,
+  ): boolean {
+    const enabled = new ArrayBuffer(4 /*u32 byte*/);
+    const res = LIBRARY.symbols.Cap_getAutoProperty(
+      this.#ctx,
+      id,
+      propertyId,
+      enabled,
+    );
+    if (res !== CAPRESULT_OK) {
+      throw errorFromResult(res, {
+        operation: "Cap_getAutoProperty",
+        streamId: id,
+      });
+    }
+    return byte.u32.read(new DataView(enabled)) !== 0;
   }
 
   /**
@@ -223,22 +382,24 @@ export class OpenPnp {
     const exmax = new ArrayBuffer(4 /*i32 byte*/);
     const exmin = new ArrayBuffer(4 /*i32 byte*/);
     const edefault = new ArrayBuffer(4 /*i32 byte*/);
-    if (
-      LIBRARY.symbols.Cap_getPropertyLimits(
-        this.#ctx,
-        id,
-        propertyId,
-        exmax,
-        exmin,
-        edefault,
-      ) !== 0
-    ) {
-      throw new Error("Cap_getPropertyLimits failed");
+    const res = LIBRARY.symbols.Cap_getPropertyLimits(
+      this.#ctx,
+      id,
+      propertyId,
+      exmin,
+      exmax,
+      edefault,
+    );
+    if (res !== CAPRESULT_OK) {
+      throw errorFromResult(res, {
+        operation: "Cap_getPropertyLimits",
+        streamId: id,
+      });
     }
     return {
-      exmax: byte.u32.read(new DataView(exmax)),
-      exmin: byte.u32.read(new DataView(exmin)),
-      edefault: byte.u32.read(new DataView(edefault)),
+      exmax: byte.i32.read(new DataView(exmax)),
+      exmin: byte.i32.read(new DataView(exmin)),
+      edefault: byte.i32.read(new DataView(edefault)),
     };
   }
 
@@ -254,7 +415,23 @@ export class OpenPnp {
       deviceId,
       deviceFormatId,
     );
-    if (res < 0) throw new Error("Cap_openStream failed");
+    if (res < 0) {
+      // no result code is returned, so tell the common causes apart here
+      const options = { operation: "Cap_openStream", deviceId };
+      if (deviceId >= this.getDeviceCount()) {
+        throw new DeviceNotFoundError(
+          `Cap_openStream failed: device ${deviceId} not found`,
+          options,
+        );
+      }
+      if (deviceFormatId >= this.getNumFormats(deviceId)) {
+        throw new FormatNotSupportedError(
+          `Cap_openStream failed: format ${deviceFormatId} not supported by device ${deviceId}`,
+          options,
+        );
+      }
+      throw new CameraError("Cap_openStream failed", options);
+    }
     return res;
   }
 
@@ -276,6 +453,32 @@ export class OpenPnp {
     return LIBRARY.symbols.Cap_hasNewFrame(this.#ctx, id) === 1;
   }
 
+  /**
+   * Waits until a new frame is available.
+   * A worker polls the driver every millisecond, so the event loop stays free.
+   * @param id - The ID of the stream.
+   * @param timeout - Maximum time to wait in milliseconds, unbounded by default.
+   * @returns A promise resolving to false if the timeout elapsed first.
+   */
+  async waitForNewFrame(id: number, timeout?: number): Promise<boolean> {
+    if (this.hasNewFrame(id)) return true;
+    let waiter = this.#waiters.get(id);
+    if (!waiter) {
+      waiter = new FrameWaiter(this.#ctx, id);
+      this.#waiters.set(id, waiter);
+    }
+    return await waiter.wait(timeout);
+  }
+
+  /**
+   * Retrieves the number of frames delivered by the driver since the stream was opened.
+   * @param id - The ID of the stream.
+   * @returns The number of frames received by the driver.
+   */
+  getStreamFrameCount(id: number): number {
+    return LIBRARY.symbols.Cap_getStreamFrameCount(this.#ctx, id);
+  }
+
   /**
    * Captures a frame from the stream.
    * @param id - The ID of the stream.
@@ -292,7 +495,10 @@ export class OpenPnp {
       buffer.byteLength,
     );
     if (res !== CAPRESULT_OK) {
-      throw new Error("Cap_captureFrame failed");
+      throw errorFromResult(res, {
+        operation: "Cap_captureFrame",
+        streamId: id,
+      });
     }
   }
 
@@ -301,17 +507,34 @@ export class OpenPnp {
    * @param id - The ID of the stream.
    */
   closeStream(id: number) {
-    if (LIBRARY.symbols.Cap_closeStream(this.#ctx, id) !== CAPRESULT_OK) {
-      throw new Error("Cap_closeStream failed");
+    this.#waiters.get(id)?.close();
+    this.#waiters.delete(id);
+    const res = LIBRARY.symbols.Cap_closeStream(this.#ctx, id);
+    if (res !== CAPRESULT_OK) {
+      throw errorFromResult(res, {
+        operation: "Cap_closeStream",
+        streamId: id,
+      });
     }
   }
 
+  /**
+   * Creates a new context, which enumerates the devices present now.
+   * @returns A new OpenPnp instance.
+   */
+  reopen(): OpenPnp {
+    return new OpenPnp();
+  }
+
   /**
    * Releases the context.
    */
   releaseContext() {
-    if (LIBRARY.symbols.Cap_releaseContext(this.#ctx) !== CAPRESULT_OK) {
-      throw new Error("Cap_releaseContext failed");
+    for (const waiter of this.#waiters.values()) waiter.close();
+    this.#waiters.clear();
+    const res = LIBRARY.symbols.Cap_releaseContext(this.#ctx);
+    if (res !== CAPRESULT_OK) {
+      throw errorFromResult(res, { operation: "Cap_releaseContext" });
     }
   }
 }
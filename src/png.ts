@@ -0,0 +1,438 @@
+/**
+ * Provides a PNG encoder and decoder for frames, written in TypeScript on top of `CompressionStream`.
+ *
+ * @example
+ * ```ts
+ * import { Camera, decodePNG, encodePNG } from "jsr:@sigma/camera";
+ *
+ * using cam = new Camera();
+ * const device = cam.devices().at(0);
+ * if (!device) throw new Error("no device found");
+ * using stream = device.stream({ width: 640, height: 480 });
+ * if (!stream) throw new Error("no stream found");
+ *
+ * const frame = await stream.capture();
+ * await Deno.writeFile("frame.png", await frame.toPNG());
+ * await Deno.writeFile(
+ *   "frame-fast.png",
+ *   await encodePNG(frame, { compressionLevel: 1 }),
+ * );
+ *
+ * const decoded = await decodePNG(await Deno.readFile("frame.png"));
+ * console.log(decoded.width, decoded.height, decoded.pixelFormat);
+ * ```
+ *
+ * @module
+ */
+
+import { Frame, PIXEL_FORMAT_CHANNELS } from "./frame.ts";
+import type { PixelFormat } from "./types.ts";
+
+/**
+ * Represents the options of the PNG encoder.
+ */
+export interface PNGEncodeOptions {
+  /**
+   * Selects how the pixels are compressed, from 0 to 9, defaults to 6.
+   *
+   * Only three settings differ: 0 stores the pixels uncompressed, 1 deflates
+   * them without filtering, and 2 to 9 all pick the best filter of each row
+   * before deflating, producing the same output. The deflate level itself is
+   * the platform default, `CompressionStream` doesn't expose it.
+   */
+  compressionLevel?: number;
+}
+
+const SIGNATURE = new Uint8Array([137, 80, 78, 71, 13, 10, 26, 10]);
+
+/** PNG color type of each pixel format. */
+const COLOR_TYPES: Record<PixelFormat, number> = {
+  gray8: 0,
+  rgb24: 2,
+  rgba32: 6,
+};
+
+/** Number of samples per pixel of each PNG color type. */
+const COLOR_TYPE_CHANNELS: Record<number, number> = {
+  0: 1,
+  2: 3,
+  3: 1,
+  4: 2,
+  6: 4,
+};
+
+/** Bit depths allowed for each PNG color type. */
+const COLOR_TYPE_BIT_DEPTHS: Record<number, number[]> = {
+  0: [1, 2, 4, 8, 16],
+  2: [8, 16],
+  3: [1, 2, 4, 8],
+  4: [8, 16],
+  6: [8, 16],
+};
+
+/** Origin and spacing of the 7 Adam7 passes, as `[x0, y0, dx, dy]`. */
+const ADAM7 = [
+  [0, 0, 8, 8],
+  [4, 0, 8, 8],
+  [0, 4, 4, 8],
+  [2, 0, 4, 4],
+  [0, 2, 2, 4],
+  [1, 0, 2, 2],
+  [0, 1, 1, 2],
+];
+
+/** Largest payload of a stored deflate block. */
+const STORED_BLOCK_SIZE = 65535;
+
+const CRC_TABLE = new Uint32Array(256).map((_, n) => {
+  let c = n;
+  for (let k = 0; k < 8; k++) c = c & 1 ? 0xEDB88320 ^ (c >>> 1) : c >>> 1;
+  return c;
+});
+
+/**
+ * Encodes a frame as a PNG image.
+ * @param frame - The frame to encode, in any pixel format.
+ * @param options - The options of the encoder.
+ * @returns A promise resolving to the PNG file contents.
+ */
+export async function encodePNG(
+  frame: Frame,
+  options: PNGEncodeOptions = {},
+): Promise<Uint8Array> {
+  const level = options.compressionLevel ?? 6;
+  if (!(Number.isInteger(level) && level >= 0 && level <= 9)) {
+    throw new RangeError(
+      `Compression level must be an integer from 0 to 9, got ${level}`,
+    );
+  }
+
+  const { width, height, channels, stride } = frame;
+  const data = frame.data;
+  const rowBytes = width * channels;
+  const filtered = new Uint8Array((rowBytes + 1) * height);
+  const candidates = Array.from({ length: 5 }, () => new Uint8Array(rowBytes));
+  for (let y = 0; y < height; y++) {
+    const row = data.subarray(y * stride, y * stride + rowBytes);
+    const prev = y > 0
+      ? data.subarray((y - 1) * stride, (y - 1) * stride + rowBytes)
+      : undefined;
+    let best = 0;
+    if (level >= 2) {
+      let bestCost = Infinity;
+      for (let filter = 0; filter < 5; filter++) {
+        filterRow(filter, row, prev, channels, candidates[filter]);
+        const cost = filterCost(candidates[filter]);
+        if (cost < bestCost) {
+          bestCost = cost;
+          best = filter;
+        }
+      }
+    } else {
+      filterRow(0, row, prev, channels, candidates[0]);
+    }
+    filtered[y * (rowBytes + 1)] = best;
+    filtered.set(candidates[best], y * (rowBytes + 1) + 1);
+  }
+
+  const ihdr = new Uint8Array(13);
+  const view = new DataView(ihdr.buffer);
+  view.setUint32(0, width);
+  view.setUint32(4, height);
+  ihdr[8] = 8;
+  ihdr[9] = COLOR_TYPES[frame.pixelFormat];
+
+  const chunks = [
+    chunk("IHDR", ihdr),
+    chunk("IDAT", level === 0 ? storeZlib(filtered) : await deflate(filtered)),
+    chunk("IEND", new Uint8Array()),
+  ];
+  const png = new Uint8Array(
+    SIGNATURE.length + chunks.reduce((sum, c) => sum + c.length, 0),
+  );
+  png.set(SIGNATURE);
+  let offset = SIGNATURE.length;
+  for (const c of chunks) {
+    png.set(c, offset);
+    offset += c.length;
+  }
+  return png;
+}
+
+/**
+ * Decodes a PNG image to a frame.
+ *
+ * All color types, bit depths and interlacing are supported. Samples are
+ * reduced to 8 bits. Grayscale images become `gray8`, images with an alpha
+ * channel or a transparent palette become `rgba32`, others become `rgb24`.
+ * @param bytes - The PNG file contents.
+ * @returns A promise resolving to a frame with a timestamp and sequence of 0.
+ */
+export async function decodePNG(bytes: Uint8Array): Promise<Frame> {
+  if (!SIGNATURE.every((b, i) => bytes[i] === b)) {
+    throw new Error("Invalid PNG signature");
+  }
+
+  const view = new DataView(bytes.buffer, bytes.byteOffset, bytes.byteLength);
+  let header: Uint8Array | undefined;
+  let palette: Uint8Array | undefined;
+  let transparency: Uint8Array | undefined;
+  const idat = [];
+  for (let offset = SIGNATURE.length;;) {
+    if (offset + 12 > bytes.length) throw new Error("Truncated PNG");
+    const length = view.getUint32(offset);
+    const end = offset + 12 + length;
+    if (end > bytes.length) throw new Error("Truncated PNG");
+    const type = String.fromCharCode(
+      ...bytes.subarray(offset + 4, offset + 8),
+    );
+    const data = bytes.subarray(offset + 8, end - 4);
+    const crc = crc32(bytes.subarray(offset + 4, end - 4));
+    if (crc !== view.getUint32(end - 4)) {
+      throw new Error(`Corrupted PNG ${type} chunk`);
+    }
+    offset = end;
+    if (type === "IHDR") header = data;
+    else if (type === "PLTE") palette = data;
+    else if (type === "tRNS") transparency = data;
+    else if (type === "IDAT") idat.push(data);
+    else if (type === "IEND") break;
+  }
+  if (!header || header.length !== 13) throw new Error("Missing PNG header");
+
+  const headerView = new DataView(header.buffer, header.byteOffset, 13);
+  const width = headerView.getUint32(0);
+  const height = headerView.getUint32(4);
+  const [bitDepth, colorType, compression, filterMethod, interlace] = header
+    .subarray(8);
+  const channels = COLOR_TYPE_CHANNELS[colorType];
+  if (
+    width === 0 || height === 0 || !channels ||
+    !COLOR_TYPE_BIT_DEPTHS[colorType].includes(bitDepth) ||
+    compression !== 0 || filterMethod !== 0 || interlace > 1
+  ) {
+    throw new Error(
+      `Unsupported PNG (${width}x${height}, color type ${colorType}, bit depth ${bitDepth}, interlace ${interlace})`,
+    );
+  }
+  if (colorType === 3 && !palette) throw new Error("Missing PNG palette");
+
+  const pixelFormat: PixelFormat = colorType === 0
+    ? "gray8"
+    : colorType === 4 || colorType === 6 || (colorType === 3 && transparency)
+    ? "rgba32"
+    : "rgb24";
+  const frame = new Frame({
+    data: new Uint8Array(width * height * PIXEL_FORMAT_CHANNELS[pixelFormat]),
+    width,
+    height,
+    pixelFormat,
+    timestamp: 0,
+    sequence: 0,
+  });
+  const out = frame.data;
+  const outChannels = frame.channels;
+
+  const inflated = await inflate(idat);
+  const bytesPerPixel = Math.max(1, channels * bitDepth / 8);
+  const maxSample = (1 << Math.min(bitDepth, 8)) - 1;
+  let offset = 0;
+  for (const [x0, y0, dx, dy] of interlace ? ADAM7 : [[0, 0, 1, 1]]) {
+    const passWidth = Math.ceil((width - x0) / dx);
+    const passHeight = Math.ceil((height - y0) / dy);
+    if (passWidth <= 0 || passHeight <= 0) continue;
+    const rowBytes = Math.ceil(passWidth * channels * bitDepth / 8);
+    const size = (rowBytes + 1) * passHeight;
+    if (offset + size > inflated.length) throw new Error("Truncated PNG data");
+    const pass = unfilter(
+      inflated.subarray(offset, offset + size),
+      rowBytes,
+      passHeight,
+      bytesPerPixel,
+    );
+    offset += size;
+
+    for (let y = 0; y < passHeight; y++) {
+      const row = pass.subarray(y * rowBytes, (y + 1) * rowBytes);
+      for (let x = 0; x < passWidth; x++) {
+        const o = ((y0 + y * dy) * width + x0 + x * dx) * outChannels;
+        const s = x * channels;
+        if (colorType === 3) {
+          const index = readSample(row, s, bitDepth);
+          if (index * 3 + 3 > palette!.length) {
+            throw new Error(`Invalid PNG palette index ${index}`);
+          }
+          out.set(palette!.subarray(index * 3, index * 3 + 3), o);
+          if (transparency) out[o + 3] = transparency[index] ?? 255;
+        } else if (colorType === 0) {
+          out[o] = Math.round(readSample(row, s, bitDepth) * 255 / maxSample);
+        } else if (colorType === 4) {
+          out.fill(readSample(row, s, bitDepth), o, o + 3);
+          out[o + 3] = readSample(row, s + 1, bitDepth);
+        } else {
+          for (let c = 0; c < channels; c++) {
+            out[o + c] = readSample(row, s + c, bitDepth);
+          }
+        }
+      }
+    }
+  }
+  return frame;
+}
+
+/** Reads the sample at an index of a row, keeping the most significant byte of 16-bit samples. */
+function readSample(row: Uint8Array, index: number, bitDepth: number): number {
+  if (bitDepth === 8) return row[index];
+  if (bitDepth === 16) return row[index * 2];
+  const perByte = 8 / bitDepth;
+  const byte = row[Math.floor(index / perByte)];
+  const shift = 8 - bitDepth * (index % perByte + 1);
+  return (byte >> shift) & ((1 << bitDepth) - 1);
+}
+
+/** The Paeth predictor defined by the PNG specification. */
+function paeth(a: number, b: number, c: number): number {
+  const p = a + b - c;
+  const pa = Math.abs(p - a);
+  const pb = Math.abs(p - b);
+  const pc = Math.abs(p - c);
+  return pa <= pb && pa <= pc ? a : pb <= pc ? b : c;
+}
+
+function predictor(filter: number, a: number, b: number, c: number): number {
+  switch (filter) {
+    case 0:
+      return 0;
+    case 1:
+      return a;
+    case 2:
+      return b;
+    case 3:
+      return (a + b) >> 1;
+    case 4:
+      return paeth(a, b, c);
+    default:
+      throw new Error(`Invalid PNG filter ${filter}`);
+  }
+}
+
+function filterRow(
+  filter: number,
+  row: Uint8Array,
+  prev: Uint8Array | undefined,
+  bytesPerPixel: number,
+  out: Uint8Array,
+) {
+  for (let x = 0; x < row.length; x++) {
+    const a = x >= bytesPerPixel ? row[x - bytesPerPixel] : 0;
+    const b = prev ? prev[x] : 0;
+    const c = prev && x >= bytesPerPixel ? prev[x - bytesPerPixel] : 0;
+    out[x] = (row[x] - predictor(filter, a, b, c)) & 0xFF;
+  }
+}
+
+/** Sum of the filtered bytes as signed values, the heuristic recommended by the specification. */
+function filterCost(row: Uint8Array): number {
+  let cost = 0;
+  for (const value of row) cost += value < 128 ? value : 256 - value;
+  return cost;
+}
+
+function unfilter(
+  data: Uint8Array,
+  rowBytes: number,
+  rows: number,
+  bytesPerPixel: number,
+): Uint8Array {
+  const raw = new Uint8Array(rowBytes * rows);
+  for (let y = 0; y < rows; y++) {
+    const filter = data[y * (rowBytes + 1)];
+    const line = data.subarray(
+      y * (rowBytes + 1) + 1,
+      (y + 1) * (rowBytes + 1),
+    );
+    const out = raw.subarray(y * rowBytes, (y + 1) * rowBytes);
+    const prev = y > 0
+      ? raw.subarray((y - 1) * rowBytes, y * rowBytes)
+      : undefined;
+    for (let x = 0; x < rowBytes; x++) {
+      const a = x >= bytesPerPixel ? out[x - bytesPerPixel] : 0;
+      const b = prev ? prev[x] : 0;
+      const c = prev && x >= bytesPerPixel ? prev[x - bytesPerPixel] : 0;
+      out[x] = (line[x] + predictor(filter, a, b, c)) & 0xFF;
+    }
+  }
+  return raw;
+}
+
+async function deflate(data: Uint8Array): Promise<Uint8Array> {
+  return new Uint8Array(
+    await new Response(
+      new Blob([data]).stream().pipeThrough(new CompressionStream("deflate")),
+    ).arrayBuffer(),
+  );
+}
+
+async function inflate(chunks: Uint8Array[]): Promise<Uint8Array> {
+  return new Uint8Array(
+    await new Response(
+      new Blob(chunks).stream().pipeThrough(new DecompressionStream("deflate")),
+    ).arrayBuffer(),
+  );
+}
+
+/** Wraps data in a zlib stream of uncompressed deflate blocks. */
+function storeZlib(data: Uint8Array): Uint8Array {
+  const blocks = Math.max(1, Math.ceil(data.length / STORED_BLOCK_SIZE));
+  const out = new Uint8Array(2 + blocks * 5 + data.length + 4);
+  const view = new DataView(out.buffer);
+  // deflate with a 32K window, fastest compression, no preset dictionary
+  out[0] = 0x78;
+  out[1] = 0x01;
+  let offset = 2;
+  for (let i = 0; i < blocks; i++) {
+    const block = data.subarray(
+      i * STORED_BLOCK_SIZE,
+      (i + 1) * STORED_BLOCK_SIZE,
+    );
+    out[offset] = i === blocks - 1 ? 1 : 0;
+    view.setUint16(offset + 1, block.length, true);
+    view.setUint16(offset + 3, ~block.length & 0xFFFF, true);
+    out.set(block, offset + 5);
+    offset += 5 + block.length;
+  }
+  view.setUint32(offset, adler32(data));
+  return out;
+}
+
+function adler32(data: Uint8Array): number {
+  let a = 1;
+  let b = 0;
+  for (let i = 0; i < data.length;) {
+    // 5552 bytes is the longest run that can't overflow before the modulo
+    const end = Math.min(i + 5552, data.length);
+    for (; i < end; i++) {
+      a += data[i];
+      b += a;
+    }
+    a %= 65521;
+    b %= 65521;
+  }
+  return ((b << 16) | a) >>> 0;
+}
+
+function crc32(data: Uint8Array): number {
+  let crc = 0xFFFFFFFF;
+  for (const byte of data) crc = CRC_TABLE[(crc ^ byte) & 0xFF] ^ (crc >>> 8);
+  return (crc ^ 0xFFFFFFFF) >>> 0;
+}
+
+function chunk(type: string, data: Uint8Array): Uint8Array {
+  const out = new Uint8Array(12 + data.length);
+  const view = new DataView(out.buffer);
+  view.setUint32(0, data.length);
+  for (let i = 0; i < 4; i++) out[4 + i] = type.charCodeAt(i);
+  out.set(data, 8);
+  view.setUint32(8 + data.length, crc32(out.subarray(4, 8 + data.length)));
+  return out;
+}
@@ -0,0 +1,195 @@
+/**
+ * Provides the WebSocket protocol shared by the frame server and its client.
+ *
+ * Each frame is a binary message made of a 24-byte little-endian header
+ * followed by the encoded pixels:
+ *
+ * | offset | size | field                                          |
+ * | ------ | ---- | ---------------------------------------------- |
+ * | 0      | 1    | protocol version, currently 1                  |
+ * | 1      | 1    | encoding: 0 raw, 1 JPEG, 2 PNG                 |
+ * | 2      | 1    | pixel format: 0 `rgb24`, 1 `rgba32`, 2 `gray8` |
+ * | 3      | 1    | reserved, 0                                    |
+ * | 4      | 4    | sequence, unsigned                             |
+ * | 8      | 8    | timestamp in milliseconds, float               |
+ * | 16     | 4    | width, unsigned                                |
+ * | 20     | 4    | height, unsigned                               |
+ *
+ * Raw pixels are packed without row padding. Control requests and their
+ * replies are JSON text messages.
+ *
+ * @module
+ */
+
+import type { FormatConstraints } from "./constraints.ts";
+import type {
+  CapPropertyID,
+  FormatInfoWithId,
+  PixelFormat,
+  PropertyLimits,
+} from "./types.ts";
+
+/**
+ * Represents how the pixels of a frame message are encoded.
+ *
+ * - `raw`: the pixels packed without row padding
+ * - `jpeg`: a baseline JPEG image
+ * - `png`: a PNG image
+ */
+export type FrameEncoding = "raw" | "jpeg" | "png";
+
+/**
+ * Represents the metadata sent along with each frame.
+ */
+export interface FrameHeader {
+  /** The sequence number of the frame. */
+  sequence: number;
+  /** The capture time in milliseconds since the Unix epoch. */
+  timestamp: number;
+  /** The width of the frame in pixels. */
+  width: number;
+  /** The height of the frame in pixels. */
+  height: number;
+  /** The pixel format of the frame, which encoded images decode to. */
+  pixelFormat: PixelFormat;
+  /** The encoding of the payload. */
+  encoding: FrameEncoding;
+}
+
+/**
+ * Represents a request sent by a client to control the stream.
+ *
+ * - `setFormat`: reopens the stream with the format with this `id`, or the one best satisfying the constraints
+ * - `getProperty`: reads the value, automatic mode and limits of a property
+ * - `setProperty`: sets the value of a property
+ * - `setAuto`: enables or disables the automatic mode of a property
+ * - `setEncoding`: changes the encoding of the frames sent to this client only
+ */
+export type ControlRequest =
+  | { type: "setFormat"; format: FormatConstraints | { id: number } }
+  | { type: "getProperty"; property: CapPropertyID }
+  | { type: "setProperty"; property: CapPropertyID; value: number }
+  | { type: "setAuto"; property: CapPropertyID; enabled: boolean }
+  | { type: "setEncoding"; encoding: FrameEncoding; quality?: number };
+
+/**
+ * Represents a control request on the wire, with the ID its reply refers to.
+ */
+export type ControlMessage = ControlRequest & { id: number };
+
+/**
+ * Represents the state of a property returned by `getProperty`.
+ */
+export interface PropertyState {
+  /** The current value of the property. */
+  value: number;
+  /** Whether automatic mode is enabled. */
+  auto: boolean;
+  /** The range of values supported by the property. */
+  limits: PropertyLimits;
+}
+
+/**
+ * Represents a text message sent by the server.
+ *
+ * - `hello`: the first message of a connection, describing the device
+ * - `format`: the stream was reopened with another format, by any client
+ * - `result`: a request succeeded, with its result if any
+ * - `error`: a request failed, or the capture did if `id` is missing, which
+ *   ends the connection
+ * - `protocolError`: a text message wasn't a request with an integer `id`,
+ *   the connection stays open
+ */
+export type ServerMessage =
+  | {
+    type: "hello";
+    version: number;
+    device: { name: string; uniqueId: string };
+    formats: FormatInfoWithId[];
+    format: FormatInfoWithId;
+    encoding: FrameEncoding;
+  }
+  | { type: "format"; format: FormatInfoWithId }
+  | { type: "result"; id: number; result?: unknown }
+  | {
+    type: "error";
+    id?: number;
+    name: string;
+    message: string;
+    code?: number;
+    operation?: string;
+  }
+  | { type: "protocolError"; message: string };
+
+/** Version of the binary frame header. */
+export const PROTOCOL_VERSION = 1;
+
+/** Size of the binary frame header in bytes. */
+export const FRAME_HEADER_SIZE = 24;
+
+const ENCODINGS: FrameEncoding[] = ["raw", "jpeg", "png"];
+const PIXEL_FORMATS: PixelFormat[] = ["rgb24", "rgba32", "gray8"];
+
+/**
+ * Writes a frame header in front of a payload.
+ * @param header - The metadata of the frame.
+ * @param payload - The encoded pixels.
+ * @returns The binary message.
+ */
+export function encodeFrameMessage(
+  header: FrameHeader,
+  payload: Uint8Array,
+): Uint8Array {
+  const message = new Uint8Array(FRAME_HEADER_SIZE + payload.length);
+  const view = new DataView(message.buffer);
+  view.setUint8(0, PROTOCOL_VERSION);
+  view.setUint8(1, ENCODINGS.indexOf(header.encoding));
+  view.setUint8(2, PIXEL_FORMATS.indexOf(header.pixelFormat));
+  view.setUint32(4, header.sequence, true);
+  view.setFloat64(8, header.timestamp, true);
+  view.setUint32(16, header.width, true);
+  view.setUint32(20, header.height, true);
+  message.set(payload, FRAME_HEADER_SIZE);
+  return message;
+}
+
+/**
+ * Reads the header of a binary message.
+ * @param message - The binary message.
+ * @returns The metadata of the frame, and the payload sharing the message buffer.
+ * @throws {Error} If the message is truncated or uses an unknown version or code.
+ */
+export function decodeFrameMessage(
+  message: Uint8Array,
+): { header: FrameHeader; payload: Uint8Array } {
+  if (message.length < FRAME_HEADER_SIZE) {
+    throw new Error(`Frame message of ${message.length} bytes is truncated`);
+  }
+  const view = new DataView(
+    message.buffer,
+    message.byteOffset,
+    message.byteLength,
+  );
+  const version = view.getUint8(0);
+  if (version !== PROTOCOL_VERSION) {
+    throw new Error(`Unsupported frame protocol version ${version}`);
+  }
+  const encoding = ENCODINGS[view.getUint8(1)];
+  const pixelFormat = PIXEL_FORMATS[view.getUint8(2)];
+  if (!encoding || !pixelFormat) {
+    throw new Error(
+      `Unknown frame encoding ${view.getUint8(1)} or pixel format ${
+        view.getUint8(2)
+      }`,
+    );
+  }
+  const header = {
+    sequence: view.getUint32(4, true),
+    timestamp: view.getFloat64(8, true),
+    width: view.getUint32(16, true),
+    height: view.getUint32(20, true),
+    pixelFormat,
+    encoding,
+  };
+  return { header, payload: message.subarray(FRAME_HEADER_SIZE) };
+}
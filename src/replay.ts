@@ -0,0 +1,644 @@
+/**
+ * Provides a capture backend replaying recorded footage as virtual devices.
+ *
+ * A device is either a directory of PPM/PGM/PNG images, played in file name
+ * order, or a Y4M (YUV4MPEG2) file. Frames are read from disk and decoded to
+ * RGB24 when they are due, at the pace of the device fps, so recordings of
+ * any length are replayed in constant memory.
+ *
+ * @example
+ * ```ts
+ * import { Camera } from "jsr:@sigma/camera";
+ * import { ReplayBackend } from "jsr:@sigma/camera/replay";
+ *
+ * const backend = await ReplayBackend.open({
+ *   devices: [{ path: "./recordings/board-01", fps: 15, mode: "once" }],
+ * });
+ * using cam = new Camera(backend);
+ *
+ * const device = cam.devices().at(0);
+ * if (!device) throw new Error("no device found");
+ * using stream = device.stream(device.formats()[0]);
+ * if (!stream) throw new Error("no stream found");
+ *
+ * for await (const frame of stream.next()) {
+ *   console.log("frame", frame.sequence, frame.timestamp);
+ * }
+ * ```
+ *
+ * @module
+ */
+
+import type { CaptureBackend } from "./backend.ts";
+import {
+  CameraError,
+  DeviceNotFoundError,
+  FormatNotSupportedError,
+} from "./errors.ts";
+import type { Frame } from "./frame.ts";
+import { decodeNetpbm } from "./netpbm.ts";
+import { decodePNG } from "./png.ts";
+import {
+  decodeY4MFrame,
+  MAX_Y4M_LINE_LENGTH,
+  parseY4MHeader,
+  y4mFrameSize,
+} from "./y4m.ts";
+import type { Y4MColorspace, Y4MHeader } from "./y4m.ts";
+import type { CapPropertyID, FormatInfo } from "./types.ts";
+
+/**
+ * Represents what happens once the last recorded frame was delivered.
+ *
+ * - `loop`: start again from the first frame
+ * - `once`: stop after the last frame, ending `Stream.next()`
+ */
+export type ReplayMode = "loop" | "once";
+
+/**
+ * Represents the configuration of a replayed device.
+ */
+export interface ReplayDeviceOptions {
+  /** Path to a directory of .ppm/.pgm/.pnm/.png images or to a .y4m file. */
+  path: string;
+  /** The name of the device, defaults to the path. */
+  name?: string;
+  /** The unique identifier of the device, defaults to `replay:<path>`. */
+  uniqueId?: string;
+  /** The frame rate, defaults to the Y4M header rate or 30 for image directories. */
+  fps?: number;
+  /** What happens after the last frame, defaults to `loop`. */
+  mode?: ReplayMode;
+}
+
+/**
+ * Represents the configuration of a ReplayBackend.
+ */
+export interface ReplayBackendOptions {
+  /** The recordings exposed as devices. */
+  devices: ReplayDeviceOptions[];
+  /** The clock in milliseconds used to pace frames, defaults to `performance.now`. */
+  now?: () => number;
+}
+
+interface Recording {
+  name: string;
+  uniqueId: string;
+  mode: ReplayMode;
+  format: FormatInfo;
+  /** The number of frames of the recording. */
+  length: number;
+  /** Reads a frame from disk and decodes it as packed RGB24. */
+  decode: (index: number) => Promise<Uint8Array>;
+  /** The frames decoded last, by index. */
+  decoded: Map<number, Uint8Array>;
+  /** The frames being decoded, by index. */
+  decoding: Map<number, Promise<Uint8Array>>;
+}
+
+interface ReplayStream {
+  recording: Recording;
+  openedAt: number;
+  captured: number;
+  /** The newest due frame found decoded, captured or not. */
+  available?: { count: number; data: Uint8Array };
+  properties: Map<CapPropertyID, { value: number; auto: boolean }>;
+}
+
+const PROPERTY_LIMITS = { exmin: 0, exmax: 255, edefault: 128 };
+
+const IMAGE_EXTENSIONS = [".ppm", ".pgm", ".pnm", ".png"];
+
+/** Number of decoded frames kept per recording, enough for a few streams. */
+const DECODED_FRAMES = 8;
+
+/**
+ * Represents a capture backend replaying recorded frame sequences.
+ *
+ * Each recording exposes a single format, so existing `Stream.next()` consumers work unchanged.
+ */
+export class ReplayBackend implements CaptureBackend {
+  #recordings: Recording[];
+  #now: () => number;
+  #streams = new Map<number, ReplayStream>();
+  #nextStreamId = 0;
+
+  private constructor(recordings: Recording[], now: () => number) {
+    this.#recordings = recordings;
+    this.#now = now;
+  }
+
+  /**
+   * Indexes the recordings and creates a ReplayBackend. Only the first frame
+   * of image directories is decoded, to know their size.
+   * @param options - The recordings to expose and the clock to use.
+   * @returns A promise resolving to the backend.
+   */
+  static async open(options: ReplayBackendOptions): Promise<ReplayBackend> {
+    const recordings = [];
+    for (const device of options.devices) {
+      recordings.push(await loadRecording(device));
+    }
+    return new ReplayBackend(
+      recordings,
+      options.now ?? (() => performance.now()),
+    );
+  }
+
+  getDeviceCount(): number {
+    return this.#recordings.length;
+  }
+
+  getDeviceName(id: number): string {
+    return this.#recording(id).name;
+  }
+
+  getDeviceUniqueId(id: number): string {
+    return this.#recording(id).uniqueId;
+  }
+
+  getNumFormats(id: number): number {
+    this.#recording(id);
+    return 1;
+  }
+
+  getFormatInfo(id: number, formatId: number): FormatInfo {
+    const recording = this.#recording(id);
+    if (formatId !== 0) {
+      throw new FormatNotSupportedError(
+        `ReplayBackend: unknown format ${formatId}`,
+        { operation: "getFormatInfo", deviceId: id },
+      );
+    }
+    return { ...recording.format };
+  }
+
+  setProperty(id: number, propertyId: CapPropertyID, value: number) {
+    this.#property(id, propertyId).value = value;
+  }
+
+  getProperty(id: number, propertyId: CapPropertyID): number {
+    return this.#property(id, propertyId).value;
+  }
+
+  setAutoProperty(id: number, propertyId: CapPropertyID, value: number) {
+    this.#property(id, propertyId).auto = value !== 0;
+  }
+
+  getAutoProperty(id: number, propertyId: CapPropertyID): boolean {
+    return this.#property(id, propertyId).auto;
+  }
+
+  getPropertyLimits(
+    id: number,
+    _propertyId: CapPropertyID,
+  ): { exmax: number; exmin: number; edefault: number } {
+    this.#stream(id);
+    return { ...PROPERTY_LIMITS };
+  }
+
+  openStream(deviceId: number, deviceFormatId: number): number {
+    const recording = this.#recording(deviceId);
+    this.getFormatInfo(deviceId, deviceFormatId);
+    const streamId = this.#nextStreamId++;
+    this.#streams.set(streamId, {
+      recording,
+      openedAt: this.#now(),
+      captured: 0,
+      properties: new Map(),
+    });
+    return streamId;
+  }
+
+  isOpenStream(id: number): boolean {
+    return this.#streams.has(id);
+  }
+
+  hasNewFrame(id: number): boolean {
+    return this.#ready(this.#stream(id)) !== undefined;
+  }
+
+  async waitForNewFrame(id: number, timeout?: number): Promise<boolean> {
+    const stream = this.#stream(id);
+    const { recording } = stream;
+    const deadline = timeout === undefined
+      ? undefined
+      : performance.now() + timeout;
+    while (!this.#ready(stream)) {
+      // the recording is over, no frame will ever come
+      if (this.isStreamEnded(id)) return false;
+      const remaining = deadline === undefined
+        ? Infinity
+        : deadline - performance.now();
+      if (remaining <= 0) return false;
+      const frameCount = this.#frameCount(stream);
+      if (frameCount > stream.captured) {
+        // the frame is due, it is only available once decoded
+        const index = (frameCount - 1) % recording.length;
+        await within(decodeFrame(recording, index), remaining);
+      } else {
+        // frames are paced by the clock, so the next one is due at a known time
+        const due = stream.openedAt + frameCount * 1000 / recording.format.fps;
+        await within(
+          new Promise((resolve) =>
+            setTimeout(resolve, Math.max(0, due - this.#now()))
+          ),
+          remaining,
+        );
+      }
+    }
+    return true;
+  }
+
+  /**
+   * Checks if a recording played once delivered its last frame.
+   * @param id - The ID of the stream.
+   * @returns A boolean indicating if no frame will ever come.
+   */
+  isStreamEnded(id: number): boolean {
+    const { recording, captured } = this.#stream(id);
+    return recording.mode === "once" && captured >= recording.length;
+  }
+
+  getStreamFrameCount(id: number): number {
+    // frames are captured once decoded, possibly after the next one is due,
+    // so the count stops at the captured one to keep sequences exact
+    return this.#stream(id).captured;
+  }
+
+  captureFrame(id: number, buffer: Uint8Array): void {
+    const stream = this.#stream(id);
+    const ready = this.#ready(stream);
+    if (!ready) {
+      throw new CameraError(
+        `ReplayBackend: no new frame decoded for stream ${id}`,
+        { operation: "captureFrame", streamId: id },
+      );
+    }
+    stream.captured = ready.count;
+    const { data } = ready;
+    buffer.set(data.subarray(0, Math.min(data.byteLength, buffer.byteLength)));
+    // read the next frame ahead, errors are reported when waiting for it
+    const { recording } = stream;
+    if (!this.isStreamEnded(id)) {
+      decodeFrame(recording, ready.count % recording.length).catch(() => {});
+    }
+  }
+
+  closeStream(id: number) {
+    if (!this.#streams.delete(id)) {
+      throw new CameraError(`ReplayBackend: unknown stream ${id}`, {
+        operation: "closeStream",
+        streamId: id,
+      });
+    }
+  }
+
+  releaseContext() {
+    this.#streams.clear();
+  }
+
+  #recording(id: number): Recording {
+    const recording = this.#recordings[id];
+    if (!recording) {
+      throw new DeviceNotFoundError(`ReplayBackend: unknown device ${id}`, {
+        operation: "getDevice",
+        deviceId: id,
+      });
+    }
+    return recording;
+  }
+
+  #stream(id: number): ReplayStream {
+    const stream = this.#streams.get(id);
+    if (!stream) {
+      throw new CameraError(`ReplayBackend: unknown stream ${id}`, {
+        operation: "getStream",
+        streamId: id,
+      });
+    }
+    return stream;
+  }
+
+  #property(id: number, propertyId: CapPropertyID) {
+    const properties = this.#stream(id).properties;
+    let property = properties.get(propertyId);
+    if (!property) {
+      property = { value: PROPERTY_LIMITS.edefault, auto: false };
+      properties.set(propertyId, property);
+    }
+    return property;
+  }
+
+  /** Number of frames delivered since the stream was opened, the first one is delivered right away. */
+  #frameCount(stream: ReplayStream): number {
+    const { format, length, mode } = stream.recording;
+    const elapsed = this.#now() - stream.openedAt;
+    const count = Math.floor(elapsed * format.fps / 1000) + 1;
+    return mode === "once" ? Math.min(count, length) : count;
+  }
+
+  /**
+   * Retrieves the newest due frame not captured yet, if it was decoded, and
+   * starts decoding it otherwise.
+   */
+  #ready(
+    stream: ReplayStream,
+  ): { count: number; data: Uint8Array } | undefined {
+    const { recording, captured } = stream;
+    const frameCount = this.#frameCount(stream);
+    if (frameCount > captured) {
+      const index = (frameCount - 1) % recording.length;
+      const data = recording.decoded.get(index);
+      if (data) stream.available = { count: frameCount, data };
+      else decodeFrame(recording, index).catch(() => {});
+    }
+    const { available } = stream;
+    if (available && available.count > captured) return available;
+  }
+
+}
+
+async function loadRecording(
+  options: ReplayDeviceOptions,
+): Promise<Recording> {
+  const name = options.name ?? options.path;
+  const uniqueId = options.uniqueId ?? `replay:${options.path}`;
+  const mode = options.mode ?? "loop";
+  const cache = { decoded: new Map(), decoding: new Map() };
+
+  if ((await Deno.stat(options.path)).isDirectory) {
+    const { width, height, length, decode } = await openImageDirectory(
+      options.path,
+    );
+    return {
+      name,
+      uniqueId,
+      mode,
+      format: {
+        width,
+        height,
+        fourcc: "RGB3",
+        fps: options.fps ?? 30,
+        bpp: 24,
+      },
+      length,
+      decode,
+      ...cache,
+    };
+  }
+
+  if (options.path.toLowerCase().endsWith(".y4m")) {
+    const y4m = await openY4M(options.path);
+    return {
+      name,
+      uniqueId,
+      mode,
+      format: {
+        width: y4m.width,
+        height: y4m.height,
+        fourcc: y4m.fourcc,
+        fps: options.fps ?? y4m.fps,
+        bpp: y4m.bpp,
+      },
+      length: y4m.length,
+      decode: y4m.decode,
+      ...cache,
+    };
+  }
+
+  throw new Error(
+    `ReplayBackend: ${options.path} is neither a directory nor a .y4m file`,
+  );
+}
+
+/**
+ * Decodes a frame of a recording, sharing the result with concurrent calls
+ * and keeping the last decoded frames.
+ */
+function decodeFrame(
+  recording: Recording,
+  index: number,
+): Promise<Uint8Array> {
+  const decoded = recording.decoded.get(index);
+  if (decoded) return Promise.resolve(decoded);
+  let decoding = recording.decoding.get(index);
+  if (!decoding) {
+    decoding = recording.decode(index)
+      .then((data) => {
+        recording.decoded.set(index, data);
+        if (recording.decoded.size > DECODED_FRAMES) {
+          recording.decoded.delete(recording.decoded.keys().next().value!);
+        }
+        return data;
+      })
+      .finally(() => recording.decoding.delete(index));
+    recording.decoding.set(index, decoding);
+  }
+  return decoding;
+}
+
+/**
+ * Waits for a promise, at most `timeout` milliseconds.
+ */
+async function within(promise: Promise<unknown>, timeout: number) {
+  if (timeout === Infinity) {
+    await promise;
+    return;
+  }
+  let timer: number | undefined;
+  try {
+    await Promise.race([
+      promise,
+      new Promise((resolve) => timer = setTimeout(resolve, timeout)),
+    ]);
+  } finally {
+    clearTimeout(timer);
+  }
+}
+
+/**
+ * Lists the images of a directory and decodes the first one to know the size
+ * of the frames.
+ */
+async function openImageDirectory(path: string): Promise<{
+  width: number;
+  height: number;
+  length: number;
+  decode: (index: number) => Promise<Uint8Array>;
+}> {
+  const files = [];
+  for await (const entry of Deno.readDir(path)) {
+    const lower = entry.name.toLowerCase();
+    if (entry.isFile && IMAGE_EXTENSIONS.some((ext) => lower.endsWith(ext))) {
+      files.push(entry.name);
+    }
+  }
+  files.sort((a, b) => a.localeCompare(b, undefined, { numeric: true }));
+  if (files.length === 0) {
+    throw new Error(`ReplayBackend: no image found in ${path}`);
+  }
+
+  const load = async (file: string): Promise<RGBImage> => {
+    const bytes = await Deno.readFile(`${path}/${file}`);
+    try {
+      return toRGB(
+        file.toLowerCase().endsWith(".png")
+          ? await decodePNG(bytes)
+          : decodeNetpbm(bytes),
+      );
+    } catch (error) {
+      throw new Error(`ReplayBackend: could not decode ${file}`, {
+        cause: error,
+      });
+    }
+  };
+  const { width, height } = await load(files[0]);
+  const decode = async (index: number) => {
+    const file = files[index];
+    const image = await load(file);
+    if (image.width !== width || image.height !== height) {
+      throw new Error(
+        `ReplayBackend: ${file} is ${image.width}x${image.height}, expected ${width}x${height}`,
+      );
+    }
+    return image.data;
+  };
+  return { width, height, length: files.length, decode };
+}
+
+interface RGBImage {
+  width: number;
+  height: number;
+  /** Pixels as packed RGB24. */
+  data: Uint8Array;
+}
+
+/**
+ * Converts a decoded image to RGB24, dropping the alpha channel.
+ */
+function toRGB(frame: Frame): RGBImage {
+  const { width, height, channels, stride } = frame;
+  const pixels = frame.data;
+  const data = new Uint8Array(width * height * 3);
+  for (let y = 0; y < height; y++) {
+    for (let x = 0; x < width; x++) {
+      const i = y * width + x;
+      const pixel = y * stride + x * channels;
+      if (channels === 1) {
+        data.fill(pixels[pixel], i * 3, i * 3 + 3);
+      } else {
+        data.set(pixels.subarray(pixel, pixel + 3), i * 3);
+      }
+    }
+  }
+  return { width, height, data };
+}
+
+/** FourCC and bits per pixel of the planar formats of each Y4M colorspace. */
+const Y4M_FORMATS: Record<Y4MColorspace, { fourcc: string; bpp: number }> = {
+  "420jpeg": { fourcc: "I420", bpp: 12 },
+  "420paldv": { fourcc: "I420", bpp: 12 },
+  "420mpeg2": { fourcc: "I420", bpp: 12 },
+  "420": { fourcc: "I420", bpp: 12 },
+  "422": { fourcc: "422P", bpp: 16 },
+  "444": { fourcc: "444P", bpp: 24 },
+  "mono": { fourcc: "GREY", bpp: 8 },
+};
+
+/**
+ * Indexes the frames of a YUV4MPEG2 file, which are read and converted to
+ * RGB24 on demand.
+ */
+async function openY4M(path: string): Promise<{
+  width: number;
+  height: number;
+  fps: number;
+  fourcc: string;
+  bpp: number;
+  length: number;
+  decode: (index: number) => Promise<Uint8Array>;
+}> {
+  let header: Y4MHeader;
+  const offsets = [];
+  try {
+    using file = await Deno.open(path);
+    const { size } = await file.stat();
+    let line = await readLine(file, 0);
+    if (!line) throw new Error("Empty Y4M file");
+    header = parseY4MHeader(line.text);
+    const frameSize = y4mFrameSize(header);
+    let offset = line.end;
+    while (offset < size) {
+      line = await readLine(file, offset);
+      if (!line || (line.text !== "FRAME" && !line.text.startsWith("FRAME "))) {
+        throw new Error(`Invalid Y4M frame header at offset ${offset}`);
+      }
+      if (line.end + frameSize > size) throw new Error("Truncated Y4M frame");
+      offsets.push(line.end);
+      offset = line.end + frameSize;
+    }
+  } catch (error) {
+    throw new Error(`ReplayBackend: could not decode ${path}`, {
+      cause: error,
+    });
+  }
+  if (offsets.length === 0) {
+    throw new Error("ReplayBackend: Y4M file has no frame");
+  }
+
+  const frameSize = y4mFrameSize(header);
+  const decode = async (index: number) => {
+    using file = await Deno.open(path);
+    const planes = await readAt(file, offsets[index], frameSize);
+    if (planes.length < frameSize) {
+      throw new Error(`ReplayBackend: ${path} was truncated`);
+    }
+    return toRGB(decodeY4MFrame(planes, header, index)).data;
+  };
+  return {
+    width: header.width,
+    height: header.height,
+    fps: header.fps,
+    ...Y4M_FORMATS[header.colorspace],
+    length: offsets.length,
+    decode,
+  };
+}
+
+/**
+ * Reads a line of a Y4M file.
+ * @returns The line without its line feed and the offset following it, undefined at the end of the file.
+ */
+async function readLine(
+  file: Deno.FsFile,
+  offset: number,
+): Promise<{ text: string; end: number } | undefined> {
+  const bytes = await readAt(file, offset, MAX_Y4M_LINE_LENGTH + 1);
+  if (bytes.length === 0) return;
+  const end = bytes.indexOf(0x0A);
+  if (end < 0) throw new Error(`Y4M line at offset ${offset} is too long`);
+  return {
+    text: new TextDecoder().decode(bytes.subarray(0, end)),
+    end: offset + end + 1,
+  };
+}
+
+/**
+ * Reads up to `size` bytes at an offset of a file.
+ */
+async function readAt(
+  file: Deno.FsFile,
+  offset: number,
+  size: number,
+): Promise<Uint8Array> {
+  await file.seek(offset, Deno.SeekMode.Start);
+  const bytes = new Uint8Array(size);
+  let read = 0;
+  while (read < size) {
+    const n = await file.read(bytes.subarray(read));
+    if (n === null) break;
+    read += n;
+  }
+  return bytes.subarray(0, read);
+}
@@ -0,0 +1,500 @@
+/**
+ * Provides HTTP servers publishing the frames of a stream, such as a live
+ * MJPEG preview that any browser can display.
+ *
+ * @example
+ * ```ts
+ * import { Camera } from "jsr:@sigma/camera";
+ * import { serveMJPEG } from "jsr:@sigma/camera/server";
+ *
+ * using cam = new Camera();
+ * const device = cam.devices().at(0);
+ * if (!device) throw new Error("no device found");
+ * using stream = device.stream({ width: 1280, height: 720 });
+ * if (!stream) throw new Error("no stream found");
+ *
+ * // open http://localhost:8080/ in a browser, or fetch /snapshot.jpg
+ * const server = serveMJPEG(stream, { port: 8080, quality: 70, maxFps: 15 });
+ * await server.finished;
+ * ```
+ *
+ * @module
+ */
+
+import type { Device, Stream } from "./camera.ts";
+import { type FormatConstraints, resolveFormat } from "./constraints.ts";
+import { CameraError } from "./errors.ts";
+import type { Frame } from "./frame.ts";
+import { encodeJPEG } from "./jpeg.ts";
+import { encodePNG } from "./png.ts";
+import { encodeFrameMessage, PROTOCOL_VERSION } from "./protocol.ts";
+import type {
+  ControlMessage,
+  FrameEncoding,
+  PropertyState,
+  ServerMessage,
+} from "./protocol.ts";
+import {
+  Broadcast,
+  limitFrameRate,
+  mapFrames,
+  packFrame,
+  readableFromFrames,
+} from "./streams.ts";
+import type { FormatInfoWithId, StreamOptions } from "./types.ts";
+
+/**
+ * Represents the options of an MJPEG request handler.
+ */
+export interface MJPEGHandlerOptions {
+  /** The path of the `multipart/x-mixed-replace` stream, defaults to `/`. */
+  path?: string;
+  /** The path of the single JPEG image endpoint, defaults to `/snapshot.jpg`. */
+  snapshotPath?: string;
+  /** The JPEG quality from 1 to 100, defaults to 80. */
+  quality?: number;
+  /** The maximum number of frames per second sent to viewers, unlimited by default. */
+  maxFps?: number;
+}
+
+/**
+ * Represents where and how long a server listens.
+ */
+export interface ServeOptions {
+  /** The port to listen on, defaults to 8080. */
+  port?: number;
+  /** The hostname to listen on, defaults to `127.0.0.1`. Use `0.0.0.0` to accept other machines. */
+  hostname?: string;
+  /** Shuts the server down when aborted. */
+  signal?: AbortSignal;
+  /** Called once the server listens, instead of logging the address. */
+  onListen?: (localAddr: Deno.NetAddr) => void;
+}
+
+/**
+ * Represents the options of an MJPEG server.
+ */
+export interface MJPEGServeOptions extends MJPEGHandlerOptions, ServeOptions {}
+
+/** Separates the JPEG parts of the multipart stream. */
+const BOUNDARY = "frame";
+
+const ENCODER = new TextEncoder();
+
+/**
+ * Creates a request handler serving the frames of a stream as MJPEG.
+ * All viewers share a single capture loop, which only runs while at least
+ * one of them is connected. Each frame is encoded once, and a viewer that
+ * can't keep up skips frames instead of delaying the others.
+ * @param stream - The stream to capture frames from.
+ * @param options - The paths and encoding of the frames.
+ * @returns A handler responding to the stream and snapshot paths, and with 404 otherwise.
+ */
+export function mjpegHandler(
+  stream: Stream,
+  options: MJPEGHandlerOptions = {},
+): (request: Request) => Promise<Response> {
+  const {
+    path = "/",
+    snapshotPath = "/snapshot.jpg",
+    quality = 80,
+    maxFps,
+  } = options;
+  // validates maxFps up front instead of failing every request
+  if (maxFps !== undefined) limitFrameRate(maxFps);
+
+  const jpegs = new Broadcast<Uint8Array>((signal) => {
+    let frames = readableFromFrames(stream.next({ signal }));
+    if (maxFps !== undefined) {
+      frames = frames.pipeThrough(limitFrameRate(maxFps));
+    }
+    return frames.pipeThrough(
+      mapFrames((frame) => encodeJPEG(frame, { quality })),
+    );
+  });
+
+  return async (request) => {
+    const { pathname } = new URL(request.url);
+    if (pathname !== path && pathname !== snapshotPath) {
+      return new Response("Not found", { status: 404 });
+    }
+    if (request.method !== "GET") {
+      return new Response("Method not allowed", {
+        status: 405,
+        headers: { allow: "GET" },
+      });
+    }
+
+    if (pathname === snapshotPath) {
+      const images = jpegs.subscribe(request.signal);
+      try {
+        const { value, done } = await images.next();
+        if (done) return new Response("Stream ended", { status: 503 });
+        return new Response(value, {
+          headers: {
+            "content-type": "image/jpeg",
+            "cache-control": "no-store",
+          },
+        });
+      } catch (error) {
+        const message = error instanceof Error ? error.message : String(error);
+        return new Response(message, { status: 503 });
+      } finally {
+        await images.return();
+      }
+    }
+
+    const controller = new AbortController();
+    const images = jpegs.subscribe(controller.signal);
+    const body = new ReadableStream<Uint8Array>({
+      async pull(body) {
+        const { value, done } = await images.next();
+        if (done) {
+          body.close();
+          return;
+        }
+        body.enqueue(ENCODER.encode(
+          `--${BOUNDARY}\r\nContent-Type: image/jpeg\r\n` +
+            `Content-Length: ${value.length}\r\n\r\n`,
+        ));
+        body.enqueue(value);
+        body.enqueue(ENCODER.encode("\r\n"));
+      },
+      async cancel(reason) {
+        controller.abort(reason);
+        await images.return().catch(() => {});
+      },
+    }, { highWaterMark: 0 });
+    return new Response(body, {
+      headers: {
+        "content-type": `multipart/x-mixed-replace; boundary=${BOUNDARY}`,
+        "cache-control": "no-store",
+      },
+    });
+  };
+}
+
+/**
+ * Serves the frames of a stream as MJPEG over HTTP with `Deno.serve`.
+ * See `mjpegHandler` for the endpoints.
+ * @param stream - The stream to capture frames from.
+ * @param options - The address to listen on, the paths and encoding of the frames.
+ * @returns The server, which can be shut down with `shutdown()`.
+ */
+export function serveMJPEG(
+  stream: Stream,
+  options: MJPEGServeOptions = {},
+): Deno.HttpServer<Deno.NetAddr> {
+  const {
+    port = 8080,
+    hostname = "127.0.0.1",
+    signal,
+    onListen,
+    ...handlerOptions
+  } = options;
+  return Deno.serve(
+    { port, hostname, signal, onListen },
+    mjpegHandler(stream, handlerOptions),
+  );
+}
+
+/**
+ * Represents the options of a WebSocket request handler.
+ */
+export interface WebSocketHandlerOptions {
+  /** The path accepting WebSocket connections, defaults to `/`. */
+  path?: string;
+  /** The initial format, or constraints to select it with, defaults to the largest and fastest. */
+  format?: FormatInfoWithId | FormatConstraints;
+  /** The options of the streams opened on the device. */
+  streamOptions?: StreamOptions;
+  /** The encoding of the frames until a client asks for another one, defaults to `jpeg`. */
+  encoding?: FrameEncoding;
+  /** The JPEG quality from 1 to 100 until a client asks for another one, defaults to 80. */
+  quality?: number;
+  /** The maximum number of frames per second sent to each client, unlimited by default. */
+  maxFps?: number;
+}
+
+/**
+ * Represents the options of a WebSocket server.
+ */
+export interface WebSocketServeOptions
+  extends WebSocketHandlerOptions, ServeOptions {}
+
+/**
+ * Bytes a client may have pending before frames are skipped for it, so that
+ * a slow connection gets the latest frame instead of an ever growing backlog.
+ */
+const MAX_BUFFERED_AMOUNT = 4 << 20;
+
+/**
+ * Creates a request handler streaming the frames of a device over WebSocket,
+ * with the binary protocol described in the `protocol` module.
+ *
+ * The device is opened when the first client connects and closed when the
+ * last one leaves. All clients share a single capture loop, and each frame is
+ * encoded once per encoding in use. Clients can change the format of the
+ * stream and its properties with control messages, and choose their own
+ * encoding.
+ * @param device - The device to capture frames from.
+ * @param options - The path, stream and encoding of the frames.
+ * @returns A handler upgrading requests to the path, and responding with 404 otherwise.
+ */
+export function websocketHandler(
+  device: Device,
+  options: WebSocketHandlerOptions = {},
+): (request: Request) => Response {
+  const {
+    path = "/",
+    streamOptions,
+    encoding = "jpeg",
+    quality = 80,
+    maxFps,
+  } = options;
+  if (maxFps !== undefined) limitFrameRate(maxFps);
+  let format = resolveFormat(device.info(), options.format ?? {}, "setFormat");
+  let active: Stream | undefined;
+  // aborted to reopen the stream with a new format
+  let reopen: AbortController | undefined;
+  const sockets = new Set<WebSocket>();
+
+  const frames = new Broadcast<Frame>(async function* (signal) {
+    while (true) {
+      const change = new AbortController();
+      reopen = change;
+      using stream = device.stream(format, streamOptions);
+      if (!stream) {
+        throw new CameraError(`Could not open a stream on ${device.name()}`, {
+          operation: "Device.stream",
+          deviceId: device.info().id,
+        });
+      }
+      active = stream;
+      try {
+        yield* stream.next({
+          signal: AbortSignal.any([signal, change.signal]),
+        });
+        return;
+      } catch (error) {
+        if (signal.aborted || !change.signal.aborted) throw error;
+      } finally {
+        active = undefined;
+      }
+    }
+  });
+
+  const encoded = new WeakMap<Frame, Map<string, Promise<Uint8Array>>>();
+  const encode = (frame: Frame, encoding: FrameEncoding, quality: number) => {
+    const key = encoding === "jpeg" ? `jpeg:${quality}` : encoding;
+    let messages = encoded.get(frame);
+    if (!messages) encoded.set(frame, messages = new Map());
+    let message = messages.get(key);
+    if (!message) {
+      message = encodeFrame(frame, encoding, quality);
+      messages.set(key, message);
+    }
+    return message;
+  };
+
+  const control = (
+    request: ControlMessage,
+    client: { encoding: FrameEncoding; quality: number },
+  ): unknown => {
+    switch (request.type) {
+      case "setFormat": {
+        format = resolveFormat(device.info(), request.format, "setFormat");
+        reopen?.abort();
+        const message = JSON.stringify({ type: "format", format });
+        for (const socket of sockets) socket.send(message);
+        return { format };
+      }
+      case "setEncoding": {
+        const { encoding, quality = client.quality } = request;
+        if (!["raw", "jpeg", "png"].includes(encoding)) {
+          throw new RangeError(`Unsupported frame encoding ${encoding}`);
+        }
+        if (!(Number.isInteger(quality) && quality >= 1 && quality <= 100)) {
+          throw new RangeError(
+            `Quality must be an integer from 1 to 100, got ${quality}`,
+          );
+        }
+        client.encoding = encoding;
+        client.quality = quality;
+        return;
+      }
+    }
+    if (!active) {
+      throw new CameraError("The stream is not open", {
+        operation: request.type,
+      });
+    }
+    const { properties } = active;
+    switch (request.type) {
+      case "getProperty":
+        return {
+          value: properties.get(request.property),
+          auto: properties.isAuto(request.property),
+          limits: properties.limits(request.property),
+        } satisfies PropertyState;
+      case "setProperty":
+        return properties.set(request.property, request.value);
+      case "setAuto":
+        return properties.setAuto(request.property, request.enabled);
+      default:
+        throw new RangeError(
+          `Unknown control request ${(request as { type: unknown }).type}`,
+        );
+    }
+  };
+
+  return (request) => {
+    const { pathname } = new URL(request.url);
+    if (pathname !== path) return new Response("Not found", { status: 404 });
+    if (request.headers.get("upgrade")?.toLowerCase() !== "websocket") {
+      return new Response("Expected a WebSocket upgrade", {
+        status: 426,
+        headers: { upgrade: "websocket" },
+      });
+    }
+
+    const { socket, response } = Deno.upgradeWebSocket(request);
+    const client = { encoding, quality };
+    const controller = new AbortController();
+    const send = (message: ServerMessage) => {
+      if (socket.readyState === WebSocket.OPEN) {
+        socket.send(JSON.stringify(message));
+      }
+    };
+
+    socket.onopen = async () => {
+      sockets.add(socket);
+      send({
+        type: "hello",
+        version: PROTOCOL_VERSION,
+        device: { name: device.name(), uniqueId: device.uniqueId() },
+        formats: device.formats(),
+        format,
+        encoding,
+      });
+      let readable = readableFromFrames(
+        frames.subscribe(controller.signal),
+        controller,
+      );
+      if (maxFps !== undefined) {
+        readable = readable.pipeThrough(limitFrameRate(maxFps));
+      }
+      try {
+        for await (const frame of readable) {
+          if (socket.bufferedAmount > MAX_BUFFERED_AMOUNT) continue;
+          const message = await encode(frame, client.encoding, client.quality);
+          if (socket.readyState !== WebSocket.OPEN) break;
+          socket.send(message);
+        }
+        socket.close(1000, "Stream ended");
+      } catch (error) {
+        if (controller.signal.aborted) return;
+        send(errorMessage(error));
+        socket.close(1011, "Capture failed");
+      }
+    };
+    socket.onmessage = (event) => {
+      const request = parseControlMessage(event.data);
+      if (typeof request === "string") {
+        send({ type: "protocolError", message: request });
+        return;
+      }
+      try {
+        const result = control(request, client);
+        send({ type: "result", id: request.id, result });
+      } catch (error) {
+        send({ ...errorMessage(error), id: request.id });
+      }
+    };
+    socket.onclose = () => {
+      sockets.delete(socket);
+      controller.abort();
+    };
+    return response;
+  };
+}
+
+/**
+ * Serves the frames of a device over WebSocket with `Deno.serve`.
+ * See `websocketHandler` for the protocol.
+ * @param device - The device to capture frames from.
+ * @param options - The address to listen on, the path, stream and encoding of the frames.
+ * @returns The server, which can be shut down with `shutdown()`.
+ */
+export function serveWebSocket(
+  device: Device,
+  options: WebSocketServeOptions = {},
+): Deno.HttpServer<Deno.NetAddr> {
+  const {
+    port = 8080,
+    hostname = "127.0.0.1",
+    signal,
+    onListen,
+    ...handlerOptions
+  } = options;
+  return Deno.serve(
+    { port, hostname, signal, onListen },
+    websocketHandler(device, handlerOptions),
+  );
+}
+
+/**
+ * Encodes a frame as a binary message of the protocol.
+ */
+async function encodeFrame(
+  frame: Frame,
+  encoding: FrameEncoding,
+  quality: number,
+): Promise<Uint8Array> {
+  const payload = encoding === "jpeg"
+    ? encodeJPEG(frame, { quality })
+    : encoding === "png"
+    ? await encodePNG(frame, { compressionLevel: 1 })
+    : packFrame(frame);
+  const { sequence, timestamp, width, height } = frame;
+  // JPEG drops the alpha channel
+  const pixelFormat = encoding === "jpeg" && frame.pixelFormat === "rgba32"
+    ? "rgb24"
+    : frame.pixelFormat;
+  return encodeFrameMessage(
+    { sequence, timestamp, width, height, pixelFormat, encoding },
+    payload,
+  );
+}
+
+/**
+ * Parses a control message, which must at least carry an integer ID to reply to.
+ * @returns The message, or why it can't be answered.
+ */
+function parseControlMessage(data: unknown): ControlMessage | string {
+  if (typeof data !== "string") return "Control messages must be JSON text";
+  let request: unknown;
+  try {
+    request = JSON.parse(data);
+  } catch (error) {
+    return `Invalid JSON: ${(error as Error).message}`;
+  }
+  if (
+    typeof request !== "object" || request === null ||
+    !Number.isInteger((request as { id?: unknown }).id)
+  ) {
+    return "Control messages must be objects with an integer id";
+  }
+  return request as ControlMessage;
+}
+
+function errorMessage(error: unknown): ServerMessage & { type: "error" } {
+  if (error instanceof CameraError) {
+    const { name, message, code, operation } = error;
+    return { type: "error", name, message, code, operation };
+  }
+  if (error instanceof Error) {
+    return { type: "error", name: error.name, message: error.message };
+  }
+  return { type: "error", name: "Error", message: String(error) };
+}
@@ -0,0 +1,249 @@
+/**
+ * Provides Web Streams adapters to pipe frames into encoders, files and HTTP responses.
+ *
+ * @example
+ * ```ts
+ * import { Camera, frameData, limitFrameRate } from "jsr:@sigma/camera";
+ *
+ * using cam = new Camera();
+ * const device = cam.devices().at(0);
+ * if (!device) throw new Error("no device found");
+ * using stream = device.stream({ width: 640, height: 480 });
+ * if (!stream) throw new Error("no stream found");
+ *
+ * // record 10 seconds of raw RGB24 at 5 fps
+ * const file = await Deno.create("frames.rgb");
+ * await stream.readable
+ *   .pipeThrough(limitFrameRate(5))
+ *   .pipeThrough(frameData())
+ *   .pipeTo(file.writable, { signal: AbortSignal.timeout(10_000) })
+ *   .catch((e) => {
+ *     if (e.name !== "TimeoutError") throw e;
+ *   });
+ * ```
+ *
+ * @module
+ */
+
+import type { Frame } from "./frame.ts";
+
+/**
+ * Creates a ReadableStream pulling frames from an async iterator.
+ * Frames are only requested when the consumer reads, so a slow consumer makes
+ * the driver drop frames instead of queueing them. Cancelling the stream
+ * returns the iterator.
+ * @param frames - The iterator yielding frames, which must own their data.
+ * @param controller - Aborted on cancel, to interrupt a pending wait of the iterator.
+ * @returns A ReadableStream of frames.
+ */
+export function readableFromFrames(
+  frames: AsyncIterator<Frame, void>,
+  controller?: AbortController,
+): ReadableStream<Frame> {
+  return new ReadableStream<Frame>({
+    async pull(controller) {
+      const { value, done } = await frames.next();
+      if (done) controller.close();
+      else controller.enqueue(value);
+    },
+    async cancel(reason) {
+      controller?.abort(reason);
+      await frames.return?.();
+    },
+  }, { highWaterMark: 0 });
+}
+
+/**
+ * Creates a TransformStream emitting the pixel data of each frame, packed without row padding.
+ * @returns A TransformStream from frames to bytes.
+ */
+export function frameData(): TransformStream<Frame, Uint8Array> {
+  return new TransformStream({
+    transform(frame, controller) {
+      controller.enqueue(packFrame(frame));
+    },
+  });
+}
+
+/**
+ * Retrieves the pixel data of a frame without row padding.
+ * @param frame - The frame to pack.
+ * @returns The data of the frame itself if it is packed already, a packed copy otherwise.
+ */
+export function packFrame(frame: Frame): Uint8Array {
+  const rowBytes = frame.width * frame.channels;
+  if (frame.stride === rowBytes) {
+    return frame.data.subarray(0, rowBytes * frame.height);
+  }
+  const packed = new Uint8Array(rowBytes * frame.height);
+  for (let y = 0; y < frame.height; y++) {
+    const start = y * frame.stride;
+    packed.set(frame.data.subarray(start, start + rowBytes), y * rowBytes);
+  }
+  return packed;
+}
+
+/**
+ * Creates a TransformStream applying a function to each frame, one at a time.
+ * @param fn - The function to apply, possibly asynchronous.
+ * @returns A TransformStream from frames to the results of the function.
+ */
+export function mapFrames<T>(
+  fn: (frame: Frame) => T | Promise<T>,
+): TransformStream<Frame, T> {
+  return new TransformStream({
+    async transform(frame, controller) {
+      controller.enqueue(await fn(frame));
+    },
+  });
+}
+
+/**
+ * Creates a TransformStream dropping frames to stay under a frame rate, based on their timestamps.
+ * @param maxFps - The maximum number of frames per second to let through.
+ * @returns A TransformStream of frames.
+ */
+export function limitFrameRate(maxFps: number): TransformStream<Frame, Frame> {
+  if (!(maxFps > 0)) {
+    throw new RangeError(`maxFps must be positive, got ${maxFps}`);
+  }
+  const interval = 1000 / maxFps;
+  let next = -Infinity;
+  return new TransformStream({
+    transform(frame, controller) {
+      if (frame.timestamp < next) return;
+      // stay on the ideal schedule unless we fell a whole interval behind it,
+      // so that jitter doesn't drag the rate below maxFps
+      next = (frame.timestamp >= next + interval ? frame.timestamp : next) +
+        interval;
+      controller.enqueue(frame);
+    },
+  });
+}
+
+/**
+ * Represents one loop over a source shared by any number of subscribers.
+ * The source only runs while someone is subscribed. Each subscriber receives
+ * the values produced after it subscribed, skipping those produced while it
+ * was busy, so a slow subscriber never holds back the others.
+ */
+export class Broadcast<T> {
+  #source: (signal: AbortSignal) => AsyncIterable<T>;
+  #subscribers = 0;
+  #run?: BroadcastRun<T>;
+  // the previous run, which must end before the source is iterated again
+  #stopping: Promise<void> = Promise.resolve();
+
+  /**
+   * Creates a broadcast.
+   * @param source - Creates the iterable to loop over, which must stop when the signal is aborted.
+   */
+  constructor(source: (signal: AbortSignal) => AsyncIterable<T>) {
+    this.#source = source;
+  }
+
+  /**
+   * Retrieves the number of active subscribers.
+   * @returns The number of subscribers.
+   */
+  get subscribers(): number {
+    return this.#subscribers;
+  }
+
+  /**
+   * Subscribes to the values of the source, starting it if needed.
+   * The generator ends when the source does, and throws if the source fails.
+   * @param signal - Aborts the pending wait, the generator then throws the abort reason.
+   * @returns An async generator of the values produced from now on.
+   */
+  async *subscribe(signal?: AbortSignal): AsyncGenerator<T, void> {
+    signal?.throwIfAborted();
+    const run = this.#run ?? this.#start();
+    let seen = run.version;
+    this.#subscribers++;
+    try {
+      while (true) {
+        while (run.version === seen) {
+          if (run.ended) {
+            if (run.ended.failed) throw run.ended.error;
+            return;
+          }
+          await abortable(run.changed.promise, signal);
+        }
+        seen = run.version;
+        yield run.latest!;
+      }
+    } finally {
+      if (--this.#subscribers === 0 && this.#run === run) {
+        this.#run = undefined;
+        run.controller.abort();
+      }
+    }
+  }
+
+  #start(): BroadcastRun<T> {
+    const run: BroadcastRun<T> = {
+      controller: new AbortController(),
+      version: 0,
+      changed: Promise.withResolvers(),
+    };
+    const notify = () => {
+      const { resolve } = run.changed;
+      run.changed = Promise.withResolvers();
+      resolve();
+    };
+    const { signal } = run.controller;
+    this.#run = run;
+    this.#stopping = this.#stopping.then(async () => {
+      try {
+        for await (const value of this.#source(signal)) {
+          if (signal.aborted) break;
+          run.latest = value;
+          run.version++;
+          notify();
+        }
+        run.ended = { failed: false };
+      } catch (error) {
+        run.ended = { failed: true, error };
+      } finally {
+        if (this.#run === run) this.#run = undefined;
+        notify();
+      }
+    });
+    return run;
+  }
+}
+
+/**
+ * Represents one iteration of the source of a broadcast.
+ */
+interface BroadcastRun<T> {
+  controller: AbortController;
+  latest?: T;
+  /** Incremented with each value, telling subscribers whether they saw the latest one. */
+  version: number;
+  /** Resolved when a value is produced or the run ends. */
+  changed: PromiseWithResolvers<void>;
+  ended?: { failed: boolean; error?: unknown };
+}
+
+/**
+ * Settles like a promise, or rejects with the abort reason as soon as a signal is aborted.
+ * @param promise - The promise to wait for.
+ * @param signal - The signal aborting the wait.
+ * @returns A promise settling first.
+ */
+export function abortable<T>(
+  promise: Promise<T>,
+  signal?: AbortSignal,
+): Promise<T> {
+  if (!signal) return promise;
+  return new Promise<T>((resolve, reject) => {
+    const onAbort = () => reject(signal.reason);
+    signal.throwIfAborted();
+    signal.addEventListener("abort", onAbort, { once: true });
+    promise.then(resolve, reject).finally(() =>
+      signal.removeEventListener("abort", onAbort)
+    );
+  });
+}
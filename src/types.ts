@@ -10,10 +10,19 @@ export interface FormatInfo {
   fourcc: string;
   /** Frames per second (FPS) of the video stream. */
   fps: number;
-  /** Bits per pixel (BPP) of the video stream. */
+  /** Bits per pixel (BPP) of the native video format, checked against its fourcc when a stream opens, 0 if unknown. Frames are always delivered as RGB24. */
   bpp: number;
 }
 
+/**
+ * Represents the layout of the pixel data of a frame.
+ *
+ * - `rgb24`: packed 8-bit red, green and blue samples
+ * - `rgba32`: packed 8-bit red, green, blue and alpha samples
+ * - `gray8`: 8-bit luminance samples
+ */
+export type PixelFormat = "rgb24" | "rgba32" | "gray8";
+
 /**
  * Enumerates the properties for camera capabilities.
  */
@@ -34,6 +43,18 @@ export enum CapPropertyID {
   Last = 14,
 }
 
+/**
+ * Represents the range of values supported by a camera property.
+ */
+export interface PropertyLimits {
+  /** The minimum value of the property. */
+  min: number;
+  /** The maximum value of the property. */
+  max: number;
+  /** The default value of the property. */
+  default: number;
+}
+
 /**
  * Enumerates the levels of logging.
  */
@@ -59,8 +80,10 @@ export enum LogLevel {
 export interface DeviceInfo {
   /** The name of the camera device. */
   name: string;
-  /** The unique identifier of the camera device. */
+  /** The enumeration index of the camera device. */
   id: number;
+  /** The stable identifier of the camera device, persistent across replugs, if the driver reports one. */
+  uniqueId?: string;
   /** Array of supported formats along with their identifiers. */
   formats: FormatInfoWithId[];
 }
@@ -72,3 +95,69 @@ export type FormatInfoWithId = FormatInfo & {
   /** The identifier of the format. */
   id: number;
 };
+
+/**
+ * Represents a query used to find a specific camera device.
+ */
+export type DeviceQuery =
+  | {
+    /** The stable identifier of the camera device. */
+    uniqueId: string;
+  }
+  | {
+    /** The name of the camera device. */
+    name: string;
+  }
+  | {
+    /** A function returning true for the wanted camera device. */
+    predicate: (info: DeviceInfo) => boolean;
+  };
+
+/**
+ * Represents how a stream reconnects after its device stopped delivering frames.
+ *
+ * The delay before attempt `n` (starting at 0) is `min(maxDelay, initialDelay * factor ** n)`.
+ */
+export interface ReconnectPolicy {
+  /** Maximum number of attempts before giving up, unlimited by default. */
+  retries?: number;
+  /** Delay before the first attempt in milliseconds, defaults to 500. */
+  initialDelay?: number;
+  /** Maximum delay between two attempts in milliseconds, defaults to 10000. */
+  maxDelay?: number;
+  /** Multiplier applied to the delay after each failed attempt, defaults to 2. */
+  factor?: number;
+}
+
+/**
+ * Represents what a stream does when the consumer is slower than the camera.
+ *
+ * - `"latest"`: a frame is captured when the consumer asks for it, so it is
+ *   always the freshest one. Frames delivered in between are dropped.
+ * - `{ queue: n }`: every frame is captured as it arrives into a queue of at
+ *   most `n` copies. When the queue is full, the oldest frame is discarded.
+ * - `"block"`: frames are captured in order into a queue of a few copies, and
+ *   capture pauses while it is full instead of discarding any of them. The
+ *   frames the device delivers meanwhile are dropped by the driver and counted
+ *   in `droppedFrames`, so memory stays bounded.
+ */
+export type DropPolicy = "latest" | "block" | { queue: number };
+
+/**
+ * Represents the options of a stream.
+ */
+export interface StreamOptions {
+  /**
+   * Maximum time to wait for a frame in milliseconds before the stream is
+   * considered stalled. Unbounded by default, 5000 when `reconnect` is set.
+   */
+  frameTimeout?: number;
+  /**
+   * Whether to reopen the same device and format when the stream stalls or
+   * fails, restoring the properties applied through `Stream.properties`.
+   * `true` uses the default policy.
+   */
+  reconnect?: boolean | ReconnectPolicy;
+  /** What to do with frames the consumer is too slow to take, defaults to `"latest"`. */
+  dropPolicy?: DropPolicy;
+}
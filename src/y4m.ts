@@ -0,0 +1,504 @@
+/**
+ * Provides a writer and reader of YUV4MPEG2 (Y4M) video files for frames.
+ *
+ * Y4M stores raw 8-bit Y'CbCr frames after a one-line text header, which
+ * makes it a lossless (apart from the color conversion) recording format
+ * that ffmpeg and most video tools read and write.
+ *
+ * @example
+ * ```ts
+ * import { Camera, Y4MReader, Y4MWriter } from "jsr:@sigma/camera";
+ *
+ * using cam = new Camera();
+ * const device = cam.devices().at(0);
+ * if (!device) throw new Error("no device found");
+ * const format = device.selectFormat({ width: 640, height: 480 }).format;
+ * // keep frames in order, pausing the capture rather than skipping queued ones
+ * using stream = device.stream(format, { dropPolicy: "block" });
+ * if (!stream) throw new Error("no stream found");
+ *
+ * // record 10 seconds, then `ffmpeg -i capture.y4m capture.mp4`
+ * const file = await Deno.create("capture.y4m");
+ * await stream.readable
+ *   .pipeTo(new Y4MWriter(file.writable, format), {
+ *     signal: AbortSignal.timeout(10_000),
+ *   })
+ *   .catch((e) => {
+ *     if (e.name !== "TimeoutError") throw e;
+ *   });
+ *
+ * const reader = new Y4MReader((await Deno.open("capture.y4m")).readable);
+ * console.log(await reader.header);
+ * for await (const frame of reader) console.log(frame.sequence);
+ * ```
+ *
+ * @module
+ */
+
+import { Frame } from "./frame.ts";
+import type { FormatInfo } from "./types.ts";
+
+/**
+ * Represents the chroma subsampling of a Y4M file.
+ *
+ * - `420jpeg`, `420paldv`, `420mpeg2`, `420`: one chroma sample per 2x2 pixels, differing by their siting
+ * - `422`: one chroma sample per 2x1 pixels
+ * - `444`: one chroma sample per pixel
+ * - `mono`: luma only
+ */
+export type Y4MColorspace =
+  | "420jpeg"
+  | "420paldv"
+  | "420mpeg2"
+  | "420"
+  | "422"
+  | "444"
+  | "mono";
+
+/**
+ * Represents the stream header of a Y4M file.
+ */
+export interface Y4MHeader {
+  /** The width of the frames in pixels. */
+  width: number;
+  /** The height of the frames in pixels. */
+  height: number;
+  /** The frame rate as a fraction, `[numerator, denominator]`. */
+  frameRate: [number, number];
+  /** The frame rate in frames per second. */
+  fps: number;
+  /** The chroma subsampling of the frames. */
+  colorspace: Y4MColorspace;
+  /** Whether samples use the full 0-255 range instead of the 16-235 video range. */
+  fullRange: boolean;
+}
+
+/**
+ * Represents the options of a Y4M writer. A `FormatInfo` can be passed as is.
+ */
+export interface Y4MWriterOptions
+  extends Pick<FormatInfo, "width" | "height" | "fps"> {
+  /** The chroma subsampling of the file, defaults to `420jpeg`. */
+  colorspace?: "420jpeg" | "444";
+}
+
+const ENCODER = new TextEncoder();
+const DECODER = new TextDecoder();
+
+/** Longest header or frame line accepted, to bail out on files that aren't Y4M. */
+export const MAX_Y4M_LINE_LENGTH = 4096;
+
+/** Horizontal and vertical chroma subsampling factors, 0 for no chroma. */
+const SUBSAMPLING: Record<Y4MColorspace, [number, number]> = {
+  "420jpeg": [2, 2],
+  "420paldv": [2, 2],
+  "420mpeg2": [2, 2],
+  "420": [2, 2],
+  "422": [2, 1],
+  "444": [1, 1],
+  "mono": [0, 0],
+};
+
+/**
+ * Represents a WritableStream of frames encoding them as a Y4M file.
+ *
+ * Frames are converted from RGB to BT.601 video range Y'CbCr, the chroma of
+ * `420jpeg` files being averaged over each 2x2 square. The header is written
+ * along with the first frame, and all frames must have the configured size.
+ */
+export class Y4MWriter extends WritableStream<Frame> {
+  #written: { frames: number };
+
+  /**
+   * Constructs an instance of the Y4MWriter class.
+   * @param sink - The stream receiving the file contents, closed along with the writer.
+   * @param options - The size, frame rate and colorspace of the file.
+   */
+  constructor(sink: WritableStream<Uint8Array>, options: Y4MWriterOptions) {
+    const { width, height, fps, colorspace = "420jpeg" } = options;
+    if (
+      !(Number.isInteger(width) && width > 0) ||
+      !(Number.isInteger(height) && height > 0)
+    ) {
+      throw new RangeError(`Invalid Y4M frame size ${width}x${height}`);
+    }
+    if (!(fps > 0)) throw new RangeError(`Invalid Y4M frame rate ${fps}`);
+    if (colorspace !== "420jpeg" && colorspace !== "444") {
+      throw new RangeError(`Unsupported Y4M colorspace ${colorspace}`);
+    }
+    const [num, den] = frameRateFraction(fps);
+    const header = ENCODER.encode(
+      `YUV4MPEG2 W${width} H${height} F${num}:${den} Ip A1:1 C${colorspace} XCOLORRANGE=LIMITED\n`,
+    );
+    const frameHeader = ENCODER.encode("FRAME\n");
+    const writer = sink.getWriter();
+    const written = { frames: 0 };
+    super({
+      async write(frame) {
+        if (frame.width !== width || frame.height !== height) {
+          throw new RangeError(
+            `Frame is ${frame.width}x${frame.height}, expected ${width}x${height}`,
+          );
+        }
+        // convert first, a borrowed frame is only valid until the next capture
+        const planes = toYCbCr(frame, SUBSAMPLING[colorspace][0]);
+        if (written.frames === 0) await writer.write(header);
+        await writer.write(frameHeader);
+        await writer.write(planes);
+        written.frames++;
+      },
+      async close() {
+        if (written.frames === 0) await writer.write(header);
+        await writer.close();
+      },
+      async abort(reason) {
+        await writer.abort(reason);
+      },
+    });
+    this.#written = written;
+  }
+
+  /**
+   * Retrieves the number of frames written so far.
+   * @returns The number of frames.
+   */
+  get frames(): number {
+    return this.#written.frames;
+  }
+}
+
+/**
+ * Represents a ReadableStream of the frames of a Y4M file.
+ *
+ * Frames are converted to `rgb24`, or `gray8` for `mono` files. Their
+ * sequence is their index in the file, and their timestamp the time elapsed
+ * since the first frame at the frame rate of the header.
+ */
+export class Y4MReader extends ReadableStream<Frame> {
+  #header: Promise<Y4MHeader>;
+
+  /**
+   * Constructs an instance of the Y4MReader class.
+   * @param source - The stream of the file contents, cancelled along with the reader.
+   */
+  constructor(source: ReadableStream<Uint8Array>) {
+    const bytes = new ByteReader(source.getReader());
+    const parsed = Promise.withResolvers<Y4MHeader>();
+    // the header is also reported through the stream when it is invalid
+    parsed.promise.catch(() => {});
+    let header: Y4MHeader;
+    let frameSize = 0;
+    let sequence = 0;
+    super({
+      async start() {
+        try {
+          const line = await bytes.readLine();
+          if (line === undefined) throw new Error("Empty Y4M file");
+          header = parseY4MHeader(line);
+          frameSize = y4mFrameSize(header);
+          parsed.resolve(header);
+        } catch (error) {
+          parsed.reject(error);
+          await bytes.cancel(error);
+          throw error;
+        }
+      },
+      async pull(controller) {
+        const line = await bytes.readLine();
+        if (line === undefined) {
+          controller.close();
+          return;
+        }
+        if (line !== "FRAME" && !line.startsWith("FRAME ")) {
+          throw new Error(`Invalid Y4M frame header ${JSON.stringify(line)}`);
+        }
+        const planes = await bytes.read(frameSize);
+        if (!planes) throw new Error("Truncated Y4M frame");
+        controller.enqueue(decodeY4MFrame(planes, header, sequence++));
+      },
+      async cancel(reason) {
+        await bytes.cancel(reason);
+      },
+    }, { highWaterMark: 0 });
+    this.#header = parsed.promise;
+  }
+
+  /**
+   * Retrieves the stream header of the file.
+   * @returns A promise resolving to the header once it was read.
+   */
+  get header(): Promise<Y4MHeader> {
+    return this.#header;
+  }
+}
+
+/**
+ * Approximates a frame rate with a fraction, as NTSC rates like 29.97 are
+ * multiples of 1000/1001.
+ * @param fps - The frame rate in frames per second.
+ * @returns The numerator and denominator of the frame rate.
+ */
+export function frameRateFraction(fps: number): [number, number] {
+  if (Number.isInteger(fps)) return [fps, 1];
+  const ntsc = fps * 1.001;
+  if (Math.abs(ntsc - Math.round(ntsc)) < 1e-3) {
+    return [Math.round(ntsc) * 1000, 1001];
+  }
+  return [Math.round(fps * 1000), 1000];
+}
+
+/**
+ * Parses the stream header line of a Y4M file.
+ * @param line - The header line without its line feed.
+ * @returns The stream header.
+ * @throws {Error} If the line isn't a valid 8-bit Y4M header.
+ */
+export function parseY4MHeader(line: string): Y4MHeader {
+  const [magic, ...params] = line.split(" ");
+  if (magic !== "YUV4MPEG2") throw new Error("Invalid Y4M signature");
+  let width = 0;
+  let height = 0;
+  let frameRate: [number, number] = [30, 1];
+  let colorspace = "420jpeg";
+  let fullRange = false;
+  for (const param of params) {
+    const value = param.slice(1);
+    switch (param[0]) {
+      case "W":
+        width = Number.parseInt(value);
+        break;
+      case "H":
+        height = Number.parseInt(value);
+        break;
+      case "F": {
+        const [num, den] = value.split(":").map(Number);
+        if (num > 0 && den > 0) frameRate = [num, den];
+        break;
+      }
+      case "C":
+        colorspace = value;
+        break;
+      case "X":
+        if (value === "COLORRANGE=FULL") fullRange = true;
+        break;
+    }
+  }
+  if (!(width > 0 && height > 0)) {
+    throw new Error(`Invalid Y4M frame size ${width}x${height}`);
+  }
+  if (!(colorspace in SUBSAMPLING)) {
+    throw new Error(`Unsupported Y4M colorspace ${colorspace}`);
+  }
+  return {
+    width,
+    height,
+    frameRate,
+    fps: frameRate[0] / frameRate[1],
+    colorspace: colorspace as Y4MColorspace,
+    fullRange,
+  };
+}
+
+/**
+ * Converts a frame to planar BT.601 video range Y'CbCr.
+ * @param frame - The frame to convert.
+ * @param subsampling - 2 to average the chroma over 2x2 squares, 1 to keep it for every pixel.
+ * @returns The Y, Cb and Cr planes.
+ */
+function toYCbCr(frame: Frame, subsampling: number): Uint8Array {
+  const { width, height, channels, stride } = frame;
+  const data = frame.data;
+  const chromaWidth = Math.ceil(width / subsampling);
+  const chromaHeight = Math.ceil(height / subsampling);
+  const chromaSize = chromaWidth * chromaHeight;
+  const planes = new Uint8Array(width * height + 2 * chromaSize);
+  const cbOffset = width * height;
+  const crOffset = cbOffset + chromaSize;
+  const sums = new Float64Array(2 * chromaSize);
+  const counts = new Uint8Array(chromaSize);
+
+  for (let y = 0; y < height; y++) {
+    const chromaRow = Math.floor(y / subsampling) * chromaWidth;
+    for (let x = 0; x < width; x++) {
+      const o = y * stride + x * channels;
+      const r = data[o];
+      const g = channels === 1 ? r : data[o + 1];
+      const b = channels === 1 ? r : data[o + 2];
+      planes[y * width + x] = Math.round(
+        16 + (65.481 * r + 128.553 * g + 24.966 * b) / 255,
+      );
+      const c = chromaRow + Math.floor(x / subsampling);
+      sums[2 * c] += (-37.797 * r - 74.203 * g + 112 * b) / 255;
+      sums[2 * c + 1] += (112 * r - 93.786 * g - 18.214 * b) / 255;
+      counts[c]++;
+    }
+  }
+  for (let c = 0; c < chromaSize; c++) {
+    planes[cbOffset + c] = Math.round(128 + sums[2 * c] / counts[c]);
+    planes[crOffset + c] = Math.round(128 + sums[2 * c + 1] / counts[c]);
+  }
+  return planes;
+}
+
+/**
+ * Computes the size of the samples of a frame, without its `FRAME` line.
+ * @param header - The stream header.
+ * @returns The size in bytes.
+ */
+export function y4mFrameSize(header: Y4MHeader): number {
+  const [sx, sy] = SUBSAMPLING[header.colorspace];
+  const { width, height } = header;
+  return width * height +
+    (sx ? 2 * Math.ceil(width / sx) * Math.ceil(height / sy) : 0);
+}
+
+/**
+ * Converts the planar Y'CbCr samples of a frame to a frame.
+ * @param planes - The samples following a `FRAME` line.
+ * @param header - The stream header.
+ * @param sequence - The index of the frame in the file.
+ * @returns An `rgb24` frame, or `gray8` for `mono` files.
+ */
+export function decodeY4MFrame(
+  planes: Uint8Array,
+  header: Y4MHeader,
+  sequence: number,
+): Frame {
+  const { width, height, colorspace, fullRange } = header;
+  const timestamp = sequence * 1000 / header.fps;
+  const size = width * height;
+  // video range samples are scaled from 16-235 (240 for chroma) to 0-255
+  const yScale = fullRange ? 1 : 255 / 219;
+  const cScale = fullRange ? 1 : 255 / 224;
+  const yOffset = fullRange ? 0 : 16;
+
+  if (colorspace === "mono") {
+    const data = new Uint8Array(size);
+    for (let i = 0; i < size; i++) {
+      data[i] = clamp((planes[i] - yOffset) * yScale);
+    }
+    return new Frame({
+      data,
+      width,
+      height,
+      pixelFormat: "gray8",
+      timestamp,
+      sequence,
+    });
+  }
+
+  const [sx, sy] = SUBSAMPLING[colorspace];
+  const chromaWidth = Math.ceil(width / sx);
+  const chromaSize = chromaWidth * Math.ceil(height / sy);
+  const data = new Uint8Array(size * 3);
+  for (let y = 0; y < height; y++) {
+    const chromaRow = Math.floor(y / sy) * chromaWidth;
+    for (let x = 0; x < width; x++) {
+      const l = (planes[y * width + x] - yOffset) * yScale;
+      const c = chromaRow + Math.floor(x / sx);
+      const cb = (planes[size + c] - 128) * cScale;
+      const cr = (planes[size + chromaSize + c] - 128) * cScale;
+      const i = (y * width + x) * 3;
+      data[i] = clamp(l + 1.402 * cr);
+      data[i + 1] = clamp(l - 0.344136 * cb - 0.714136 * cr);
+      data[i + 2] = clamp(l + 1.772 * cb);
+    }
+  }
+  return new Frame({
+    data,
+    width,
+    height,
+    pixelFormat: "rgb24",
+    timestamp,
+    sequence,
+  });
+}
+
+function clamp(value: number): number {
+  return value < 0 ? 0 : value > 255 ? 255 : Math.round(value);
+}
+
+/**
+ * Represents a reader of lines and fixed size blocks over a byte stream.
+ */
+class ByteReader {
+  #reader: ReadableStreamDefaultReader<Uint8Array>;
+  #chunks: Uint8Array[] = [];
+  #buffered = 0;
+  #done = false;
+
+  constructor(reader: ReadableStreamDefaultReader<Uint8Array>) {
+    this.#reader = reader;
+  }
+
+  /**
+   * Reads up to the next line feed.
+   * @returns The line without its line feed, undefined at the end of the stream.
+   */
+  async readLine(): Promise<string | undefined> {
+    while (true) {
+      const bytes = this.#peek();
+      const end = bytes.indexOf(0x0A);
+      if (end >= 0) {
+        this.#consume(end + 1);
+        return DECODER.decode(bytes.subarray(0, end));
+      }
+      if (bytes.length > MAX_Y4M_LINE_LENGTH) {
+        throw new Error("Y4M header line is too long");
+      }
+      if (!await this.#fill()) {
+        if (bytes.length === 0) return;
+        throw new Error("Truncated Y4M header");
+      }
+    }
+  }
+
+  /**
+   * Reads a number of bytes.
+   * @returns The bytes, undefined if the stream ended before.
+   */
+  async read(size: number): Promise<Uint8Array | undefined> {
+    while (this.#buffered < size) {
+      if (!await this.#fill()) return;
+    }
+    const bytes = this.#peek().subarray(0, size);
+    this.#consume(size);
+    return bytes;
+  }
+
+  async cancel(reason?: unknown) {
+    await this.#reader.cancel(reason);
+  }
+
+  async #fill(): Promise<boolean> {
+    if (this.#done) return false;
+    const { value, done } = await this.#reader.read();
+    if (done) {
+      this.#done = true;
+      return false;
+    }
+    this.#chunks.push(value);
+    this.#buffered += value.length;
+    return true;
+  }
+
+  /** Joins the buffered chunks. */
+  #peek(): Uint8Array {
+    if (this.#chunks.length > 1) {
+      const joined = new Uint8Array(this.#buffered);
+      let offset = 0;
+      for (const chunk of this.#chunks) {
+        joined.set(chunk, offset);
+        offset += chunk.length;
+      }
+      this.#chunks = [joined];
+    }
+    return this.#chunks[0] ?? new Uint8Array(0);
+  }
+
+  #consume(size: number) {
+    const rest = this.#peek().subarray(size);
+    this.#chunks = rest.length ? [rest] : [];
+    this.#buffered = rest.length;
+  }
+}
@@ -0,0 +1,242 @@
+import {
+  assertEquals,
+  assertGreater,
+  assertLessOrEqual,
+  assertRejects,
+} from "@std/assert";
+import { AVIWriter, type SeekableWriter } from "../src/avi.ts";
+import { Frame } from "../src/frame.ts";
+import { decodeJPEG } from "./jpeg_decoder.ts";
+
+const WIDTH = 32;
+const HEIGHT = 24;
+const DECODER = new TextDecoder();
+
+/** A file kept in memory, which can fail one write at a given offset. */
+class MemoryFile implements SeekableWriter {
+  bytes = new Uint8Array(0);
+  #position = 0;
+  #failAt?: number;
+
+  constructor(failAt?: number) {
+    this.#failAt = failAt;
+  }
+
+  write(bytes: Uint8Array): Promise<number> {
+    let length = bytes.length;
+    const failAt = this.#failAt;
+    if (failAt !== undefined && this.#position + length > failAt) {
+      this.#failAt = undefined;
+      length = failAt - this.#position;
+    }
+    const end = this.#position + length;
+    if (end > this.bytes.length) {
+      const grown = new Uint8Array(end);
+      grown.set(this.bytes);
+      this.bytes = grown;
+    }
+    this.bytes.set(bytes.subarray(0, length), this.#position);
+    this.#position = end;
+    if (length < bytes.length) {
+      return Promise.reject(new Error("No space left on device"));
+    }
+    return Promise.resolve(length);
+  }
+
+  seek(offset: number, whence: Deno.SeekMode): Promise<number> {
+    if (whence === Deno.SeekMode.Current) offset += this.#position;
+    if (whence === Deno.SeekMode.End) offset += this.bytes.length;
+    this.#position = offset;
+    return Promise.resolve(offset);
+  }
+}
+
+interface Chunk {
+  fourcc: string;
+  /** The list type of `RIFF` and `LIST` chunks. */
+  type?: string;
+  offset: number;
+  size: number;
+  data: Uint8Array;
+}
+
+/** Reads the chunks between two offsets. */
+function readChunks(bytes: Uint8Array, start: number, end: number): Chunk[] {
+  const view = new DataView(bytes.buffer, bytes.byteOffset);
+  const fourcc = (at: number) => DECODER.decode(bytes.subarray(at, at + 4));
+  const chunks = [];
+  for (let offset = start; offset < end;) {
+    const size = view.getUint32(offset + 4, true);
+    const id = fourcc(offset);
+    const list = id === "RIFF" || id === "LIST";
+    chunks.push({
+      fourcc: id,
+      type: list ? fourcc(offset + 8) : undefined,
+      offset,
+      size,
+      data: bytes.subarray(offset + 8, offset + 8 + size),
+    });
+    offset += 8 + size + (size & 1);
+    assertLessOrEqual(offset, end, `${id} overflows its list`);
+  }
+  return chunks;
+}
+
+/** Reads the chunks of a `RIFF` or `LIST` chunk. */
+function children(bytes: Uint8Array, list: Chunk): Chunk[] {
+  return readChunks(bytes, list.offset + 12, list.offset + 8 + list.size);
+}
+
+function find(chunks: Chunk[], fourcc: string, type?: string): Chunk {
+  const chunk = chunks.find((c) => c.fourcc === fourcc && c.type === type);
+  if (!chunk) throw new Error(`Missing ${fourcc} ${type ?? ""}`);
+  return chunk;
+}
+
+function testFrame(sequence: number, width = WIDTH): Frame {
+  const data = new Uint8Array(width * HEIGHT * 3);
+  for (let i = 0; i < data.length; i++) data[i] = (i * 7 + sequence * 13) & 255;
+  return new Frame({
+    data,
+    width,
+    height: HEIGHT,
+    pixelFormat: "rgb24",
+    timestamp: sequence * 40,
+    sequence,
+  });
+}
+
+async function record(
+  file: MemoryFile,
+  count: number,
+  maxSegmentSize?: number,
+) {
+  const writer = new AVIWriter(file, {
+    width: WIDTH,
+    height: HEIGHT,
+    fps: 25,
+    maxSegmentSize,
+  });
+  const frames = Array.from({ length: count }, (_, i) => testFrame(i));
+  await ReadableStream.from(frames).pipeTo(writer);
+  assertEquals(writer.frames, count);
+}
+
+/**
+ * Checks the structure of a recording.
+ * @returns The number of frames of each segment.
+ */
+function checkAVI(bytes: Uint8Array): number[] {
+  const view = new DataView(bytes.buffer, bytes.byteOffset);
+  const riffs = readChunks(bytes, 0, bytes.length);
+  assertEquals(riffs.map((riff) => [riff.fourcc, riff.type]), [
+    ["RIFF", "AVI "],
+    ...riffs.slice(1).map(() => ["RIFF", "AVIX"]),
+  ]);
+
+  const first = children(bytes, riffs[0]);
+  const hdrl = children(bytes, find(first, "LIST", "hdrl"));
+  const strl = children(bytes, find(hdrl, "LIST", "strl"));
+  const odml = children(bytes, find(hdrl, "LIST", "odml"));
+  const indx = find(strl, "indx").offset + 8;
+  assertEquals(view.getUint32(indx + 4, true), riffs.length);
+
+  const segmentFrames = [];
+  let total = 0;
+  for (const [i, riff] of riffs.entries()) {
+    const chunks = children(bytes, riff);
+    const movi = find(chunks, "LIST", "movi");
+    const frames = children(bytes, movi);
+    const ix00 = frames.pop()!;
+    assertEquals(ix00.fourcc, "ix00");
+    for (const frame of frames) {
+      assertEquals(frame.fourcc, "00dc");
+      const image = decodeJPEG(frame.data);
+      assertEquals([image.width, image.height], [WIDTH, HEIGHT]);
+    }
+
+    // super index entry: offset, size and frames of the standard index
+    const entry = indx + 24 + 16 * i;
+    assertEquals(Number(view.getBigUint64(entry, true)), ix00.offset);
+    assertEquals(view.getUint32(entry + 8, true), ix00.size + 8);
+    assertEquals(view.getUint32(entry + 12, true), frames.length);
+
+    // standard index: base offset, then offsets of the frame data
+    const ix = ix00.offset + 8;
+    assertEquals(view.getUint32(ix + 4, true), frames.length);
+    assertEquals(Number(view.getBigUint64(ix + 12, true)), movi.offset);
+    for (const [j, frame] of frames.entries()) {
+      const at = ix + 24 + 8 * j;
+      assertEquals(view.getUint32(at, true), frame.offset + 8 - movi.offset);
+      assertEquals(view.getUint32(at + 4, true), frame.size);
+    }
+
+    // legacy index, in the first segment only
+    const idx1 = chunks.find((chunk) => chunk.fourcc === "idx1");
+    assertEquals(idx1 !== undefined, i === 0);
+    if (idx1) {
+      assertEquals(idx1.size, 16 * frames.length);
+      for (const [j, frame] of frames.entries()) {
+        const at = idx1.offset + 8 + 16 * j;
+        assertEquals(DECODER.decode(bytes.subarray(at, at + 4)), "00dc");
+        const offset = frame.offset - movi.offset - 8;
+        assertEquals(view.getUint32(at + 8, true), offset);
+        assertEquals(view.getUint32(at + 12, true), frame.size);
+      }
+      // avih counts the frames of the first segment
+      const avih = find(hdrl, "avih");
+      assertEquals(view.getUint32(avih.offset + 24, true), frames.length);
+    }
+    segmentFrames.push(frames.length);
+    total += frames.length;
+  }
+
+  // strh and dmlh count all the frames
+  const strh = find(strl, "strh");
+  assertEquals(view.getUint32(strh.offset + 8 + 32, true), total);
+  const dmlh = find(odml, "dmlh");
+  assertEquals(view.getUint32(dmlh.offset + 8, true), total);
+  return segmentFrames;
+}
+
+Deno.test("AVIWriter writes an indexed RIFF file", async () => {
+  const file = new MemoryFile();
+  await record(file, 5);
+  assertEquals(checkAVI(file.bytes), [5]);
+});
+
+Deno.test("AVIWriter starts AVIX segments past maxSegmentSize", async () => {
+  const file = new MemoryFile();
+  await record(file, 30, 8000);
+  const segments = checkAVI(file.bytes);
+  assertGreater(segments.length, 1);
+  assertEquals(segments.reduce((sum, frames) => sum + frames, 0), 30);
+  for (const riff of readChunks(file.bytes, 0, file.bytes.length)) {
+    assertLessOrEqual(riff.size + 8, 8000);
+  }
+});
+
+Deno.test("AVIWriter finishes the file when a write fails", async () => {
+  const file = new MemoryFile();
+  const writer = new AVIWriter(file, { width: WIDTH, height: HEIGHT, fps: 25 });
+  const frames = [testFrame(0), testFrame(1), testFrame(2, WIDTH + 2)];
+  await assertRejects(
+    () => ReadableStream.from(frames).pipeTo(writer),
+    RangeError,
+    "expected 32x24",
+  );
+  assertEquals(checkAVI(file.bytes), [2]);
+
+  // a frame partly written before an error is left out of the indexes
+  const full = new MemoryFile();
+  await record(full, 3);
+  const movi = find(
+    children(full.bytes, readChunks(full.bytes, 0, full.bytes.length)[0]),
+    "LIST",
+    "movi",
+  );
+  const third = children(full.bytes, movi)[2];
+  const failing = new MemoryFile(third.offset + 20);
+  await assertRejects(() => record(failing, 3), Error, "No space left");
+  assertEquals(checkAVI(failing.bytes), [2]);
+});
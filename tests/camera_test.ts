@@ -0,0 +1,112 @@
+import { assert, assertEquals, assertThrows } from "@std/assert";
+import { Camera } from "../src/camera.ts";
+import {
+  DeviceNotFoundError,
+  FormatNotSupportedError,
+} from "../src/errors.ts";
+import { MockBackend } from "../src/mock.ts";
+
+const FORMAT = { width: 64, height: 48, fourcc: "RGB3", fps: 30, bpp: 24 };
+
+/**
+ * A mock backend counting its open streams, and refusing to be used once its
+ * context is released.
+ */
+class TrackedBackend extends MockBackend {
+  released = false;
+  openStreams = 0;
+
+  openStream(deviceId: number, deviceFormatId: number): number {
+    if (this.released) throw new Error("The context was released");
+    this.openStreams++;
+    return super.openStream(deviceId, deviceFormatId);
+  }
+
+  closeStream(id: number) {
+    this.openStreams--;
+    super.closeStream(id);
+  }
+
+  releaseContext() {
+    this.released = true;
+    super.releaseContext();
+  }
+}
+
+function mockDevices() {
+  return {
+    devices: [
+      { uniqueId: "usb:1", formats: [FORMAT] },
+      { uniqueId: "usb:2", formats: [FORMAT] },
+    ],
+  };
+}
+
+Deno.test("Camera.refresh keeps previous devices usable", async () => {
+  const backend = new TrackedBackend(mockDevices());
+  using cam = new Camera(backend);
+  const [first, second] = cam.devices();
+  backend.plug({ uniqueId: "usb:3", formats: [FORMAT] });
+  backend.unplug("usb:2");
+  const { added, removed } = cam.refresh();
+  assertEquals(added.map((device) => device.uniqueId()), ["usb:3"]);
+  assertEquals(removed, [second]);
+  assert(backend.released, "the unused context was kept");
+
+  using stream = first.stream(first.formats()[0]);
+  assert(stream);
+  assertEquals((await stream.capture()).width, 64);
+  assertThrows(() => second.stream({ width: 64 }), DeviceNotFoundError);
+});
+
+Deno.test("Camera.refresh keeps contexts with open streams", () => {
+  const backend = new TrackedBackend(mockDevices());
+  using cam = new Camera(backend);
+  const device = cam.devices()[0];
+  const stream = device.stream({ width: 64 })!;
+  backend.plug({ formats: [FORMAT] });
+  cam.refresh();
+  assert(!backend.released, "the context of an open stream was released");
+  stream[Symbol.dispose]();
+  assert(backend.released, "the retired context was kept");
+  using reopened = device.stream({ width: 64 });
+  assert(reopened);
+});
+
+Deno.test("Device.stream checks the format before opening it", () => {
+  const backend = new TrackedBackend({
+    devices: [{
+      formats: [
+        { ...FORMAT, fourcc: "YUYV", bpp: 24 },
+        { ...FORMAT, fourcc: "YUYV", bpp: 0 },
+      ],
+    }],
+  });
+  using cam = new Camera(backend);
+  const [device] = cam.devices();
+  assertThrows(
+    () => device.stream(device.formats()[0]),
+    FormatNotSupportedError,
+    "YUYV has 16 bits per pixel, not 24",
+  );
+  assertEquals(backend.openStreams, 0);
+
+  // drivers report 0 bits per pixel when they don't know
+  using stream = device.stream(device.formats()[1]);
+  assert(stream);
+  assertEquals(backend.openStreams, 1);
+});
+
+Deno.test("Device.stream checks the drop policy before opening", () => {
+  const backend = new TrackedBackend(mockDevices());
+  using cam = new Camera(backend);
+  const [device] = cam.devices();
+  for (const queue of [0, -1, 1.5]) {
+    assertThrows(
+      () => device.stream({ width: 64 }, { dropPolicy: { queue } }),
+      RangeError,
+      "Queue size must be a positive integer",
+    );
+  }
+  assertEquals(backend.openStreams, 0);
+});
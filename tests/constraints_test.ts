@@ -0,0 +1,23 @@
+import { assertEquals, assertThrows } from "@std/assert";
+import { selectFormat } from "../src/constraints.ts";
+import { FormatNotSupportedError } from "../src/errors.ts";
+
+const FORMATS = [
+  { id: 0, width: 640, height: 480, fourcc: "YUYV", fps: 30, bpp: 16 },
+  { id: 1, width: 640, height: 480, fourcc: "Y16 ", fps: 30, bpp: 16 },
+  { id: 2, width: 1280, height: 720, fourcc: "MJPG", fps: 30, bpp: 0 },
+];
+
+Deno.test("selectFormat ignores the padding of fourccs", () => {
+  for (const fourcc of ["Y16", "Y16 ", ["Y16"], { exact: "Y16" }]) {
+    assertEquals(selectFormat(FORMATS, { fourcc }).format.id, 1);
+  }
+  const { format, distance } = selectFormat(FORMATS, {
+    fourcc: { exact: ["Y16", "YUYV"], ideal: ["Y16", "YUYV"] },
+  });
+  assertEquals([format.id, distance], [1, 0]);
+  assertThrows(
+    () => selectFormat(FORMATS, { fourcc: { exact: "Y8" } }),
+    FormatNotSupportedError,
+  );
+});
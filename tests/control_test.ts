@@ -0,0 +1,104 @@
+import { assertEquals } from "@std/assert";
+import { Camera } from "../src/camera.ts";
+import { controlHandler } from "../src/control.ts";
+import { CameraError, PropertyNotSupportedError } from "../src/errors.ts";
+import { MockBackend } from "../src/mock.ts";
+import { CapPropertyID } from "../src/types.ts";
+
+const FORMAT = { width: 64, height: 48, fourcc: "RGB3", fps: 30, bpp: 24 };
+
+/** A mock backend whose exposure is unsupported and focus fails to read. */
+class FlakyBackend extends MockBackend {
+  getPropertyLimits(id: number, propertyId: CapPropertyID) {
+    if (propertyId === CapPropertyID.Exposure) {
+      throw new PropertyNotSupportedError("No exposure", {
+        operation: "getPropertyLimits",
+      });
+    }
+    if (propertyId === CapPropertyID.Focus) {
+      throw new CameraError("Focus failed", {
+        code: 1,
+        operation: "getPropertyLimits",
+      });
+    }
+    return super.getPropertyLimits(id, propertyId);
+  }
+}
+
+/** Creates a handler over two mock devices, the second without unique id. */
+function startControl(backend = new MockBackend(options())) {
+  const cam = new Camera(backend);
+  const closed = new AbortController();
+  const handler = controlHandler(cam, { signal: closed.signal });
+  return {
+    request(method: string, path: string, body?: unknown) {
+      const init = body === undefined ? {} : { body: JSON.stringify(body) };
+      return handler(
+        new Request(`http://localhost${path}`, { method, ...init }),
+      );
+    },
+    [Symbol.dispose]() {
+      closed.abort();
+      cam[Symbol.dispose]();
+    },
+  };
+}
+
+function options() {
+  return {
+    devices: [
+      { name: "front", uniqueId: "usb:1", formats: [FORMAT] },
+      { name: "back", uniqueId: "", formats: [FORMAT] },
+    ],
+  };
+}
+
+Deno.test("controlHandler answers 400 to malformed URL encodings", async () => {
+  using control = startControl();
+  const response = await control.request("GET", "/devices/%E0%A4%A");
+  assertEquals(response.status, 400);
+  assertEquals((await response.json()).error.name, "HttpError");
+});
+
+Deno.test("controlHandler addresses devices without unique id", async () => {
+  using control = startControl();
+  const byUniqueId = await control.request("GET", "/devices/usb%3A1");
+  assertEquals((await byUniqueId.json()).name, "front");
+  const byId = await control.request("GET", "/devices/1");
+  assertEquals((await byId.json()).name, "back");
+  // devices with a unique id are only addressed by it
+  const shadowed = await control.request("GET", "/devices/0");
+  assertEquals(shadowed.status, 404);
+  await shadowed.body?.cancel();
+
+  const opened = await control.request("POST", "/devices/1/streams", {
+    format: { width: 64 },
+  });
+  assertEquals(opened.status, 201);
+  assertEquals((await opened.json()).device.name, "back");
+});
+
+Deno.test("controlHandler lists the properties that fail to read", async () => {
+  using control = startControl(new FlakyBackend(options()));
+  const opened = await control.request("POST", "/devices/usb%3A1/streams", {
+    format: { width: 64 },
+  });
+  const { id } = await opened.json();
+  const response = await control.request("GET", `/streams/${id}/properties`);
+  assertEquals(response.status, 200);
+  const properties = await response.json();
+  const names = properties.map((property: { name: string }) => property.name);
+  assertEquals(names.includes("exposure"), false);
+  assertEquals(names.includes("gain"), true);
+  const focus = properties.find((p: { name: string }) => p.name === "focus");
+  assertEquals(focus, {
+    name: "focus",
+    id: CapPropertyID.Focus,
+    error: {
+      name: "CameraError",
+      message: "Focus failed",
+      code: 1,
+      operation: "getPropertyLimits",
+    },
+  });
+});
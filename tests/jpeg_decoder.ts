@@ -0,0 +1,303 @@
+/**
+ * A minimal baseline JPEG decoder checking the output of the encoder.
+ *
+ * It decodes sequential Huffman coded images with any sampling factors, and
+ * rejects anything the encoder never writes: progressive or arithmetic coding,
+ * 16-bit quantization tables and restart intervals. It favors being obviously
+ * correct over speed, with a direct IDCT and nearest neighbor chroma
+ * upsampling.
+ *
+ * @module
+ */
+
+/** Natural order index of each coefficient in zigzag order. */
+// deno-fmt-ignore
+const ZIGZAG = [
+  0, 1, 8, 16, 9, 2, 3, 10, 17, 24, 32, 25, 18, 11, 4, 5,
+  12, 19, 26, 33, 40, 48, 41, 34, 27, 20, 13, 6, 7, 14, 21, 28,
+  35, 42, 49, 56, 57, 50, 43, 36, 29, 22, 15, 23, 30, 37, 44, 51,
+  58, 59, 52, 45, 38, 31, 39, 46, 53, 60, 61, 54, 47, 55, 62, 63,
+];
+
+/** `COSINES[x * 8 + u]` is `C(u) / 2 * cos((2x + 1) * u * PI / 16)`. */
+const COSINES = Float64Array.from({ length: 64 }, (_, i) => {
+  const x = i >> 3;
+  const u = i & 7;
+  const c = u === 0 ? Math.SQRT1_2 : 1;
+  return c / 2 * Math.cos((2 * x + 1) * u * Math.PI / 16);
+});
+
+/**
+ * Represents a decoded image, with its samples interleaved.
+ */
+export interface DecodedJPEG {
+  width: number;
+  height: number;
+  /** 1 for grayscale images, 3 for RGB. */
+  channels: number;
+  /** The horizontal and vertical sampling factors of each component. */
+  sampling: [number, number][];
+  data: Uint8Array;
+}
+
+interface Huffman {
+  /** Symbols keyed by the code length in the upper 16 bits and the code. */
+  symbols: Map<number, number>;
+}
+
+interface Component {
+  id: number;
+  h: number;
+  v: number;
+  table: number;
+  dc: number;
+  ac: number;
+  prediction: number;
+  blocksX: number;
+  blocksY: number;
+  samples: Uint8Array;
+}
+
+/**
+ * Decodes a baseline JPEG image.
+ * @param bytes - The JPEG file contents.
+ * @returns The decoded image.
+ * @throws {Error} If the file is malformed or uses an unsupported feature.
+ */
+export function decodeJPEG(bytes: Uint8Array): DecodedJPEG {
+  const quantization: Int32Array[] = [];
+  const huffman: Huffman[] = [];
+  let components: Component[] = [];
+  let width = 0;
+  let height = 0;
+  let offset = 0;
+
+  const u16 = (at: number) => (bytes[at] << 8) | bytes[at + 1];
+  if (u16(0) !== 0xFFD8) throw new Error("Missing SOI marker");
+  offset = 2;
+
+  while (true) {
+    if (bytes[offset] !== 0xFF) throw new Error(`No marker at ${offset}`);
+    const marker = bytes[offset + 1];
+    if (marker === 0xD9) break;
+    const length = u16(offset + 2);
+    const segment = bytes.subarray(offset + 4, offset + 2 + length);
+    offset += 2 + length;
+
+    if (marker === 0xDB) {
+      for (let i = 0; i < segment.length; i += 65) {
+        if (segment[i] >> 4) throw new Error("16-bit quantization table");
+        const table = new Int32Array(64);
+        for (let k = 0; k < 64; k++) table[ZIGZAG[k]] = segment[i + 1 + k];
+        quantization[segment[i] & 15] = table;
+      }
+    } else if (marker === 0xC0) {
+      if (segment[0] !== 8) throw new Error("Not an 8-bit image");
+      height = (segment[1] << 8) | segment[2];
+      width = (segment[3] << 8) | segment[4];
+      components = [];
+      for (let i = 0; i < segment[5]; i++) {
+        const c = segment.subarray(6 + i * 3, 9 + i * 3);
+        components.push({
+          id: c[0],
+          h: c[1] >> 4,
+          v: c[1] & 15,
+          table: c[2],
+          dc: 0,
+          ac: 0,
+          prediction: 0,
+          blocksX: 0,
+          blocksY: 0,
+          samples: new Uint8Array(0),
+        });
+      }
+    } else if (marker === 0xC4) {
+      for (let i = 0; i < segment.length;) {
+        const id = segment[i];
+        const counts = segment.subarray(i + 1, i + 17);
+        let k = i + 17;
+        let code = 0;
+        const symbols = new Map<number, number>();
+        for (let length = 1; length <= 16; length++) {
+          for (let n = 0; n < counts[length - 1]; n++) {
+            symbols.set((length << 16) | code++, segment[k++]);
+          }
+          code <<= 1;
+        }
+        // DC tables first, then AC tables
+        huffman[(id >> 4) * 4 + (id & 15)] = { symbols };
+        i = k;
+      }
+    } else if (marker === 0xDA) {
+      const scanned = [];
+      for (let i = 0; i < segment[0]; i++) {
+        const id = segment[1 + i * 2];
+        const tables = segment[2 + i * 2];
+        const component = components.find((c) => c.id === id);
+        if (!component) throw new Error(`Unknown component ${id}`);
+        component.dc = tables >> 4;
+        component.ac = 4 + (tables & 15);
+        scanned.push(component);
+      }
+      if (scanned.length !== components.length) {
+        throw new Error("Non-interleaved scans are not supported");
+      }
+      offset = decodeScan(bytes, offset, components, quantization, huffman, {
+        width,
+        height,
+      });
+    } else if (marker === 0xDD || (marker >= 0xC1 && marker <= 0xCF)) {
+      throw new Error(`Unsupported marker 0xFF${marker.toString(16)}`);
+    }
+  }
+
+  const channels = components.length === 1 ? 1 : 3;
+  const data = new Uint8Array(width * height * channels);
+  const hMax = Math.max(...components.map((c) => c.h));
+  const vMax = Math.max(...components.map((c) => c.v));
+  const sample = (c: Component, x: number, y: number) =>
+    c.samples[
+      Math.floor(y * c.v / vMax) * c.blocksX * 8 + Math.floor(x * c.h / hMax)
+    ];
+  for (let y = 0; y < height; y++) {
+    for (let x = 0; x < width; x++) {
+      const i = (y * width + x) * channels;
+      const luma = sample(components[0], x, y);
+      if (channels === 1) {
+        data[i] = luma;
+        continue;
+      }
+      const cb = sample(components[1], x, y) - 128;
+      const cr = sample(components[2], x, y) - 128;
+      data[i] = clampByte(luma + 1.402 * cr);
+      data[i + 1] = clampByte(luma - 0.344136 * cb - 0.714136 * cr);
+      data[i + 2] = clampByte(luma + 1.772 * cb);
+    }
+  }
+  return {
+    width,
+    height,
+    channels,
+    sampling: components.map((c) => [c.h, c.v]),
+    data,
+  };
+}
+
+/**
+ * Decodes the entropy coded data of an interleaved scan.
+ * @returns The offset of the marker following the scan.
+ */
+function decodeScan(
+  bytes: Uint8Array,
+  offset: number,
+  components: Component[],
+  quantization: Int32Array[],
+  huffman: Huffman[],
+  size: { width: number; height: number },
+): number {
+  const hMax = Math.max(...components.map((c) => c.h));
+  const vMax = Math.max(...components.map((c) => c.v));
+  const mcusX = Math.ceil(size.width / (8 * hMax));
+  const mcusY = Math.ceil(size.height / (8 * vMax));
+  for (const c of components) {
+    c.blocksX = mcusX * c.h;
+    c.blocksY = mcusY * c.v;
+    c.samples = new Uint8Array(c.blocksX * c.blocksY * 64);
+    c.prediction = 0;
+  }
+
+  let buffer = 0;
+  let count = 0;
+  const bit = () => {
+    if (count === 0) {
+      const byte = bytes[offset++];
+      if (byte === 0xFF) {
+        if (bytes[offset] !== 0) throw new Error("Unexpected marker in scan");
+        offset++;
+      }
+      buffer = byte;
+      count = 8;
+    }
+    return (buffer >> --count) & 1;
+  };
+  const receive = (length: number) => {
+    let value = 0;
+    for (let i = 0; i < length; i++) value = (value << 1) | bit();
+    return value;
+  };
+  const extend = (value: number, length: number) =>
+    value < 1 << (length - 1) ? value - (1 << length) + 1 : value;
+  const decode = (table: Huffman) => {
+    let code = 0;
+    for (let length = 1; length <= 16; length++) {
+      code = (code << 1) | bit();
+      const symbol = table.symbols.get((length << 16) | code);
+      if (symbol !== undefined) return symbol;
+    }
+    throw new Error("Invalid Huffman code");
+  };
+
+  const coefficients = new Float64Array(64);
+  for (let my = 0; my < mcusY; my++) {
+    for (let mx = 0; mx < mcusX; mx++) {
+      for (const c of components) {
+        const table = quantization[c.table];
+        for (let by = 0; by < c.v; by++) {
+          for (let bx = 0; bx < c.h; bx++) {
+            coefficients.fill(0);
+            const dcSize = decode(huffman[c.dc]);
+            if (dcSize > 11) throw new Error(`Invalid DC size ${dcSize}`);
+            c.prediction += dcSize ? extend(receive(dcSize), dcSize) : 0;
+            coefficients[0] = c.prediction * table[0];
+            for (let k = 1; k < 64;) {
+              const symbol = decode(huffman[c.ac]);
+              const run = symbol >> 4;
+              const acSize = symbol & 15;
+              if (acSize === 0) {
+                if (run !== 15) break;
+                k += 16;
+                continue;
+              }
+              k += run;
+              if (k > 63) throw new Error("AC coefficients overflow the block");
+              const z = ZIGZAG[k++];
+              coefficients[z] = extend(receive(acSize), acSize) * table[z];
+            }
+            inverseDCT(
+              coefficients,
+              c.samples,
+              ((my * c.v + by) * 8) * c.blocksX * 8 + (mx * c.h + bx) * 8,
+              c.blocksX * 8,
+            );
+          }
+        }
+      }
+    }
+  }
+  return offset;
+}
+
+/** Computes the inverse DCT of a block, level shifted and clamped. */
+function inverseDCT(
+  coefficients: Float64Array,
+  out: Uint8Array,
+  offset: number,
+  stride: number,
+) {
+  for (let y = 0; y < 8; y++) {
+    for (let x = 0; x < 8; x++) {
+      let sum = 0;
+      for (let v = 0; v < 8; v++) {
+        for (let u = 0; u < 8; u++) {
+          sum += COSINES[x * 8 + u] * COSINES[y * 8 + v] *
+            coefficients[v * 8 + u];
+        }
+      }
+      out[offset + y * stride + x] = clampByte(sum + 128);
+    }
+  }
+}
+
+function clampByte(value: number): number {
+  return Math.max(0, Math.min(255, Math.round(value)));
+}
@@ -0,0 +1,181 @@
+import {
+  assertEquals,
+  assertGreater,
+  assertLess,
+  assertThrows,
+} from "@std/assert";
+import { Frame } from "../src/frame.ts";
+import { type ChromaSubsampling, encodeJPEG } from "../src/jpeg.ts";
+import type { PixelFormat } from "../src/types.ts";
+import { decodeJPEG } from "./jpeg_decoder.ts";
+
+/** Creates a frame of smooth gradients, as most camera images are. */
+function smoothFrame(
+  pixelFormat: PixelFormat,
+  width = 97,
+  height = 61,
+): Frame {
+  const channels = pixelFormat === "gray8" ? 1 : 3;
+  const data = new Uint8Array(width * height * channels);
+  for (let y = 0; y < height; y++) {
+    for (let x = 0; x < width; x++) {
+      const i = (y * width + x) * channels;
+      const wave = 128 + 90 * Math.sin(x / 9) * Math.cos(y / 7);
+      data[i] = wave;
+      if (channels === 1) continue;
+      data[i + 1] = x * 255 / width;
+      data[i + 2] = y * 255 / height;
+    }
+  }
+  return new Frame({
+    data,
+    width,
+    height,
+    pixelFormat,
+    timestamp: 0,
+    sequence: 0,
+  });
+}
+
+/**
+ * Creates a frame of black and white pixels, random or in vertical bars
+ * splitting each block in halves, which maximize the DCT coefficients.
+ */
+function contrastFrame(pattern: "noise" | "bars"): Frame {
+  const width = 64;
+  const height = 48;
+  let seed = 1;
+  const data = Uint8Array.from({ length: width * height * 3 }, (_, i) => {
+    seed = (Math.imul(seed, 1103515245) + 12345) >>> 0;
+    const white = pattern === "noise"
+      ? (seed >>> 16) & 1
+      : (Math.floor(i / 3) % width) & 4;
+    return white ? 255 : 0;
+  });
+  return new Frame({
+    data,
+    width,
+    height,
+    pixelFormat: "rgb24",
+    timestamp: 0,
+    sequence: 0,
+  });
+}
+
+/** Computes the peak signal to noise ratio of two images in dB. */
+function psnr(expected: Uint8Array, actual: Uint8Array): number {
+  assertEquals(actual.length, expected.length);
+  let sum = 0;
+  for (let i = 0; i < expected.length; i++) {
+    sum += (expected[i] - actual[i]) ** 2;
+  }
+  return 10 * Math.log10(255 ** 2 / (sum / expected.length));
+}
+
+/** Computes the BT.601 luma of an RGB frame, as JPEG defines it. */
+function luma(frame: Frame): Uint8Array {
+  return Uint8Array.from(
+    { length: frame.width * frame.height },
+    (_, i) =>
+      Math.round(
+        0.299 * frame.data[i * 3] + 0.587 * frame.data[i * 3 + 1] +
+          0.114 * frame.data[i * 3 + 2],
+      ),
+  );
+}
+
+Deno.test("encodeJPEG writes 4:2:0 color images", () => {
+  const frame = smoothFrame("rgb24");
+  const decoded = decodeJPEG(encodeJPEG(frame, { quality: 90 }));
+  assertEquals([decoded.width, decoded.height], [97, 61]);
+  assertEquals(decoded.sampling, [[2, 2], [1, 1], [1, 1]]);
+  assertGreater(psnr(frame.data, decoded.data), 35);
+});
+
+Deno.test("encodeJPEG writes 4:4:4 color images", () => {
+  const frame = smoothFrame("rgb24");
+  const bytes = encodeJPEG(frame, { quality: 90, subsampling: "4:4:4" });
+  const decoded = decodeJPEG(bytes);
+  assertEquals(decoded.sampling, [[1, 1], [1, 1], [1, 1]]);
+  assertGreater(psnr(frame.data, decoded.data), 42);
+  // full resolution chroma is closer to the original than subsampled chroma
+  const subsampled = decodeJPEG(encodeJPEG(frame, { quality: 90 }));
+  assertGreater(
+    psnr(frame.data, decoded.data),
+    psnr(frame.data, subsampled.data),
+  );
+});
+
+Deno.test("encodeJPEG writes grayscale images", () => {
+  const gray = smoothFrame("gray8");
+  const decoded = decodeJPEG(encodeJPEG(gray, { quality: 90 }));
+  assertEquals([decoded.channels, decoded.sampling], [1, [[1, 1]]]);
+  assertGreater(psnr(gray.data, decoded.data), 40);
+
+  const color = smoothFrame("rgb24");
+  const bytes = encodeJPEG(color, { quality: 90, grayscale: true });
+  const converted = decodeJPEG(bytes);
+  assertEquals(converted.channels, 1);
+  assertGreater(psnr(luma(color), converted.data), 40);
+});
+
+Deno.test("encodeJPEG ignores the alpha channel", () => {
+  const rgb = smoothFrame("rgb24", 16, 16);
+  const data = new Uint8Array(16 * 16 * 4);
+  for (let i = 0; i < 16 * 16; i++) {
+    data.set(rgb.data.subarray(i * 3, i * 3 + 3), i * 4);
+    data[i * 4 + 3] = i;
+  }
+  const rgba = new Frame({
+    data,
+    width: 16,
+    height: 16,
+    pixelFormat: "rgba32",
+    timestamp: 0,
+    sequence: 0,
+  });
+  assertEquals(encodeJPEG(rgba), encodeJPEG(rgb));
+});
+
+Deno.test("encodeJPEG trades quality for size", () => {
+  const frame = smoothFrame("rgb24");
+  const sizes = [10, 50, 90, 100].map((quality) =>
+    encodeJPEG(frame, { quality }).length
+  );
+  for (let i = 1; i < sizes.length; i++) assertLess(sizes[i - 1], sizes[i]);
+  const decoded = decodeJPEG(encodeJPEG(frame, { quality: 10 }));
+  assertGreater(psnr(frame.data, decoded.data), 22);
+});
+
+Deno.test("encodeJPEG codes the largest coefficients of quality 100", () => {
+  for (const pattern of ["noise", "bars"] as const) {
+    const frame = contrastFrame(pattern);
+    // 4:2:0 can't keep the colors of single pixels, but must stay decodable
+    decodeJPEG(encodeJPEG(frame, { quality: 100 }));
+    const bytes = encodeJPEG(frame, { quality: 100, subsampling: "4:4:4" });
+    assertGreater(psnr(frame.data, decodeJPEG(bytes).data), 45);
+    const gray = encodeJPEG(frame, { quality: 100, grayscale: true });
+    assertGreater(psnr(luma(frame), decodeJPEG(gray).data), 45);
+  }
+});
+
+Deno.test("encodeJPEG encodes frames smaller than a block", () => {
+  const frame = smoothFrame("rgb24", 1, 1);
+  const decoded = decodeJPEG(encodeJPEG(frame, { quality: 100 }));
+  assertEquals([decoded.width, decoded.height], [1, 1]);
+  assertGreater(psnr(frame.data, decoded.data), 30);
+});
+
+Deno.test("encodeJPEG rejects invalid options", () => {
+  const frame = smoothFrame("rgb24", 8, 8);
+  for (const quality of [0, 101, 50.5]) {
+    assertThrows(() => encodeJPEG(frame, { quality }), RangeError);
+  }
+  assertThrows(
+    () =>
+      encodeJPEG(frame, {
+        subsampling: "4:2:2" as unknown as ChromaSubsampling,
+      }),
+    RangeError,
+  );
+});
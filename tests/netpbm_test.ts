@@ -0,0 +1,219 @@
+import { assertEquals, assertRejects, assertThrows } from "@std/assert";
+import { Frame } from "../src/frame.ts";
+import {
+  decodeNetpbm,
+  encodeNetpbm,
+  netpbmDecoder,
+  netpbmEncoder,
+} from "../src/netpbm.ts";
+import type { PixelFormat } from "../src/types.ts";
+
+const ENCODER = new TextEncoder();
+
+/** Creates a frame filled with a deterministic pattern covering 0..255. */
+function testFrame(pixelFormat: PixelFormat, width = 7, height = 5): Frame {
+  const channels = pixelFormat === "gray8" ? 1 : 3;
+  const data = new Uint8Array(width * height * channels);
+  for (let i = 0; i < data.length; i++) data[i] = (i * 37 + 11) & 0xFF;
+  data[0] = 0;
+  data[1] = 255;
+  return new Frame({
+    data,
+    width,
+    height,
+    pixelFormat,
+    timestamp: 0,
+    sequence: 0,
+  });
+}
+
+/** Splits bytes into chunks of pseudo-random sizes from 1 to `max`. */
+function split(bytes: Uint8Array, seed: number, max: number): Uint8Array[] {
+  const chunks = [];
+  for (let offset = 0; offset < bytes.length;) {
+    seed = (Math.imul(seed, 1103515245) + 12345) >>> 0;
+    const size = 1 + (seed >>> 16) % max;
+    chunks.push(bytes.slice(offset, offset + size));
+    offset += size;
+  }
+  return chunks;
+}
+
+async function decodeChunks(chunks: Uint8Array[]): Promise<Frame[]> {
+  const frames = [];
+  const readable = ReadableStream.from(chunks).pipeThrough(netpbmDecoder());
+  for await (const frame of readable) frames.push(frame);
+  return frames;
+}
+
+function concat(images: Uint8Array[]): Uint8Array {
+  const bytes = new Uint8Array(images.reduce((sum, i) => sum + i.length, 0));
+  let offset = 0;
+  for (const image of images) {
+    bytes.set(image, offset);
+    offset += image.length;
+  }
+  return bytes;
+}
+
+for (const plain of [false, true]) {
+  for (const pixelFormat of ["gray8", "rgb24"] as const) {
+    const magic = pixelFormat === "gray8"
+      ? (plain ? "P2" : "P5")
+      : (plain ? "P3" : "P6");
+    Deno.test(`netpbm round trips ${magic} images`, () => {
+      const frame = testFrame(pixelFormat);
+      const bytes = encodeNetpbm(frame, { plain });
+      assertEquals(new TextDecoder().decode(bytes.subarray(0, 2)), magic);
+      const decoded = decodeNetpbm(bytes);
+      assertEquals(decoded.pixelFormat, pixelFormat);
+      assertEquals([decoded.width, decoded.height], [7, 5]);
+      assertEquals(decoded.data, frame.data);
+    });
+
+    Deno.test(`netpbm round trips 16-bit ${magic} images`, () => {
+      const frame = testFrame(pixelFormat);
+      const bytes = encodeNetpbm(frame, { plain, maxval: 65535 });
+      if (!plain) {
+        const header = `${magic}\n7 5\n65535\n`.length;
+        assertEquals(bytes.length, header + frame.data.length * 2);
+        // 255 scales to 65535, stored big-endian
+        const samples = [...bytes.subarray(header, header + 4)];
+        assertEquals(samples, [0, 0, 255, 255]);
+      }
+      assertEquals(decodeNetpbm(bytes).data, frame.data);
+    });
+  }
+}
+
+Deno.test("netpbm scales samples from and to any maxval", () => {
+  const bytes = ENCODER.encode("P2\n# a comment\n3 1\n1000\n0 500 1000\n");
+  assertEquals([...decodeNetpbm(bytes).data], [0, 128, 255]);
+
+  const frame = decodeNetpbm(ENCODER.encode("P5 2 1 15\n\x00\x0F"));
+  assertEquals([...frame.data], [0, 255]);
+  const bilevel = encodeNetpbm(testFrame("gray8"), { maxval: 1 });
+  assertEquals(
+    decodeNetpbm(bilevel).data.every((v) => v === 0 || v === 255),
+    true,
+  );
+});
+
+Deno.test("netpbm drops the alpha channel of rgba32 frames", () => {
+  const data = new Uint8Array([1, 2, 3, 4, 5, 6, 7, 8]);
+  const frame = new Frame({
+    data,
+    width: 2,
+    height: 1,
+    pixelFormat: "rgba32",
+    timestamp: 0,
+    sequence: 0,
+  });
+  const decoded = decodeNetpbm(encodeNetpbm(frame));
+  assertEquals([...decoded.data], [1, 2, 3, 5, 6, 7]);
+});
+
+Deno.test("netpbm rejects invalid maxvals when encoding", () => {
+  for (const maxval of [0, 65536, 1.5]) {
+    assertThrows(
+      () => encodeNetpbm(testFrame("gray8"), { maxval }),
+      RangeError,
+    );
+  }
+});
+
+Deno.test("netpbm rejects malformed headers", () => {
+  const cases: [string, string][] = [
+    ["P7\n1 1\n255\n\x00", "Unsupported netpbm type"],
+    ["P61 1\n255\n\x00", "Unsupported netpbm type"],
+    ["P5\nx 1\n255\n\x00", "Invalid netpbm width"],
+    ["P5\n1 -1\n255\n\x00", "Invalid netpbm height"],
+    ["P5\n0 1\n255\n", "Invalid netpbm size"],
+    ["P5\n1 1\n70000\n\x00\x00", "Invalid netpbm maxval"],
+    ["P5\n1 1\n0\n\x00", "Invalid netpbm maxval"],
+    ["P5\n1 1\n255x\x00", "Invalid netpbm maxval"],
+    ["P2\n2 1\n255\n1 a\n", "Invalid netpbm sample"],
+    ["P2\n2 1\n100\n1 101\n", "exceeds maxval"],
+    ["P5\n1 1\n15\n\x10", "exceeds maxval"],
+  ];
+  for (const [image, message] of cases) {
+    assertThrows(() => decodeNetpbm(ENCODER.encode(image)), Error, message);
+  }
+});
+
+Deno.test("netpbm rejects truncated images", () => {
+  const cases = [
+    "P6",
+    "P6\n",
+    "P6\n4 4",
+    "P6\n4 4\n255",
+    "P6\n4 4\n# comment",
+    "P5\n2 2\n255\n\x00\x00\x00",
+    "P5\n2 1\n65535\n\x00\x00\x00",
+    "P2\n2 2\n255\n1 2 3",
+  ];
+  for (const image of cases) {
+    assertThrows(
+      () => decodeNetpbm(ENCODER.encode(image)),
+      Error,
+      "Truncated",
+    );
+  }
+});
+
+Deno.test("netpbmDecoder splits images at any chunk boundary", async () => {
+  const frames = [
+    testFrame("rgb24"),
+    testFrame("gray8", 3, 4),
+    testFrame("rgb24", 40, 30),
+    testFrame("gray8", 1, 1),
+  ];
+  const bytes = concat([
+    encodeNetpbm(frames[0]),
+    ENCODER.encode("# between images\n"),
+    encodeNetpbm(frames[1], { plain: true }),
+    encodeNetpbm(frames[2], { plain: true, maxval: 1023 }),
+    encodeNetpbm(frames[3], { maxval: 65535 }),
+    ENCODER.encode("\n"),
+  ]);
+  for (const [seed, max] of [[1, 1], [2, 3], [3, 17], [4, 256], [5, 4096]]) {
+    const decoded = await decodeChunks(split(bytes, seed, max));
+    assertEquals(decoded.length, frames.length);
+    for (const [i, frame] of decoded.entries()) {
+      assertEquals(frame.pixelFormat, frames[i].pixelFormat);
+      assertEquals([frame.width, frame.height], [
+        frames[i].width,
+        frames[i].height,
+      ]);
+      assertEquals(frame.data, frames[i].data);
+    }
+  }
+});
+
+Deno.test("netpbmDecoder decodes the output of netpbmEncoder", async () => {
+  const frames = [testFrame("rgb24"), testFrame("gray8")];
+  const readable = ReadableStream.from(frames)
+    .pipeThrough(netpbmEncoder({ plain: true }))
+    .pipeThrough(netpbmDecoder());
+  const decoded = [];
+  for await (const frame of readable) decoded.push(frame.data);
+  assertEquals(decoded, frames.map((frame) => frame.data));
+});
+
+Deno.test("netpbmDecoder rejects a truncated last image", async () => {
+  for (const image of [
+    encodeNetpbm(testFrame("rgb24")).subarray(0, 40),
+    encodeNetpbm(testFrame("gray8"), { plain: true }).subarray(0, 30),
+  ]) {
+    const bytes = concat([encodeNetpbm(testFrame("gray8")), image]);
+    const chunks = split(bytes, 6, 9);
+    await assertRejects(() => decodeChunks(chunks), Error, "Truncated");
+  }
+});
+
+Deno.test("netpbmDecoder reads large plain images quickly", async () => {
+  const frame = testFrame("rgb24", 640, 480);
+  const chunks = split(encodeNetpbm(frame, { plain: true }), 7, 64);
+  const [decoded] = await decodeChunks(chunks);
+  assertEquals(decoded.data, frame.data);
+});
@@ -0,0 +1,285 @@
+import { assertEquals, assertRejects } from "@std/assert";
+import { Frame } from "../src/frame.ts";
+import { decodePNG, encodePNG } from "../src/png.ts";
+import type { PixelFormat } from "../src/types.ts";
+
+const SIGNATURE = [137, 80, 78, 71, 13, 10, 26, 10];
+const ENCODER = new TextEncoder();
+
+/** Origin and spacing of the 7 Adam7 passes, as `[x0, y0, dx, dy]`. */
+const ADAM7 = [
+  [0, 0, 8, 8],
+  [4, 0, 8, 8],
+  [0, 4, 4, 8],
+  [2, 0, 4, 4],
+  [0, 2, 2, 4],
+  [1, 0, 2, 2],
+  [0, 1, 1, 2],
+];
+
+/** Creates a frame filled with a deterministic pattern covering 0..255. */
+function testFrame(pixelFormat: PixelFormat, width = 13, height = 7): Frame {
+  const channels = { gray8: 1, rgb24: 3, rgba32: 4 }[pixelFormat];
+  const data = new Uint8Array(width * height * channels);
+  for (let i = 0; i < data.length; i++) data[i] = (i * 37 + 11) & 0xFF;
+  return new Frame({
+    data,
+    width,
+    height,
+    pixelFormat,
+    timestamp: 0,
+    sequence: 0,
+  });
+}
+
+function crc32(bytes: Uint8Array): number {
+  let crc = 0xFFFFFFFF;
+  for (const byte of bytes) {
+    crc ^= byte;
+    for (let k = 0; k < 8; k++) {
+      crc = crc & 1 ? 0xEDB88320 ^ (crc >>> 1) : crc >>> 1;
+    }
+  }
+  return (crc ^ 0xFFFFFFFF) >>> 0;
+}
+
+function chunk(type: string, data: Uint8Array | number[]): number[] {
+  const body = [...ENCODER.encode(type), ...data];
+  const crc = crc32(new Uint8Array(body));
+  const length = data.length;
+  return [
+    length >>> 24,
+    (length >>> 16) & 0xFF,
+    (length >>> 8) & 0xFF,
+    length & 0xFF,
+    ...body,
+    crc >>> 24,
+    (crc >>> 16) & 0xFF,
+    (crc >>> 8) & 0xFF,
+    crc & 0xFF,
+  ];
+}
+
+async function deflate(bytes: number[]): Promise<Uint8Array> {
+  return new Uint8Array(
+    await new Response(
+      new Blob([new Uint8Array(bytes)]).stream().pipeThrough(
+        new CompressionStream("deflate"),
+      ),
+    ).arrayBuffer(),
+  );
+}
+
+interface Image {
+  width: number;
+  height: number;
+  bitDepth: number;
+  colorType: number;
+  interlace?: boolean;
+  /** The rows of packed samples, without their filter byte. */
+  rows: number[][];
+  palette?: number[];
+  transparency?: number[];
+}
+
+/** Builds a PNG file from unfiltered rows, split into two IDAT chunks. */
+async function buildPNG(image: Image): Promise<Uint8Array> {
+  const { width, height } = image;
+  const header = [
+    ...[width, height].flatMap((n) => [n >>> 24, n >>> 16, n >>> 8, n]),
+    image.bitDepth,
+    image.colorType,
+    0,
+    0,
+    image.interlace ? 1 : 0,
+  ].map((b) => b & 0xFF);
+  const idat = await deflate(image.rows.flatMap((row) => [0, ...row]));
+  const half = idat.length >> 1;
+  return new Uint8Array([
+    ...SIGNATURE,
+    ...chunk("IHDR", header),
+    ...(image.palette ? chunk("PLTE", image.palette) : []),
+    ...(image.transparency ? chunk("tRNS", image.transparency) : []),
+    ...chunk("IDAT", idat.subarray(0, half)),
+    ...chunk("IDAT", idat.subarray(half)),
+    ...chunk("IEND", []),
+  ]);
+}
+
+for (const pixelFormat of ["gray8", "rgb24", "rgba32"] as const) {
+  for (const compressionLevel of [0, 1, 6]) {
+    const name = `PNG round trip: ${pixelFormat}, level ${compressionLevel}`;
+    Deno.test(name, async () => {
+      const frame = testFrame(pixelFormat);
+      const decoded = await decodePNG(
+        await encodePNG(frame, { compressionLevel }),
+      );
+      assertEquals(decoded.pixelFormat, pixelFormat);
+      assertEquals([decoded.width, decoded.height], [13, 7]);
+      assertEquals(decoded.data, frame.data);
+    });
+  }
+}
+
+Deno.test("PNG levels above 1 only enable filtering", async () => {
+  const frame = testFrame("rgb24");
+  const filtered = await encodePNG(frame, { compressionLevel: 2 });
+  assertEquals(await encodePNG(frame, { compressionLevel: 9 }), filtered);
+  assertEquals(await encodePNG(frame), filtered);
+  await assertRejects(
+    () => encodePNG(frame, { compressionLevel: 10 }),
+    RangeError,
+    "from 0 to 9",
+  );
+});
+
+Deno.test("decodePNG reads Adam7 interlaced images", async () => {
+  // 5x3 leaves the third pass empty
+  const frame = testFrame("gray8", 5, 3);
+  const rows = [];
+  for (const [x0, y0, dx, dy] of ADAM7) {
+    for (let y = y0; y < 3; y += dy) {
+      const row = [];
+      for (let x = x0; x < 5; x += dx) row.push(frame.data[y * 5 + x]);
+      if (row.length) rows.push(row);
+    }
+  }
+  const decoded = await decodePNG(
+    await buildPNG({
+      width: 5,
+      height: 3,
+      bitDepth: 8,
+      colorType: 0,
+      interlace: true,
+      rows,
+    }),
+  );
+  assertEquals(decoded.pixelFormat, "gray8");
+  assertEquals(decoded.data, frame.data);
+});
+
+Deno.test("decodePNG scales sub-8-bit grayscale samples", async () => {
+  const decoded = await decodePNG(
+    await buildPNG({
+      width: 10,
+      height: 2,
+      bitDepth: 1,
+      colorType: 0,
+      rows: [[0b10110000, 0b01000000], [0b01001111, 0b11000000]],
+    }),
+  );
+  assertEquals(
+    [...decoded.data].map((v) => v / 255),
+    [1, 0, 1, 1, 0, 0, 0, 0, 0, 1, 0, 1, 0, 0, 1, 1, 1, 1, 1, 1],
+  );
+
+  const twoBits = await decodePNG(
+    await buildPNG({
+      width: 4,
+      height: 1,
+      bitDepth: 2,
+      colorType: 0,
+      rows: [[0b00011011]],
+    }),
+  );
+  assertEquals([...twoBits.data], [0, 85, 170, 255]);
+});
+
+Deno.test("decodePNG applies the palette and its transparency", async () => {
+  const palette = [255, 0, 0, 0, 255, 0, 0, 0, 255];
+  const rows = [[0b00011000]];
+  const opaque = await decodePNG(
+    await buildPNG({
+      width: 3,
+      height: 1,
+      bitDepth: 2,
+      colorType: 3,
+      rows,
+      palette,
+    }),
+  );
+  assertEquals(opaque.pixelFormat, "rgb24");
+  assertEquals([...opaque.data], palette);
+
+  // entries past the end of tRNS are opaque
+  const transparent = await decodePNG(
+    await buildPNG({
+      width: 3,
+      height: 1,
+      bitDepth: 2,
+      colorType: 3,
+      rows,
+      palette,
+      transparency: [0, 128],
+    }),
+  );
+  assertEquals(transparent.pixelFormat, "rgba32");
+  assertEquals(
+    [...transparent.data],
+    [255, 0, 0, 0, 0, 255, 0, 128, 0, 0, 255, 255],
+  );
+
+  await assertRejects(
+    async () =>
+      decodePNG(
+        await buildPNG({
+          width: 1,
+          height: 1,
+          bitDepth: 8,
+          colorType: 3,
+          rows: [[3]],
+          palette,
+        }),
+      ),
+    Error,
+    "Invalid PNG palette index 3",
+  );
+});
+
+Deno.test("decodePNG keeps the high byte of 16-bit samples", async () => {
+  const rgb = await decodePNG(
+    await buildPNG({
+      width: 2,
+      height: 1,
+      bitDepth: 16,
+      colorType: 2,
+      rows: [[0x12, 0x34, 0xAB, 0xCD, 0xFF, 0xFF, 0, 1, 0x80, 0, 0x7F, 0xFF]],
+    }),
+  );
+  assertEquals(rgb.pixelFormat, "rgb24");
+  assertEquals([...rgb.data], [0x12, 0xAB, 0xFF, 0, 0x80, 0x7F]);
+
+  const grayAlpha = await decodePNG(
+    await buildPNG({
+      width: 1,
+      height: 1,
+      bitDepth: 16,
+      colorType: 4,
+      rows: [[0x40, 0x01, 0xC0, 0x02]],
+    }),
+  );
+  assertEquals(grayAlpha.pixelFormat, "rgba32");
+  assertEquals([...grayAlpha.data], [0x40, 0x40, 0x40, 0xC0]);
+});
+
+Deno.test("decodePNG rejects corrupted files", async () => {
+  const png = await encodePNG(testFrame("rgb24"));
+  const corrupted = png.slice();
+  // first byte of the IDAT data, after the signature and IHDR
+  corrupted[8 + 25 + 8] ^= 1;
+  await assertRejects(
+    () => decodePNG(corrupted),
+    Error,
+    "Corrupted PNG IDAT chunk",
+  );
+  await assertRejects(
+    () => decodePNG(png.subarray(0, png.length - 20)),
+    Error,
+    "Truncated PNG",
+  );
+  await assertRejects(
+    () => decodePNG(png.subarray(1)),
+    Error,
+    "Invalid PNG signature",
+  );
+});
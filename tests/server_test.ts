@@ -0,0 +1,112 @@
+import { assert, assertEquals } from "@std/assert";
+import { Camera } from "../src/camera.ts";
+import { MockBackend } from "../src/mock.ts";
+import { serveMJPEG } from "../src/server.ts";
+import { decodeJPEG } from "./jpeg_decoder.ts";
+
+const ENCODER = new TextEncoder();
+const DECODER = new TextDecoder();
+
+/** Opens a 64x48 mock stream and serves it on a free port. */
+async function startServer() {
+  const cam = new Camera(
+    new MockBackend({
+      devices: [{
+        formats: [{ width: 64, height: 48, fourcc: "RGB3", fps: 60, bpp: 24 }],
+      }],
+    }),
+  );
+  const stream = cam.devices()[0].stream({ width: 64 })!;
+  const listening = Promise.withResolvers<Deno.NetAddr>();
+  const server = serveMJPEG(stream, {
+    port: 0,
+    quality: 90,
+    onListen: listening.resolve,
+  });
+  const addr = await listening.promise;
+  return {
+    addr,
+    url: `http://${addr.hostname}:${addr.port}`,
+    async [Symbol.asyncDispose]() {
+      await server.shutdown();
+      stream[Symbol.dispose]();
+      cam[Symbol.dispose]();
+    },
+  };
+}
+
+function assertJPEG(bytes: Uint8Array) {
+  assertEquals([bytes[0], bytes[1]], [0xFF, 0xD8], "missing SOI marker");
+  assertEquals(bytes.subarray(-2), new Uint8Array([0xFF, 0xD9]));
+  const image = decodeJPEG(bytes);
+  assertEquals([image.width, image.height], [64, 48]);
+}
+
+function indexOf(bytes: Uint8Array, search: Uint8Array, from = 0): number {
+  for (let i = from; i <= bytes.length - search.length; i++) {
+    if (search.every((byte, j) => bytes[i + j] === byte)) return i;
+  }
+  return -1;
+}
+
+Deno.test("serveMJPEG listens on localhost by default", async () => {
+  await using server = await startServer();
+  assertEquals(server.addr.hostname, "127.0.0.1");
+});
+
+Deno.test("serveMJPEG serves snapshots", async () => {
+  await using server = await startServer();
+  const response = await fetch(`${server.url}/snapshot.jpg`);
+  assertEquals(response.status, 200);
+  assertEquals(response.headers.get("content-type"), "image/jpeg");
+  assertEquals(response.headers.get("cache-control"), "no-store");
+  assertJPEG(new Uint8Array(await response.arrayBuffer()));
+});
+
+Deno.test("serveMJPEG streams multipart JPEG frames", async () => {
+  await using server = await startServer();
+  const response = await fetch(`${server.url}/`);
+  assertEquals(response.status, 200);
+  assertEquals(
+    response.headers.get("content-type"),
+    "multipart/x-mixed-replace; boundary=frame",
+  );
+
+  const reader = response.body!.getReader();
+  let buffer = new Uint8Array(0);
+  const parts = [];
+  while (parts.length < 3) {
+    const { value, done } = await reader.read();
+    assert(!done, "the stream ended");
+    const joined = new Uint8Array(buffer.length + value.length);
+    joined.set(buffer);
+    joined.set(value, buffer.length);
+    buffer = joined;
+
+    // --frame\r\n, the part headers, an empty line, the JPEG then \r\n
+    const end = indexOf(buffer, ENCODER.encode("\r\n\r\n"));
+    if (end === -1) continue;
+    const headers = DECODER.decode(buffer.subarray(0, end)).split("\r\n");
+    assertEquals(headers[0], "--frame");
+    assertEquals(headers[1], "Content-Type: image/jpeg");
+    const length = Number(headers[2].match(/^Content-Length: (\d+)$/)![1]);
+    const start = end + 4;
+    if (buffer.length < start + length + 2) continue;
+    parts.push(buffer.slice(start, start + length));
+    const separator = buffer.subarray(start + length, start + length + 2);
+    assertEquals(DECODER.decode(separator), "\r\n");
+    buffer = buffer.slice(start + length + 2);
+  }
+  await reader.cancel();
+  for (const part of parts) assertJPEG(part);
+});
+
+Deno.test("serveMJPEG rejects other paths and methods", async () => {
+  await using server = await startServer();
+  const missing = await fetch(`${server.url}/missing`);
+  assertEquals(missing.status, 404);
+  await missing.body?.cancel();
+  const post = await fetch(`${server.url}/snapshot.jpg`, { method: "POST" });
+  assertEquals([post.status, post.headers.get("allow")], [405, "GET"]);
+  await post.body?.cancel();
+});
@@ -0,0 +1,214 @@
+import { assertEquals, assertGreater, assertRejects } from "@std/assert";
+import { Frame } from "../src/frame.ts";
+import {
+  frameRateFraction,
+  y4mFrameSize,
+  Y4MReader,
+  Y4MWriter,
+  type Y4MWriterOptions,
+} from "../src/y4m.ts";
+
+const ENCODER = new TextEncoder();
+const DECODER = new TextDecoder();
+
+/** Creates an `rgb24` frame of smooth gradients, which survive subsampling. */
+function testFrame(sequence: number, width = 9, height = 5): Frame {
+  const data = new Uint8Array(width * height * 3);
+  for (let y = 0; y < height; y++) {
+    for (let x = 0; x < width; x++) {
+      const i = (y * width + x) * 3;
+      data[i] = 40 + x * 16;
+      data[i + 1] = 200 - y * 24;
+      data[i + 2] = (sequence * 60 + x * 8 + y * 8) & 0xFF;
+    }
+  }
+  return new Frame({
+    data,
+    width,
+    height,
+    pixelFormat: "rgb24",
+    timestamp: 0,
+    sequence,
+  });
+}
+
+/** Splits bytes into chunks of pseudo-random sizes from 1 to `max`. */
+function split(bytes: Uint8Array, seed: number, max: number): Uint8Array[] {
+  const chunks = [];
+  for (let offset = 0; offset < bytes.length;) {
+    seed = (Math.imul(seed, 1103515245) + 12345) >>> 0;
+    const size = 1 + (seed >>> 16) % max;
+    chunks.push(bytes.slice(offset, offset + size));
+    offset += size;
+  }
+  return chunks;
+}
+
+async function record(
+  frames: Frame[],
+  options: Y4MWriterOptions,
+): Promise<Uint8Array> {
+  const chunks: Uint8Array[] = [];
+  const sink = new WritableStream<Uint8Array>({
+    write(chunk) {
+      chunks.push(chunk.slice());
+    },
+  });
+  await ReadableStream.from(frames).pipeTo(new Y4MWriter(sink, options));
+  return new Uint8Array(await new Blob(chunks).arrayBuffer());
+}
+
+async function read(chunks: Uint8Array[]): Promise<Frame[]> {
+  const frames = [];
+  for await (const frame of new Y4MReader(ReadableStream.from(chunks))) {
+    frames.push(frame);
+  }
+  return frames;
+}
+
+/** Creates a file from its text lines followed by samples. */
+function file(lines: string, samples: number[] = []): Uint8Array {
+  return new Uint8Array([...ENCODER.encode(lines), ...samples]);
+}
+
+/** Peak signal-to-noise ratio between two images, in decibels. */
+function psnr(a: Uint8Array, b: Uint8Array): number {
+  let squares = 0;
+  for (let i = 0; i < a.length; i++) squares += (a[i] - b[i]) ** 2;
+  return 10 * Math.log10(255 ** 2 * a.length / squares);
+}
+
+function headerLine(bytes: Uint8Array): string {
+  return DECODER.decode(bytes.subarray(0, bytes.indexOf(0x0A)));
+}
+
+Deno.test("frameRateFraction keeps NTSC rates exact", () => {
+  assertEquals(frameRateFraction(30), [30, 1]);
+  assertEquals(frameRateFraction(29.97), [30000, 1001]);
+  assertEquals(frameRateFraction(30000 / 1001), [30000, 1001]);
+  assertEquals(frameRateFraction(59.94), [60000, 1001]);
+  assertEquals(frameRateFraction(23.976), [24000, 1001]);
+  assertEquals(frameRateFraction(12.5), [12500, 1000]);
+});
+
+Deno.test("Y4MWriter writes the stream header", async () => {
+  const frame = testFrame(0);
+  const ntsc = await record([frame], { width: 9, height: 5, fps: 29.97 });
+  assertEquals(
+    headerLine(ntsc),
+    "YUV4MPEG2 W9 H5 F30000:1001 Ip A1:1 C420jpeg XCOLORRANGE=LIMITED",
+  );
+  const full = await record([frame], {
+    width: 9,
+    height: 5,
+    fps: 25,
+    colorspace: "444",
+  });
+  assertEquals(
+    headerLine(full),
+    "YUV4MPEG2 W9 H5 F25:1 Ip A1:1 C444 XCOLORRANGE=LIMITED",
+  );
+
+  // an empty recording still has its header
+  const empty = await record([], { width: 9, height: 5, fps: 25 });
+  assertEquals(
+    DECODER.decode(empty),
+    "YUV4MPEG2 W9 H5 F25:1 Ip A1:1 C420jpeg XCOLORRANGE=LIMITED\n",
+  );
+
+  await assertRejects(
+    () => record([testFrame(0, 8)], { width: 9, height: 5, fps: 25 }),
+    RangeError,
+    "Frame is 8x5, expected 9x5",
+  );
+});
+
+for (const colorspace of ["420jpeg", "444"] as const) {
+  Deno.test(`Y4M round trips ${colorspace} frames`, async () => {
+    const frames = [testFrame(0), testFrame(1), testFrame(2)];
+    const bytes = await record(frames, {
+      width: 9,
+      height: 5,
+      fps: 29.97,
+      colorspace,
+    });
+
+    const reader = new Y4MReader(ReadableStream.from([bytes]));
+    const header = await reader.header;
+    assertEquals(header, {
+      width: 9,
+      height: 5,
+      frameRate: [30000, 1001],
+      fps: 30000 / 1001,
+      colorspace,
+      fullRange: false,
+    });
+    // odd sizes round the chroma planes up
+    const frameSize = colorspace === "444" ? 9 * 5 * 3 : 9 * 5 + 5 * 3 * 2;
+    assertEquals(y4mFrameSize(header), frameSize);
+    assertEquals(
+      bytes.length,
+      headerLine(bytes).length + 1 + 3 * ("FRAME\n".length + frameSize),
+    );
+
+    const decoded = [];
+    for await (const frame of reader) decoded.push(frame);
+    assertEquals(decoded.length, 3);
+    for (const [i, frame] of decoded.entries()) {
+      assertEquals(frame.pixelFormat, "rgb24");
+      assertEquals(frame.sequence, i);
+      assertEquals(frame.timestamp, i * 1001 / 30);
+      // video range quantization, plus the chroma averaging of 420jpeg
+      const minimum = colorspace === "444" ? 45 : 28;
+      assertGreater(psnr(frame.data, frames[i].data), minimum);
+    }
+  });
+}
+
+Deno.test("Y4MReader reads chunked input", async () => {
+  const frames = [testFrame(0), testFrame(1), testFrame(2)];
+  const bytes = await record(frames, { width: 9, height: 5, fps: 25 });
+  const expected = await read([bytes]);
+  assertEquals(expected.length, 3);
+  for (const [seed, max] of [[1, 1], [2, 7], [3, 64], [4, 300]]) {
+    const decoded = await read(split(bytes, seed, max));
+    assertEquals(
+      decoded.map((frame) => frame.data),
+      expected.map((frame) => frame.data),
+    );
+  }
+});
+
+Deno.test("Y4MReader reads full range and mono files", async () => {
+  const full = file(
+    "YUV4MPEG2 W2 H1 F30:1 C444 XCOLORRANGE=FULL\nFRAME Ixyz\n",
+    [100, 255, 128, 128, 128, 128],
+  );
+  const [color] = await read(split(full, 5, 3));
+  assertEquals([...color.data], [100, 100, 100, 255, 255, 255]);
+
+  const mono = file("YUV4MPEG2 W2 H1 Cmono\nFRAME\n", [16, 235]);
+  const reader = new Y4MReader(ReadableStream.from([mono]));
+  assertEquals((await reader.header).frameRate, [30, 1]);
+  const frames = [];
+  for await (const frame of reader) frames.push(frame);
+  assertEquals(frames.length, 1);
+  const [gray] = frames;
+  assertEquals(gray.pixelFormat, "gray8");
+  assertEquals([...gray.data], [0, 255]);
+});
+
+Deno.test("Y4MReader rejects malformed files", async () => {
+  const cases: [string, string][] = [
+    ["", "Empty Y4M file"],
+    ["YUV4MPEG W2 H2\n", "Invalid Y4M signature"],
+    ["YUV4MPEG2 W2\n", "Invalid Y4M frame size 2x0"],
+    ["YUV4MPEG2 W2 H2 C411\n", "Unsupported Y4M colorspace 411"],
+    ["YUV4MPEG2 W2 H2", "Truncated Y4M header"],
+    ["YUV4MPEG2 W1 H1 Cmono\nFRAME\n", "Truncated Y4M frame"],
+    ["YUV4MPEG2 W1 H1 Cmono\nFRAMES\n", "Invalid Y4M frame header"],
+  ];
+  for (const [lines, message] of cases) {
+    await assertRejects(() => read([file(lines)]), Error, message);
+  }
+});